@@ -0,0 +1,140 @@
+//! The command line: files to open, each with an optional place to start
+//! at. `+120 foo.rs` starts at line 120, as in vi, and so does
+//! `foo.rs:120`, the way compilers and grep print locations;
+//! `foo.rs:120:5` goes to column 5 as well. `--vim` makes keys work as in
+//! vi, as the `vim` setting does.
+
+use std::path::Path;
+
+pub struct FileArg {
+    pub path: String,
+    /// Line and column, both counted from 1.
+    pub line: Option<usize>,
+    pub col: Option<usize>,
+}
+
+pub struct Args {
+    pub files: Vec<FileArg>,
+    pub vim: bool,
+}
+
+/// What `args` ask for, or `None` when they don't make sense.
+pub fn parse(args: &[String]) -> Option<Args> {
+    let mut files = Vec::new();
+    let mut line = None;
+    let mut vim = false;
+    for arg in args {
+        if let Some(number) = arg.strip_prefix('+') {
+            line = Some(number.parse().ok()?);
+            continue;
+        }
+        if arg == "--vim" {
+            vim = true;
+            continue;
+        }
+        if arg.starts_with('-') {
+            return None;
+        }
+        let mut file = split_location(arg);
+        if line.is_some() {
+            file.line = line.take();
+            file.col = None;
+        }
+        files.push(file);
+    }
+    Some(Args { files, vim })
+}
+
+/// Takes `:line` and `:line:col` off the end of `arg`, unless there's a
+/// file by the whole name.
+fn split_location(arg: &str) -> FileArg {
+    let whole = FileArg { path: arg.to_string(), line: None, col: None };
+    if Path::new(arg).exists() {
+        return whole;
+    }
+    // grep and some compilers put a colon after the location too.
+    let trimmed = arg.trim_end_matches(':');
+    let mut parts = trimmed.rsplitn(3, ':');
+    let last = parts.next().and_then(|n| n.parse().ok());
+    let before = parts.next();
+    let rest = parts.next();
+    match (last, before, rest) {
+        (Some(col), Some(line), Some(path)) if !path.is_empty() && line.parse::<usize>().is_ok() => {
+            FileArg { path: path.to_string(), line: line.parse().ok(), col: Some(col) }
+        }
+        (Some(line), Some(path), None) if !path.is_empty() => {
+            FileArg { path: path.to_string(), line: Some(line), col: None }
+        }
+        (Some(line), Some(before), Some(path)) if !path.is_empty() => {
+            // A colon earlier in the name itself.
+            FileArg { path: format!("{}:{}", path, before), line: Some(line), col: None }
+        }
+        _ => whole,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs, process};
+
+    /// A file with its line and column.
+    type Place = (String, Option<usize>, Option<usize>);
+
+    fn places(args: &[&str]) -> Option<Vec<Place>> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        let files = parse(&args)?.files;
+        Some(files.into_iter().map(|f| (f.path, f.line, f.col)).collect())
+    }
+
+    fn place(path: &str, line: Option<usize>, col: Option<usize>) -> Place {
+        (path.to_string(), line, col)
+    }
+
+    #[test]
+    fn plain_names_start_at_the_top() {
+        assert_eq!(places(&[]), Some(vec![]));
+        assert_eq!(places(&["a.rs", "b.rs"]), Some(vec![place("a.rs", None, None), place("b.rs", None, None)]));
+    }
+
+    #[test]
+    fn plus_line_goes_with_the_next_file() {
+        assert_eq!(places(&["+120", "a.rs", "b.rs"]),
+                   Some(vec![place("a.rs", Some(120), None), place("b.rs", None, None)]));
+        assert_eq!(places(&["+5", "a.rs:3:4"]), Some(vec![place("a.rs", Some(5), None)]));
+        assert_eq!(places(&["+x", "a.rs"]), None);
+    }
+
+    #[test]
+    fn locations_come_off_the_end() {
+        assert_eq!(places(&["a.rs:120"]), Some(vec![place("a.rs", Some(120), None)]));
+        assert_eq!(places(&["a.rs:120:5"]), Some(vec![place("a.rs", Some(120), Some(5))]));
+        assert_eq!(places(&["a.rs:120:5:"]), Some(vec![place("a.rs", Some(120), Some(5))]));
+        assert_eq!(places(&["a:b:7"]), Some(vec![place("a:b", Some(7), None)]));
+        assert_eq!(places(&["notes:todo"]), Some(vec![place("notes:todo", None, None)]));
+        assert_eq!(places(&[":7"]), Some(vec![place(":7", None, None)]));
+    }
+
+    #[test]
+    fn a_file_by_the_whole_name_wins() {
+        let path = env::temp_dir().join(format!("kilo-args-{}:3", process::id()));
+        fs::write(&path, "").unwrap();
+        let name = path.to_str().unwrap();
+        let found = places(&[name]);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(found, Some(vec![place(name, None, None)]));
+    }
+
+    #[test]
+    fn unknown_options_are_refused() {
+        assert_eq!(places(&["-x", "a.rs"]), None);
+    }
+
+    #[test]
+    fn vim_is_an_option() {
+        let args = parse(&["--vim".to_string(), "a.rs".to_string()]).unwrap();
+        assert!(args.vim);
+        assert_eq!(args.files.len(), 1);
+        assert!(!parse(&["a.rs".to_string()]).unwrap().vim);
+    }
+}
@@ -0,0 +1,85 @@
+//! Saving without ever leaving a file cut short. The text goes to a
+//! temporary file next to the original, is synced to disk, then renamed
+//! over it, so a crash midway leaves either the old file or the new one.
+//!
+//! Where that can't be done -- a directory we can't create files in, a file
+//! with other hard links or owned by someone else, a filesystem that won't
+//! rename over a mount point -- the file is written in place as before,
+//! and the caller is told why.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use libc;
+
+pub struct Saved {
+    pub bytes: usize,
+    /// Why the file had to be written in place, if it did.
+    pub in_place: Option<io::Error>,
+}
+
+/// Writes `data` to `path`, replacing the file atomically when possible.
+pub fn save(path: &Path, data: &[u8]) -> io::Result<Saved> {
+    // Through a symlink the file it points at is replaced, not the link.
+    let target = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    match replace(&target, data) {
+        Ok(()) => Ok(Saved { bytes: data.len(), in_place: None }),
+        Err(e) => {
+            write_in_place(&target, data)?;
+            Ok(Saved { bytes: data.len(), in_place: Some(e) })
+        }
+    }
+}
+
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path.file_name().map_or("kilo".into(), |n| n.to_string_lossy());
+    path.with_file_name(format!(".{}.kilo-save-{}", name, process::id()))
+}
+
+fn replace(path: &Path, data: &[u8]) -> io::Result<()> {
+    let old = match fs::metadata(path) {
+        Ok(meta) => Some(meta),
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e),
+    };
+    if old.as_ref().is_some_and(|meta| meta.nlink() > 1) {
+        // A new file would split it from its other names.
+        return Err(io::Error::other("file has other hard links"));
+    }
+    let temp = temp_path(path);
+    let mut file = OpenOptions::new().write(true).create_new(true).open(&temp)?;
+    let res = (|| {
+        if let Some(ref meta) = old {
+            file.set_permissions(meta.permissions())?;
+            let owner = (meta.uid(), meta.gid());
+            if owner != unsafe { (libc::geteuid(), libc::getegid()) } &&
+                unsafe { libc::fchown(file.as_raw_fd(), meta.uid(), meta.gid()) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        file.write_all(data)?;
+        file.sync_all()?;
+        fs::rename(&temp, path)
+    })();
+    if res.is_err() {
+        let _ = fs::remove_file(&temp);
+        return res;
+    }
+    // Sync the directory too, or the rename itself may be lost in a crash.
+    if let Some(dir) = path.parent() {
+        if let Ok(dir) = File::open(dir) {
+            let _ = dir.sync_all();
+        }
+    }
+    Ok(())
+}
+
+fn write_in_place(path: &Path, data: &[u8]) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    file.write_all(data)?;
+    file.sync_all()
+}
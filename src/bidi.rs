@@ -0,0 +1,52 @@
+//! Display order for lines holding right-to-left text. Terminals draw
+//! characters left to right in the order they're sent, so Hebrew and Arabic
+//! runs have to be reordered by the Unicode bidi algorithm before drawing.
+
+use unicode_bidi::BidiInfo;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// The order the graphemes of `line` are drawn in, as indexes into them in
+/// text order, or `None` when it has no right-to-left text and the two
+/// are the same. Graphemes are moved whole, so marks stay on their base.
+pub fn visual(line: &str) -> Option<Vec<usize>> {
+    let info = BidiInfo::new(line, None);
+    if !info.has_rtl() {
+        return None;
+    }
+    let para = info.paragraphs.first()?;
+    let (levels, runs) = info.visual_runs(para, para.range.clone());
+
+    let starts: Vec<usize> = line.grapheme_indices(true).map(|(i, _)| i).collect();
+    let mut order = Vec::with_capacity(starts.len());
+    for run in runs {
+        let graphemes = starts.partition_point(|&i| i < run.start)..starts.partition_point(|&i| i < run.end);
+        if levels[run.start].is_rtl() {
+            order.extend(graphemes.rev());
+        } else {
+            order.extend(graphemes);
+        }
+    }
+    Some(order)
+}
+
+/// The screen column, counted from the start of the line, that the
+/// grapheme of `line` at text column `column` is drawn at when put in
+/// `order`. The end of the line stays after the last grapheme.
+pub fn display_column(line: &str, order: &[usize], column: usize) -> usize {
+    let graphemes: Vec<&str> = line.graphemes(true).collect();
+    let mut starts = Vec::with_capacity(graphemes.len());
+    let mut col = 0;
+    for g in &graphemes {
+        starts.push(col);
+        col += g.width();
+    }
+    let mut x = 0;
+    for &k in order {
+        if starts[k] <= column && column < starts[k] + graphemes[k].width().max(1) {
+            return x;
+        }
+        x += graphemes[k].width();
+    }
+    x
+}
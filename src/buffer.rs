@@ -0,0 +1,233 @@
+//! Buffers: the one being edited lives in the editor itself, the others
+//! are put aside and swapped in when switched to.
+
+use std::{env, mem};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use syntax::Highlighter;
+use config::Config;
+use undo::History;
+use hex::HexView;
+use loader::Loader;
+use lsp::Diagnostic;
+use signs::SignRegistry;
+use text::Rows;
+use editor::Editor;
+
+/// A buffer put aside while another one is being edited.
+pub(crate) struct Buffer {
+    pub(crate) rows: Rows,
+    pub(crate) filename: Option<String>,
+    pub(crate) scratch: bool,
+    pub(crate) dirty: bool,
+    pub(crate) file_stamp: Option<(SystemTime, u64)>,
+    pub(crate) cx: usize,
+    pub(crate) cy: usize,
+    pub(crate) rowoff: usize,
+    pub(crate) coloff: usize,
+    pub(crate) anchor: Option<(usize, usize)>,
+    pub(crate) history: History,
+    pub(crate) signs: SignRegistry,
+    pub(crate) mark_columns: HashMap<char, usize>,
+    pub(crate) lock: Option<PathBuf>,
+    pub(crate) swap: Option<PathBuf>,
+    pub(crate) read_only: bool,
+    pub(crate) loading: Option<Loader>,
+    /// Settings for this buffer, changed by commands since it was opened.
+    pub(crate) config: Config,
+    pub(crate) tab_stop: usize,
+    pub(crate) crlf: bool,
+    pub(crate) hex: Option<HexView>,
+    pub(crate) listing: bool,
+    pub(crate) locations: bool,
+    pub(crate) diagnostics: Vec<Diagnostic>,
+}
+
+/// Keys that move the view back from the end of the buffer.
+/// A buffer's path relative to the working directory when it lies below
+/// it.
+pub(crate) fn display_name(filename: &Option<String>, scratch: bool) -> String {
+    let filename = match *filename {
+        Some(ref f) => f,
+        None if scratch => return tr!(Scratch),
+        None => return tr!(NoName),
+    };
+    let path = Path::new(filename);
+    match env::current_dir() {
+        Ok(ref cwd) if path.starts_with(cwd) => {
+            path.strip_prefix(cwd).unwrap().to_string_lossy().into_owned()
+        }
+        _ => filename.clone(),
+    }
+}
+
+impl Editor {
+    pub(crate) fn rows_to_string(&self) -> String {
+        self.rows.join("\n") + "\n"
+    }
+
+    /// The text as it goes into the file, with the file's line endings.
+    pub(crate) fn file_text(&self) -> String {
+        let eol = if self.crlf { "\r\n" } else { "\n" };
+        self.rows.join(eol) + eol
+    }
+
+    /// What saving writes: the text, or the bytes in the hex view.
+    pub(crate) fn file_bytes(&self) -> Vec<u8> {
+        match self.hex {
+            Some(ref hex) => hex.bytes.clone(),
+            None => self.file_text().into_bytes(),
+        }
+    }
+
+    pub(crate) fn display_name(&self) -> String {
+        display_name(&self.filename, self.scratch)
+    }
+
+    /// Whether any buffer has changes that would be lost on quitting.
+    pub(crate) fn unsaved(&self) -> bool {
+        (self.dirty && !self.scratch) || self.buffers.iter().any(|b| b.dirty && !b.scratch)
+    }
+
+    /// Moves the current buffer out of the editor, leaving an empty one.
+    pub(crate) fn take_buffer(&mut self) -> Buffer {
+        // Nothing is written for a buffer put aside, so catch up now.
+        self.update_swap(true);
+        let history = History::new(self.history.max_edits, self.history.max_bytes);
+        let buffer = Buffer {
+            rows: mem::replace(&mut self.rows, Rows::new()),
+            filename: self.filename.take(),
+            scratch: self.scratch,
+            dirty: self.dirty,
+            file_stamp: self.file_stamp.take(),
+            cx: self.cx,
+            cy: self.cy,
+            rowoff: self.rowoff,
+            coloff: self.coloff,
+            anchor: self.anchor.take(),
+            history: mem::replace(&mut self.history, history),
+            signs: mem::take(&mut self.signs),
+            mark_columns: mem::take(&mut self.mark_columns),
+            lock: self.lock.take(),
+            swap: self.swap.take(),
+            read_only: self.read_only,
+            loading: self.loading.take(),
+            config: self.config.clone(),
+            tab_stop: self.tab_stop,
+            crlf: self.crlf,
+            hex: self.hex.take(),
+            listing: self.listing,
+            locations: self.locations,
+            diagnostics: mem::take(&mut self.diagnostics),
+        };
+        self.read_only = false;
+        self.crlf = false;
+        self.listing = false;
+        self.locations = false;
+        self.blame = None;
+        self.completion_menu = None;
+        self.snippet = None;
+        self.scratch = false;
+        self.dirty = false;
+        self.follow = None;
+        self.highlighter = Highlighter::new(None);
+        self.cx = 0;
+        self.cy = 0;
+        self.rowoff = 0;
+        self.coloff = 0;
+        buffer
+    }
+
+    pub(crate) fn put_buffer(&mut self, buffer: Buffer) {
+        self.rows = buffer.rows;
+        self.filename = buffer.filename;
+        self.scratch = buffer.scratch;
+        self.dirty = buffer.dirty;
+        self.file_stamp = buffer.file_stamp;
+        self.cx = buffer.cx;
+        self.cy = buffer.cy;
+        self.rowoff = buffer.rowoff;
+        self.coloff = buffer.coloff;
+        self.anchor = buffer.anchor;
+        self.history = buffer.history;
+        self.signs = buffer.signs;
+        self.mark_columns = buffer.mark_columns;
+        self.lock = buffer.lock;
+        self.swap = buffer.swap;
+        self.read_only = buffer.read_only;
+        self.loading = buffer.loading;
+        self.config = buffer.config;
+        self.tab_stop = buffer.tab_stop;
+        self.crlf = buffer.crlf;
+        self.hex = buffer.hex;
+        self.listing = buffer.listing;
+        self.locations = buffer.locations;
+        self.diagnostics = buffer.diagnostics;
+        self.select_syntax();
+    }
+
+    /// Keeps `buffer` in the tab before the current one.
+    pub(crate) fn set_aside(&mut self, buffer: Buffer) {
+        self.buffers.push(buffer);
+        self.tab += 1;
+    }
+
+    pub(crate) fn new_scratch(&mut self) {
+        let buffer = self.take_buffer();
+        self.set_aside(buffer);
+        self.scratch = true;
+    }
+
+    pub(crate) fn next_buffer(&mut self) {
+        if self.buffers.is_empty() {
+            self.set_status_msg(tr!(OnlyBuffer));
+            return;
+        }
+        let next = self.buffers.remove(0);
+        let current = self.take_buffer();
+        self.buffers.push(current);
+        self.put_buffer(next);
+        self.tab = (self.tab + 1) % (self.buffers.len() + 1);
+    }
+
+    pub(crate) fn prev_buffer(&mut self) {
+        let prev = match self.buffers.pop() {
+            Some(buffer) => buffer,
+            None => {
+                self.set_status_msg(tr!(OnlyBuffer));
+                return;
+            }
+        };
+        let current = self.take_buffer();
+        self.buffers.insert(0, current);
+        self.put_buffer(prev);
+        self.tab = (self.tab + self.buffers.len()) % (self.buffers.len() + 1);
+    }
+
+    /// Switches to the buffer of the file at `path`, returning whether
+    /// there is one.
+    pub(crate) fn switch_to_file(&mut self, path: &str) -> bool {
+        let is_path = |filename: &Option<String>| filename.as_ref().map(|f| f.as_str()) == Some(path);
+        if is_path(&self.filename) {
+            return true;
+        }
+        let split = self.buffers.len() - self.tab;
+        match self.buffers.iter().position(|b| is_path(&b.filename)) {
+            Some(i) => {
+                let tab = if i >= split { i - split } else { self.tab + 1 + i };
+                self.switch_to_tab(tab);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub(crate) fn switch_to_tab(&mut self, tab: usize) {
+        let tabs = self.buffers.len() + 1;
+        for _ in 0..(tab + tabs - self.tab) % tabs {
+            self.next_buffer();
+        }
+    }
+}
@@ -0,0 +1,215 @@
+//! A small calculator for `+ - * / %` and parentheses over integers,
+//! floats and `0x` hex literals. Integer arithmetic stays exact while it
+//! can; a division that doesn't come out even gives a float.
+
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+    Int(i64),
+    Float(f64),
+}
+
+impl Value {
+    fn as_float(self) -> f64 {
+        match self {
+            Value::Int(i) => i as f64,
+            Value::Float(f) => f,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Value::Int(i) => write!(f, "{}", i),
+            Value::Float(x) => write!(f, "{}", x),
+        }
+    }
+}
+
+/// The result of evaluating an expression. When any hex literal was used
+/// an integer result is shown in hex too.
+pub struct Answer {
+    pub value: Value,
+    pub hex: bool,
+}
+
+impl fmt::Display for Answer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.value {
+            Value::Int(i) if self.hex && i >= 0 => write!(f, "{:#x}", i),
+            Value::Int(i) if self.hex => write!(f, "-{:#x}", -(i as i128)),
+            value => write!(f, "{}", value),
+        }
+    }
+}
+
+pub fn eval(expr: &str) -> Result<Answer, String> {
+    let mut parser = Parser { input: expr.as_bytes(), pos: 0, hex: false };
+    let value = parser.sum()?;
+    parser.skip_space();
+    if parser.pos < parser.input.len() {
+        return Err(format!("unexpected '{}'", parser.input[parser.pos] as char));
+    }
+    Ok(Answer { value, hex: parser.hex })
+}
+
+struct Parser<'a> {
+    input: &'a [u8],
+    pos: usize,
+    hex: bool,
+}
+
+impl<'a> Parser<'a> {
+    fn skip_space(&mut self) {
+        while self.pos < self.input.len() && self.input[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+    }
+
+    fn next_op(&mut self, ops: &[u8]) -> Option<u8> {
+        self.skip_space();
+        let c = *self.input.get(self.pos)?;
+        if ops.contains(&c) {
+            self.pos += 1;
+            Some(c)
+        } else {
+            None
+        }
+    }
+
+    fn sum(&mut self) -> Result<Value, String> {
+        let mut value = self.product()?;
+        while let Some(op) = self.next_op(b"+-") {
+            let rhs = self.product()?;
+            value = apply(op, value, rhs)?;
+        }
+        Ok(value)
+    }
+
+    fn product(&mut self) -> Result<Value, String> {
+        let mut value = self.unary()?;
+        while let Some(op) = self.next_op(b"*/%") {
+            let rhs = self.unary()?;
+            value = apply(op, value, rhs)?;
+        }
+        Ok(value)
+    }
+
+    fn unary(&mut self) -> Result<Value, String> {
+        match self.next_op(b"-+") {
+            Some(b'-') => match self.unary()? {
+                Value::Int(i) => i.checked_neg().map(Value::Int).ok_or_else(overflow),
+                Value::Float(f) => Ok(Value::Float(-f)),
+            },
+            Some(_) => self.unary(),
+            None => self.atom(),
+        }
+    }
+
+    fn atom(&mut self) -> Result<Value, String> {
+        if self.next_op(b"(").is_some() {
+            let value = self.sum()?;
+            return match self.next_op(b")") {
+                Some(_) => Ok(value),
+                None => Err("missing ')'".to_string()),
+            };
+        }
+        let rest = &self.input[self.pos..];
+        if rest.starts_with(b"0x") || rest.starts_with(b"0X") {
+            let len = rest[2..].iter().take_while(|b| b.is_ascii_hexdigit()).count();
+            let digits = String::from_utf8_lossy(&rest[2..2 + len]).into_owned();
+            self.pos += 2 + len;
+            self.hex = true;
+            return i64::from_str_radix(&digits, 16).map(Value::Int).map_err(|e| e.to_string());
+        }
+        let len = rest.iter().take_while(|b| b.is_ascii_digit() || **b == b'.').count();
+        if len == 0 {
+            return Err(match rest.first() {
+                Some(&c) => format!("unexpected '{}'", c as char),
+                None => "unexpected end".to_string(),
+            });
+        }
+        let number = String::from_utf8_lossy(&rest[..len]).into_owned();
+        self.pos += len;
+        if number.contains('.') {
+            number.parse().map(Value::Float).map_err(|_| format!("bad number '{}'", number))
+        } else {
+            number.parse().map(Value::Int).map_err(|_| format!("bad number '{}'", number))
+        }
+    }
+}
+
+fn overflow() -> String {
+    "overflow".to_string()
+}
+
+fn apply(op: u8, lhs: Value, rhs: Value) -> Result<Value, String> {
+    if let (Value::Int(a), Value::Int(b)) = (lhs, rhs) {
+        let res = match op {
+            b'+' => a.checked_add(b),
+            b'-' => a.checked_sub(b),
+            b'*' => a.checked_mul(b),
+            b'/' | b'%' if b == 0 => return Err("division by zero".to_string()),
+            b'/' if a % b != 0 => return Ok(Value::Float(a as f64 / b as f64)),
+            b'/' => a.checked_div(b),
+            _ => a.checked_rem(b),
+        };
+        return res.map(Value::Int).ok_or_else(overflow);
+    }
+    let (a, b) = (lhs.as_float(), rhs.as_float());
+    Ok(Value::Float(match op {
+        b'+' => a + b,
+        b'-' => a - b,
+        b'*' => a * b,
+        b'/' => a / b,
+        _ => a % b,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn show(expr: &str) -> String {
+        match eval(expr) {
+            Ok(answer) => answer.to_string(),
+            Err(e) => format!("error: {}", e),
+        }
+    }
+
+    #[test]
+    fn precedence_and_parentheses() {
+        assert_eq!(show("1 + 2 * 3"), "7");
+        assert_eq!(show("(1 + 2) * 3"), "9");
+        assert_eq!(show("10 - 4 - 3"), "3");
+        assert_eq!(show("-(2 + 3) * -2"), "10");
+        assert_eq!(show("17 % 5"), "2");
+    }
+
+    #[test]
+    fn uneven_division_gives_a_float() {
+        assert_eq!(eval("6 / 3").unwrap().value, Value::Int(2));
+        assert_eq!(eval("7 / 2").unwrap().value, Value::Float(3.5));
+        assert_eq!(eval("1.5 * 2").unwrap().value, Value::Float(3.0));
+    }
+
+    #[test]
+    fn hex_literals_give_hex_answers() {
+        assert_eq!(show("0x10 + 1"), "0x11");
+        assert_eq!(show("0x1 - 3"), "-0x2");
+        assert_eq!(show("0xff / 2"), "127.5");
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(show("1 / 0"), "error: division by zero");
+        assert_eq!(show("5 % 0"), "error: division by zero");
+        assert_eq!(show("9223372036854775807 + 1"), "error: overflow");
+        assert_eq!(show("-(-9223372036854775807 - 1)"), "error: overflow");
+        assert_eq!(show("(1 + 2"), "error: missing ')'");
+        assert_eq!(show("1 +"), "error: unexpected end");
+        assert_eq!(show("2 x"), "error: unexpected 'x'");
+    }
+}
@@ -0,0 +1,192 @@
+//! The system clipboard. Where the terminal supports it, OSC 52 reaches the
+//! clipboard through the terminal, so copying works over SSH too; pastes
+//! then come back as bracketed pastes rather than by asking the terminal
+//! for the clipboard, which most terminals refuse. Otherwise the usual
+//! command line tools are run: wl-copy, xclip, xsel or pbcopy.
+//!
+//! `clipboard` in the config picks one by name; by default it's a tool for
+//! the display in use if one is installed, and OSC 52 failing that.
+
+use std::env;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::process::{Command, Stdio};
+
+/// Terminals drop longer OSC 52 sequences, some silently.
+pub const OSC52_MAX: usize = 100_000;
+
+pub enum Error {
+    /// Too much text for the clipboard to take.
+    TooLarge,
+    Io(io::Error),
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Io(e)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::TooLarge => write!(f, "too large"),
+            Error::Io(ref e) => e.fmt(f),
+        }
+    }
+}
+
+pub trait Clipboard {
+    /// Puts `text` on the clipboard. `terminal` is where escape sequences
+    /// go, for backends that talk to the terminal.
+    fn copy(&self, text: &str, terminal: &mut dyn Write) -> Result<(), Error>;
+
+    /// What's on the clipboard, or `None` if it can't be read this way.
+    fn paste(&self) -> Option<io::Result<String>>;
+}
+
+pub struct Osc52;
+
+impl Clipboard for Osc52 {
+    fn copy(&self, text: &str, terminal: &mut dyn Write) -> Result<(), Error> {
+        let seq = osc52(text).ok_or(Error::TooLarge)?;
+        terminal.write_all(seq.as_bytes())?;
+        terminal.flush()?;
+        Ok(())
+    }
+
+    fn paste(&self) -> Option<io::Result<String>> {
+        None
+    }
+}
+
+/// A pair of programs that write stdin to the clipboard and the clipboard
+/// to stdout.
+pub struct Tool {
+    name: &'static str,
+    copy: &'static [&'static str],
+    paste: &'static [&'static str],
+}
+
+const TOOLS: &[Tool] = &[
+    Tool { name: "wl-copy", copy: &["wl-copy"], paste: &["wl-paste", "--no-newline"] },
+    Tool { name: "xclip", copy: &["xclip", "-selection", "clipboard"], paste: &["xclip", "-selection", "clipboard", "-o"] },
+    Tool { name: "xsel", copy: &["xsel", "--clipboard", "--input"], paste: &["xsel", "--clipboard", "--output"] },
+    Tool { name: "pbcopy", copy: &["pbcopy"], paste: &["pbpaste"] },
+];
+
+impl Clipboard for &'static Tool {
+    fn copy(&self, text: &str, _: &mut dyn Write) -> Result<(), Error> {
+        // The X tools stay around to serve the selection, so nothing waits
+        // on their output.
+        let mut child = Command::new(self.copy[0])
+            .args(&self.copy[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+        child.stdin.take().expect("piped stdin").write_all(text.as_bytes())?;
+        let status = child.wait()?;
+        if !status.success() {
+            return Err(Error::Io(io::Error::other(format!("{} {}", self.name, status))));
+        }
+        Ok(())
+    }
+
+    fn paste(&self) -> Option<io::Result<String>> {
+        let run = || {
+            let mut child = Command::new(self.paste[0])
+                .args(&self.paste[1..])
+                .stdin(Stdio::null())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn()?;
+            let mut text = String::new();
+            child.stdout.take().expect("piped stdout").read_to_string(&mut text)?;
+            child.wait()?;
+            Ok(text)
+        };
+        Some(run())
+    }
+}
+
+fn on_path(program: &str) -> bool {
+    env::var_os("PATH").is_some_and(|path| env::split_paths(&path).any(|dir| dir.join(program).is_file()))
+}
+
+fn tool(name: &str) -> Option<&'static Tool> {
+    TOOLS.iter().find(|tool| tool.name == name)
+}
+
+/// The names `by_name` knows, besides `"auto"` and `"off"`.
+pub fn names() -> Vec<&'static str> {
+    let mut names = vec!["osc52"];
+    names.extend(TOOLS.iter().map(|tool| tool.name));
+    names
+}
+
+/// The backend called `name`, or `None` for `"off"` and unknown names.
+pub fn by_name(name: &str) -> Option<Box<dyn Clipboard>> {
+    match name {
+        "auto" => Some(detect()),
+        "osc52" => Some(Box::new(Osc52)),
+        name => tool(name).map(|tool| Box::new(tool) as Box<dyn Clipboard>),
+    }
+}
+
+/// A tool for the display in use if one is installed, OSC 52 otherwise.
+pub fn detect() -> Box<dyn Clipboard> {
+    let wanted: &[&str] = if env::var_os("WAYLAND_DISPLAY").is_some() {
+        &["wl-copy", "xclip", "xsel"]
+    } else if env::var_os("DISPLAY").is_some() {
+        &["xclip", "xsel"]
+    } else if cfg!(target_os = "macos") && env::var_os("SSH_TTY").is_none() {
+        &["pbcopy"]
+    } else {
+        &[]
+    };
+    match wanted.iter().filter_map(|&name| tool(name)).find(|tool| on_path(tool.copy[0]) && on_path(tool.paste[0])) {
+        Some(tool) => Box::new(tool),
+        None => Box::new(Osc52),
+    }
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// The sequence that puts `text` on the clipboard, or `None` when it's too
+/// long for terminals to take. Inside tmux it is wrapped to reach the
+/// terminal outside.
+pub fn osc52(text: &str) -> Option<String> {
+    let encoded = base64(text.as_bytes());
+    if encoded.len() > OSC52_MAX {
+        return None;
+    }
+    let seq = format!("\x1b]52;c;{}\x07", encoded);
+    if env::var_os("TMUX").is_some() {
+        Some(format!("\x1bPtmux;{}\x1b\\", seq.replace('\x1b', "\x1b\x1b")))
+    } else {
+        Some(seq)
+    }
+}
+
+/// Text from a bracketed paste with the terminal's carriage returns made
+/// into newlines.
+pub fn pasted_text(raw: &[u8]) -> String {
+    String::from_utf8_lossy(raw).replace("\r\n", "\n").replace('\r', "\n")
+}
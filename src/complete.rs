@@ -0,0 +1,224 @@
+//! Completing the word before the cursor from words already typed. Ctrl-N
+//! puts in the nearest word starting the same way; pressing it again swaps
+//! that for the next one, coming back round to what was typed.
+//!
+//! Where the buffer has a language server that can complete, typing a word
+//! asks it what could go there, and what it offers pops up under the
+//! cursor, narrowed as more is typed. Up and Down move through the list,
+//! Enter or Tab puts in the one picked and Esc closes it.
+
+use edit::is_word_char;
+use editor::Editor;
+use input::Key;
+use keymap::Action;
+use text::Rows;
+
+/// Where a round of completion is up to.
+pub(crate) struct WordCompletion {
+    /// Where the word being completed starts.
+    start: (usize, usize),
+    prefix: String,
+    candidates: Vec<String>,
+    /// The candidate in the buffer now; one past the last is the prefix.
+    index: usize,
+}
+
+/// The words in `rows`, nearest `near` first, then in order.
+fn words_in(rows: &Rows, near: usize, words: &mut Vec<String>) {
+    let below = (near..rows.len()).map(|y| (y - near, y));
+    let above = (0..near.min(rows.len())).rev().map(|y| (near - y, y));
+    let mut order: Vec<(usize, usize)> = below.chain(above).collect();
+    order.sort();
+    for (_, y) in order {
+        for word in rows[y].split(|c| !is_word_char(c)) {
+            if !word.is_empty() && !word.chars().next().is_some_and(|c| c.is_numeric()) {
+                words.push(word.to_string());
+            }
+        }
+    }
+}
+
+/// Something a language server offers to put in at the cursor.
+pub(crate) struct CompletionItem {
+    pub label: String,
+    /// What sort of thing it is, as `function` or `field`.
+    pub kind: &'static str,
+    pub detail: String,
+    /// The text put in.
+    pub text: String,
+    /// Where the text put in starts when the server said, as (row, byte);
+    /// it runs to the cursor. Otherwise it replaces the word there.
+    pub start: Option<(usize, usize)>,
+    /// What's typed is matched against this.
+    pub filter: String,
+}
+
+/// The server's completions, shown under the cursor as the word they
+/// complete is typed.
+pub(crate) struct CompletionMenu {
+    /// Where the word being completed starts.
+    pub start: (usize, usize),
+    items: Vec<CompletionItem>,
+    /// The items matching what's typed so far.
+    pub shown: Vec<usize>,
+    pub selected: usize,
+}
+
+impl CompletionMenu {
+    /// The items shown, in order.
+    pub fn shown(&self) -> impl Iterator<Item = &CompletionItem> {
+        self.shown.iter().map(move |&i| &self.items[i])
+    }
+
+    /// Shows only the items matching `prefix`, ignoring case.
+    fn filter(&mut self, prefix: &str) {
+        let prefix = prefix.to_lowercase();
+        let items = &self.items;
+        self.shown = (0..items.len()).filter(|&i| items[i].filter.to_lowercase().starts_with(&prefix)).collect();
+        self.selected = self.selected.min(self.shown.len().saturating_sub(1));
+    }
+}
+
+/// Where the word ending at byte `x` of `row` starts.
+fn word_start(row: &str, x: usize) -> usize {
+    row[..x].rfind(|c| !is_word_char(c))
+        .map_or(0, |i| i + row[i..].chars().next().map_or(1, |c| c.len_utf8()))
+}
+
+impl Editor {
+    /// The words starting with `prefix` in the open buffers, the current
+    /// one first and nearest the cursor first within it.
+    pub(crate) fn word_candidates(&self, prefix: &str) -> Vec<String> {
+        let mut words = Vec::new();
+        words_in(&self.rows, self.cy, &mut words);
+        for buffer in &self.buffers {
+            words_in(&buffer.rows, buffer.cy, &mut words);
+        }
+        let mut candidates: Vec<String> = Vec::new();
+        for word in words {
+            if word.len() > prefix.len() && word.starts_with(prefix) && !candidates.contains(&word) {
+                candidates.push(word);
+            }
+        }
+        candidates
+    }
+
+    /// Completes the word before the cursor, or straight after a
+    /// completion, swaps it for the next candidate.
+    pub(crate) fn complete_word(&mut self) {
+        let again = self.last_action == Some(Action::CompleteWord) &&
+            self.word_completion.as_ref().is_some_and(|c| c.start.0 == self.cy);
+        if !again {
+            let row = match self.rows.get(self.cy) {
+                Some(row) => row,
+                None => return,
+            };
+            let start = self.word_before().1;
+            let prefix = row[start..self.cx].to_string();
+            if prefix.is_empty() {
+                self.set_status_msg(tr!(NoCompletions));
+                return;
+            }
+            let candidates = self.word_candidates(&prefix);
+            if candidates.is_empty() {
+                self.set_status_msg(tr!(NoCompletions));
+                return;
+            }
+            self.word_completion = Some(WordCompletion {
+                start: (self.cy, start),
+                prefix: prefix.clone(),
+                candidates,
+                // Nothing but the prefix is in yet.
+                index: usize::MAX,
+            });
+        }
+        let (start, text, index, count) = {
+            let completion = self.word_completion.as_mut().unwrap();
+            completion.index = completion.index.wrapping_add(1) % (completion.candidates.len() + 1);
+            let text = completion.candidates.get(completion.index).unwrap_or(&completion.prefix).clone();
+            (completion.start, text, completion.index, completion.candidates.len())
+        };
+        self.replace_region(start, (self.cy, self.cx), &text);
+        if index < count {
+            self.set_status_msg(tr!(WordCompletion, index + 1, count));
+        } else {
+            self.set_status_msg(tr!(WordCompletionOriginal));
+        }
+    }
+
+    /// Where the word before the cursor starts.
+    pub(crate) fn word_before(&self) -> (usize, usize) {
+        (self.cy, self.rows.get(self.cy).map_or(0, |row| word_start(row, self.cx)))
+    }
+
+    /// Shows the server's completions for the word the cursor is in.
+    pub(crate) fn show_completions(&mut self, items: Vec<CompletionItem>) {
+        let start = self.word_before();
+        let mut menu = CompletionMenu { start, items, shown: Vec::new(), selected: 0 };
+        menu.filter(&self.rows[self.cy][start.1..self.cx]);
+        self.completion_menu = if menu.shown.is_empty() { None } else { Some(menu) };
+    }
+
+    /// Handles a key meant for the completion menu: moving through it,
+    /// taking an item or closing it. Anything else goes on to the text.
+    pub(crate) fn completion_key(&mut self, c: Key, action: Option<Action>) -> bool {
+        let menu = match self.completion_menu {
+            Some(ref mut menu) => menu,
+            None => return false,
+        };
+        let count = menu.shown.len();
+        match c {
+            Key::Up | Key::Ctrl(b'p') => menu.selected = (menu.selected + count - 1) % count,
+            Key::Down => menu.selected = (menu.selected + 1) % count,
+            _ if action == Some(Action::CompleteWord) => menu.selected = (menu.selected + 1) % count,
+            Key::Return | Key::Ctrl(b'i') => self.accept_completion(),
+            Key::Char(b'\x1b') => self.completion_menu = None,
+            _ => return false,
+        }
+        true
+    }
+
+    /// Puts in the selected completion in place of what it completes, as
+    /// one undoable edit.
+    fn accept_completion(&mut self) {
+        let menu = match self.completion_menu.take() {
+            Some(menu) => menu,
+            None => return,
+        };
+        let item = &menu.items[menu.shown[menu.selected]];
+        let start = match item.start {
+            Some(start) if start.0 == self.cy && start.1 <= self.cx => start,
+            _ => menu.start,
+        };
+        if self.read_only {
+            self.set_status_msg(tr!(ReadOnly));
+            return;
+        }
+        self.replace_region(start, (self.cy, self.cx), &item.text);
+    }
+
+    /// After a key, narrows the completion menu to what's typed, or closes
+    /// it once the cursor has left the word; and asks the server for
+    /// completions when a word is being typed.
+    pub(crate) fn update_completion(&mut self, c: Key, action: Option<Action>) {
+        let row = self.rows.get(self.cy).map_or("", |row| row.as_str());
+        if let Some(mut menu) = self.completion_menu.take() {
+            let (y, x) = menu.start;
+            if y == self.cy && x <= self.cx && self.cx <= row.len() && row[x..self.cx].chars().all(is_word_char) {
+                menu.filter(&row[x..self.cx]);
+                if !menu.shown.is_empty() {
+                    self.completion_menu = Some(menu);
+                }
+            }
+        }
+        let typed = match (c, action) {
+            (Key::Char(c), None) if c >= 0x20 && c != 0x7f => row[..self.cx].chars().next_back(),
+            _ => None,
+        };
+        if let Some(typed) = typed {
+            if self.completion_menu.is_none() || !is_word_char(typed) {
+                self.lsp_complete(typed);
+            }
+        }
+    }
+}
@@ -0,0 +1,432 @@
+//! Settings read from TOML files. The global settings are read at startup
+//! from `~/.config/kilo/config.toml`; a `.kilo.toml` found by searching
+//! upward from the opened file is merged over them, so the conventions of
+//! a repository apply to every file in it. Anything not given keeps its
+//! built-in default.
+//!
+//! ```toml
+//! tab_stop = 4
+//! quit_times = 1
+//! status_timeout = 10
+//! line_numbers = "relative"
+//! syntax = true
+//! clipboard = "osc52"
+//! tab_bar = true
+//! indent_width = 4
+//! expand_tab = true
+//! auto_indent = true
+//! auto_pairs = true
+//! detect_indent = false
+//! show_trailing = true
+//! show_whitespace = true
+//! strip_trailing = true
+//! text_width = 100
+//! formatter = "rustfmt --emit stdout"
+//! autosave = 30
+//! exclude = ["target", "node_modules"]
+//! status_left = ["name", "modified"]
+//! status_right = ["filetype", "column", "position"]
+//! vim = true
+//! save_on_blur = true
+//! smooth_scroll = true
+//! normalize = false
+//! memory_limit = 4096
+//! alt_screen = false
+//! screen_reader = true
+//! undo_edits = 10000
+//! undo_bytes = 67108864
+//!
+//! [keys]
+//! "alt-q" = "quit"
+//!
+//! [filetype.python]
+//! indent_width = 4
+//! expand_tab = true
+//!
+//! [filetype.make]
+//! expand_tab = false
+//!
+//! [filetype.rust]
+//! language_server = "rust-analyzer"
+//! ```
+//!
+//! A `[filetype.NAME]` table applies over the rest to files of that
+//! filetype, named as in the highlighting database (`c`, `rust`, ...), by
+//! extension without the dot, or by file name.
+//!
+//! `alt_screen`, `screen_reader`, `undo_edits` and `undo_bytes` are only
+//! read from the global settings, at startup. The `KILO_*` environment
+//! variables that came before them still work, and win over the files.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use toml::Value;
+use toml::value::Table;
+
+use clipboard;
+use keymap::{self, Action};
+use input::Key;
+use status::Segment;
+
+pub const PROJECT_FILE: &str = ".kilo.toml";
+
+/// The plain settings, the ones that can be given with `set`.
+pub const SETTINGS: &[&str] = &[
+    "tab_stop", "quit_times", "status_timeout", "line_numbers", "syntax", "clipboard", "tab_bar",
+    "indent_width", "expand_tab", "auto_indent", "auto_pairs", "detect_indent", "show_trailing",
+    "show_whitespace", "strip_trailing", "text_width", "formatter", "autosave", "exclude",
+    "status_left", "status_right", "language_server", "vim", "save_on_blur", "smooth_scroll",
+    "normalize", "memory_limit",
+];
+
+/// Settings that are `None` were not given and leave the ones underneath
+/// them in place when merging.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Config {
+    /// Columns between tab stops.
+    pub tab_stop: Option<usize>,
+    /// Extra presses of Ctrl-Q needed to quit with unsaved changes.
+    pub quit_times: Option<usize>,
+    /// Seconds a status message stays up.
+    pub status_timeout: Option<usize>,
+    /// `"off"`, `"absolute"` or `"relative"`.
+    pub line_numbers: Option<String>,
+    /// Whether to highlight syntax.
+    pub syntax: Option<bool>,
+    /// How copies reach the system clipboard: `"auto"`, `"off"` or one of
+    /// `clipboard::names()`.
+    pub clipboard: Option<String>,
+    /// Whether to show a tab for each buffer when there are several.
+    pub tab_bar: Option<bool>,
+    pub indent_width: Option<usize>,
+    pub expand_tab: Option<bool>,
+    /// Whether Enter indents the new line like the one before; on unless
+    /// turned off.
+    pub auto_indent: Option<bool>,
+    /// Whether brackets and quotes are closed as they're typed; off unless
+    /// turned on.
+    pub auto_pairs: Option<bool>,
+    /// Whether files are indented the way they already are, over
+    /// `indent_width` and `expand_tab`; on unless turned off.
+    pub detect_indent: Option<bool>,
+    /// Whether whitespace at the ends of lines is shown on red.
+    pub show_trailing: Option<bool>,
+    /// Whether tabs and spaces are drawn as `→` and `·`.
+    pub show_whitespace: Option<bool>,
+    /// Whether saving takes whitespace off the ends of lines.
+    pub strip_trailing: Option<bool>,
+    pub text_width: Option<usize>,
+    /// Shell command that reads the buffer on stdin and writes it back
+    /// formatted.
+    pub formatter: Option<String>,
+    /// Seconds without a key press before a buffer with changes is saved;
+    /// 0 turns it off.
+    pub autosave: Option<usize>,
+    /// Directories skipped when searching the project.
+    pub exclude: Option<Vec<String>>,
+    /// What the status bar shows on the left and on the right.
+    pub status_left: Option<Vec<Segment>>,
+    pub status_right: Option<Vec<Segment>>,
+    /// Shell command starting a language server for the file, usually
+    /// given for a filetype.
+    pub language_server: Option<String>,
+    /// Whether keys work as in vi, with normal, insert and visual modes.
+    pub vim: Option<bool>,
+    /// Whether a buffer with changes is saved when the terminal loses
+    /// focus.
+    pub save_on_blur: Option<bool>,
+    /// Whether long jumps scroll the screen a few rows at a time.
+    pub smooth_scroll: Option<bool>,
+    /// Whether typed and pasted text is put in NFC; on unless turned off.
+    pub normalize: Option<bool>,
+    /// MiB a file may take before opening it has to be confirmed; 0 for no
+    /// limit.
+    pub memory_limit: Option<usize>,
+    /// Whether to draw on the alternate screen, so the shell's scrollback
+    /// survives; on unless turned off.
+    pub alt_screen: Option<bool>,
+    /// Whether the screen is drawn for a screen reader to follow.
+    pub screen_reader: Option<bool>,
+    /// How many edits undo keeps, and how many bytes of text in them.
+    pub undo_edits: Option<usize>,
+    pub undo_bytes: Option<usize>,
+    /// Key bindings over the defaults; `None` unbinds the key.
+    pub keys: Option<Vec<(Key, Option<Action>)>>,
+    /// Settings for files of one filetype, by name.
+    pub filetypes: Option<Vec<(String, Config)>>,
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Config, String> {
+        let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        Config::parse(&text)
+    }
+
+    pub fn parse(text: &str) -> Result<Config, String> {
+        match text.parse::<Value>().map_err(|e| e.to_string())? {
+            Value::Table(table) => Config::from_table(&table),
+            _ => Err("expected a table".to_string()),
+        }
+    }
+
+    fn from_table(table: &Table) -> Result<Config, String> {
+        let mut config = Config::default();
+        for (key, value) in table {
+            match key.as_str() {
+                "tab_stop" => match integer(key, value)? {
+                    0 => return Err(expected(key, "a positive integer")),
+                    n => config.tab_stop = Some(n),
+                },
+                "quit_times" => config.quit_times = Some(integer(key, value)?),
+                "status_timeout" => config.status_timeout = Some(integer(key, value)?),
+                "line_numbers" => match value.as_str() {
+                    Some(mode @ "off") | Some(mode @ "absolute") | Some(mode @ "relative") => {
+                        config.line_numbers = Some(mode.to_string())
+                    }
+                    _ => return Err(expected(key, "\"off\", \"absolute\" or \"relative\"")),
+                },
+                "syntax" => config.syntax = Some(value.as_bool().ok_or_else(|| expected(key, "a boolean"))?),
+                "clipboard" => match value.as_str() {
+                    Some(way) if way == "auto" || way == "off" || clipboard::names().contains(&way) => {
+                        config.clipboard = Some(way.to_string())
+                    }
+                    _ => return Err(expected(key, &format!("\"auto\", \"off\" or one of {}", clipboard::names().join(", ")))),
+                },
+                "tab_bar" => config.tab_bar = Some(value.as_bool().ok_or_else(|| expected(key, "a boolean"))?),
+                "indent_width" => config.indent_width = Some(integer(key, value)?),
+                "text_width" => config.text_width = Some(integer(key, value)?),
+                "expand_tab" => config.expand_tab = Some(value.as_bool().ok_or_else(|| expected(key, "a boolean"))?),
+                "auto_indent" => config.auto_indent = Some(value.as_bool().ok_or_else(|| expected(key, "a boolean"))?),
+                "auto_pairs" => config.auto_pairs = Some(value.as_bool().ok_or_else(|| expected(key, "a boolean"))?),
+                "detect_indent" => config.detect_indent = Some(value.as_bool().ok_or_else(|| expected(key, "a boolean"))?),
+                "show_trailing" => config.show_trailing = Some(value.as_bool().ok_or_else(|| expected(key, "a boolean"))?),
+                "show_whitespace" => config.show_whitespace = Some(value.as_bool().ok_or_else(|| expected(key, "a boolean"))?),
+                "strip_trailing" => config.strip_trailing = Some(value.as_bool().ok_or_else(|| expected(key, "a boolean"))?),
+                "formatter" => config.formatter = Some(value.as_str().ok_or_else(|| expected(key, "a string"))?.to_string()),
+                "autosave" => config.autosave = Some(integer(key, value)?),
+                "exclude" => {
+                    let dirs = value.as_array().ok_or_else(|| expected(key, "an array"))?;
+                    let dirs: Option<Vec<String>> = dirs.iter().map(|d| d.as_str().map(|d| d.to_string())).collect();
+                    config.exclude = Some(dirs.ok_or_else(|| expected(key, "an array of strings"))?);
+                }
+                "status_left" => config.status_left = Some(segments(key, value)?),
+                "status_right" => config.status_right = Some(segments(key, value)?),
+                "language_server" => {
+                    config.language_server = Some(value.as_str().ok_or_else(|| expected(key, "a string"))?.to_string())
+                }
+                "vim" => config.vim = Some(value.as_bool().ok_or_else(|| expected(key, "a boolean"))?),
+                "save_on_blur" => config.save_on_blur = Some(value.as_bool().ok_or_else(|| expected(key, "a boolean"))?),
+                "smooth_scroll" => config.smooth_scroll = Some(value.as_bool().ok_or_else(|| expected(key, "a boolean"))?),
+                "normalize" => config.normalize = Some(value.as_bool().ok_or_else(|| expected(key, "a boolean"))?),
+                "memory_limit" => config.memory_limit = Some(integer(key, value)?),
+                "alt_screen" => config.alt_screen = Some(value.as_bool().ok_or_else(|| expected(key, "a boolean"))?),
+                "screen_reader" => config.screen_reader = Some(value.as_bool().ok_or_else(|| expected(key, "a boolean"))?),
+                "undo_edits" => config.undo_edits = Some(integer(key, value)?),
+                "undo_bytes" => config.undo_bytes = Some(integer(key, value)?),
+                "keys" => {
+                    let table = value.as_table().ok_or_else(|| expected(key, "a table"))?;
+                    let mut keys = Vec::new();
+                    for (name, action) in table {
+                        let k = keymap::parse_key(name).ok_or_else(|| format!("keys: unknown key {:?}", name))?;
+                        let action = match action.as_str() {
+                            Some("none") => None,
+                            Some(action) => Some(Action::from_name(action)
+                                .ok_or_else(|| format!("keys: unknown command {:?}", action))?),
+                            None => return Err(expected(name, "a command name")),
+                        };
+                        keys.push((k, action));
+                    }
+                    config.keys = Some(keys);
+                }
+                "filetype" => {
+                    let table = value.as_table().ok_or_else(|| expected(key, "a table"))?;
+                    let mut filetypes = Vec::new();
+                    for (name, settings) in table {
+                        let settings = settings.as_table().ok_or_else(|| expected(name, "a table"))?;
+                        let settings = Config::from_table(settings).map_err(|e| format!("{}.{}", name, e))?;
+                        filetypes.push((name.clone(), settings));
+                    }
+                    config.filetypes = Some(filetypes);
+                }
+                // Unknown keys are left for newer versions.
+                _ => {}
+            }
+        }
+        Ok(config)
+    }
+
+    /// Overrides these settings with the ones `over` gives.
+    pub fn merge(&mut self, over: Config) {
+        if over.tab_stop.is_some() { self.tab_stop = over.tab_stop; }
+        if over.quit_times.is_some() { self.quit_times = over.quit_times; }
+        if over.status_timeout.is_some() { self.status_timeout = over.status_timeout; }
+        if over.line_numbers.is_some() { self.line_numbers = over.line_numbers; }
+        if over.syntax.is_some() { self.syntax = over.syntax; }
+        if over.clipboard.is_some() { self.clipboard = over.clipboard; }
+        if over.tab_bar.is_some() { self.tab_bar = over.tab_bar; }
+        if over.indent_width.is_some() { self.indent_width = over.indent_width; }
+        if over.expand_tab.is_some() { self.expand_tab = over.expand_tab; }
+        if over.auto_indent.is_some() { self.auto_indent = over.auto_indent; }
+        if over.auto_pairs.is_some() { self.auto_pairs = over.auto_pairs; }
+        if over.detect_indent.is_some() { self.detect_indent = over.detect_indent; }
+        if over.show_trailing.is_some() { self.show_trailing = over.show_trailing; }
+        if over.show_whitespace.is_some() { self.show_whitespace = over.show_whitespace; }
+        if over.strip_trailing.is_some() { self.strip_trailing = over.strip_trailing; }
+        if over.text_width.is_some() { self.text_width = over.text_width; }
+        if over.formatter.is_some() { self.formatter = over.formatter; }
+        if over.autosave.is_some() { self.autosave = over.autosave; }
+        if over.exclude.is_some() { self.exclude = over.exclude; }
+        if over.status_left.is_some() { self.status_left = over.status_left; }
+        if over.status_right.is_some() { self.status_right = over.status_right; }
+        if over.language_server.is_some() { self.language_server = over.language_server; }
+        if over.vim.is_some() { self.vim = over.vim; }
+        if over.save_on_blur.is_some() { self.save_on_blur = over.save_on_blur; }
+        if over.smooth_scroll.is_some() { self.smooth_scroll = over.smooth_scroll; }
+        if over.normalize.is_some() { self.normalize = over.normalize; }
+        if over.memory_limit.is_some() { self.memory_limit = over.memory_limit; }
+        if over.alt_screen.is_some() { self.alt_screen = over.alt_screen; }
+        if over.screen_reader.is_some() { self.screen_reader = over.screen_reader; }
+        if over.undo_edits.is_some() { self.undo_edits = over.undo_edits; }
+        if over.undo_bytes.is_some() { self.undo_bytes = over.undo_bytes; }
+        if over.keys.is_some() { self.keys = over.keys; }
+        for (name, settings) in over.filetypes.into_iter().flatten() {
+            let filetypes = self.filetypes.get_or_insert_with(Vec::new);
+            match filetypes.iter_mut().find(|&&mut (ref n, _)| *n == name) {
+                Some(&mut (_, ref mut under)) => under.merge(settings),
+                None => filetypes.push((name, settings)),
+            }
+        }
+    }
+
+    /// Applies the `[filetype]` settings for the first of `names` that has
+    /// any.
+    pub fn merge_filetype(&mut self, names: &[&str]) {
+        let over = self.filetypes.iter().flatten()
+            .find(|&(name, _)| names.contains(&name.as_str()))
+            .map(|(_, settings)| settings.clone());
+        if let Some(over) = over {
+            self.merge(over);
+        }
+    }
+}
+
+/// Where the global settings are: `$XDG_CONFIG_HOME/kilo/config.toml`, or
+/// under `~/.config` without it.
+pub fn global_path() -> Option<PathBuf> {
+    let dir = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(dir.join("kilo").join("config.toml"))
+}
+
+/// The nearest `.kilo.toml` in `dir` or one of its parents.
+pub fn find_project(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors().map(|d| d.join(PROJECT_FILE)).find(|p| p.is_file())
+}
+
+fn integer(key: &str, value: &Value) -> Result<usize, String> {
+    match value.as_integer() {
+        Some(n) if n >= 0 => Ok(n as usize),
+        _ => Err(expected(key, "a positive integer")),
+    }
+}
+
+fn segments(key: &str, value: &Value) -> Result<Vec<Segment>, String> {
+    let names = value.as_array().ok_or_else(|| expected(key, "an array"))?;
+    names.iter().map(|name| match name.as_str() {
+        Some(name) => Segment::from_name(name)
+            .ok_or_else(|| format!("{}: unknown segment {:?}, expected one of {}", key, name, Segment::names().join(", "))),
+        None => Err(expected(key, "an array of strings")),
+    }).collect()
+}
+
+fn expected(key: &str, what: &str) -> String {
+    format!("{}: expected {}", key, what)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_are_read() {
+        let config = Config::parse("tab_stop = 4\nline_numbers = \"relative\"\nsyntax = false\n\
+                                    formatter = \"rustfmt\"\nexclude = [\"target\"]\n").unwrap();
+        assert_eq!(config, Config {
+            tab_stop: Some(4),
+            line_numbers: Some("relative".to_string()),
+            syntax: Some(false),
+            formatter: Some("rustfmt".to_string()),
+            exclude: Some(vec!["target".to_string()]),
+            ..Config::default()
+        });
+    }
+
+    #[test]
+    fn unknown_keys_are_left_alone() {
+        assert_eq!(Config::parse("from_the_future = 1\n"), Ok(Config::default()));
+        assert_eq!(Config::parse(""), Ok(Config::default()));
+    }
+
+    #[test]
+    fn bad_values_say_what_was_expected() {
+        assert_eq!(Config::parse("tab_stop = 0"), Err("tab_stop: expected a positive integer".to_string()));
+        assert_eq!(Config::parse("quit_times = -1"), Err("quit_times: expected a positive integer".to_string()));
+        assert_eq!(Config::parse("syntax = \"yes\""), Err("syntax: expected a boolean".to_string()));
+        assert_eq!(Config::parse("exclude = [1]"), Err("exclude: expected an array of strings".to_string()));
+        assert!(Config::parse("line_numbers = \"sideways\"").unwrap_err().starts_with("line_numbers: expected"));
+        assert!(Config::parse("tab_stop = ").is_err());
+    }
+
+    #[test]
+    fn merging_keeps_what_is_not_given() {
+        let mut config = Config::parse("tab_stop = 8\nquit_times = 1\n").unwrap();
+        config.merge(Config::parse("tab_stop = 2\nsyntax = true\n").unwrap());
+        assert_eq!(config, Config {
+            tab_stop: Some(2),
+            quit_times: Some(1),
+            syntax: Some(true),
+            ..Config::default()
+        });
+    }
+
+    #[test]
+    fn filetype_settings_go_over_the_rest() {
+        let mut config = Config::parse("indent_width = 8\nexpand_tab = false\n\
+                                        [filetype.python]\nindent_width = 4\nexpand_tab = true\n\
+                                        [filetype.make]\nexpand_tab = false\n").unwrap();
+        let global = config.clone();
+        config.merge_filetype(&["python", "py", "setup.py"]);
+        assert_eq!((config.indent_width, config.expand_tab), (Some(4), Some(true)));
+        let mut other = global.clone();
+        other.merge_filetype(&["", "txt", "notes.txt"]);
+        assert_eq!((other.indent_width, other.expand_tab), (Some(8), Some(false)));
+    }
+
+    #[test]
+    fn filetype_tables_merge_by_name() {
+        let mut config = Config::parse("[filetype.rust]\nindent_width = 4\n").unwrap();
+        config.merge(Config::parse("[filetype.rust]\nexpand_tab = true\n[filetype.c]\ntab_stop = 8\n").unwrap());
+        config.merge_filetype(&["rust"]);
+        assert_eq!((config.indent_width, config.expand_tab), (Some(4), Some(true)));
+        assert!(Config::parse("[filetype.rust]\ntab_stop = 0\n").unwrap_err().starts_with("rust.tab_stop"));
+    }
+
+    #[test]
+    fn startup_options_are_read() {
+        let config = Config::parse("alt_screen = false\nscreen_reader = true\nundo_edits = 10\n\
+                                    memory_limit = 0\nnormalize = false\n").unwrap();
+        assert_eq!(config, Config {
+            alt_screen: Some(false),
+            screen_reader: Some(true),
+            undo_edits: Some(10),
+            memory_limit: Some(0),
+            normalize: Some(false),
+            ..Config::default()
+        });
+        assert_eq!(Config::parse("save_on_blur = 1"), Err("save_on_blur: expected a boolean".to_string()));
+    }
+}
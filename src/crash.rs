@@ -0,0 +1,111 @@
+//! Crash reports. A panic would otherwise leave the terminal in raw mode on
+//! the alternate screen with the message scribbled somewhere over the text.
+//! The panic hook puts the terminal back first, then writes what led up to
+//! the crash to a file and says where it is.
+//!
+//! What the editor was doing is noted as it goes, since the hook can't get
+//! at the editor itself. Typed text and buffer contents are only kept when
+//! `KILO_CRASH_CONTENTS` is set.
+
+use std::backtrace::Backtrace;
+use std::collections::VecDeque;
+use std::env;
+use std::fmt::Write as FmtWrite;
+use std::fs;
+use std::io::{self, Write};
+use std::panic;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use termios::{tcsetattr, Termios, TCSAFLUSH};
+
+const RECENT_KEYS: usize = 50;
+
+struct State {
+    term: Option<Termios>,
+    dir: Option<PathBuf>,
+    keys: VecDeque<String>,
+    buffer: String,
+    contents: Option<String>,
+}
+
+static STATE: Mutex<State> = Mutex::new(State {
+    term: None,
+    dir: None,
+    keys: VecDeque::new(),
+    buffer: String::new(),
+    contents: None,
+});
+
+/// Whether the user agreed to typed text and contents going in reports.
+pub fn with_contents() -> bool {
+    env::var_os("KILO_CRASH_CONTENTS").is_some()
+}
+
+/// Installs the panic hook. `term` is the terminal state to go back to and
+/// reports are written to `dir`.
+pub fn install(term: Termios, dir: Option<PathBuf>) {
+    if let Ok(mut state) = STATE.lock() {
+        state.term = Some(term);
+        state.dir = dir;
+    }
+    panic::set_hook(Box::new(|info| {
+        let backtrace = Backtrace::force_capture();
+        // The lock may be poisoned if the panic happened while holding it;
+        // the state is still good enough for a report.
+        let state = match STATE.lock() {
+            Ok(state) => state,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let mut stdout = io::stdout();
+        let _ = stdout.write_all(b"\x1b[?1000l\x1b[?1006l\x1b[?2004l\x1b[?1004l\x1b[?1049l\r\n");
+        let _ = stdout.flush();
+        if let Some(ref term) = state.term {
+            let _ = tcsetattr(0, TCSAFLUSH, term);
+        }
+
+        let mut report = String::new();
+        let _ = writeln!(report, "kilo {} crashed: {}", env!("CARGO_PKG_VERSION"), info);
+        let _ = writeln!(report, "\nbuffer: {}", state.buffer);
+        let _ = writeln!(report, "\nrecent keys (oldest first):");
+        for key in &state.keys {
+            let _ = writeln!(report, "  {}", key);
+        }
+        let _ = writeln!(report, "\nbacktrace:\n{}", backtrace);
+        if let Some(ref contents) = state.contents {
+            let _ = writeln!(report, "\ncontents:\n{}", contents);
+        }
+
+        let stamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        let dir = state.dir.clone().unwrap_or_else(env::temp_dir);
+        let path = dir.join(format!("crash-{}.txt", stamp));
+        let written = fs::create_dir_all(&dir).and_then(|_| fs::write(&path, &report));
+        let mut stderr = io::stderr();
+        let _ = writeln!(stderr, "kilo crashed: {}", info);
+        let _ = match written {
+            Ok(()) => writeln!(stderr, "crash report written to {}", path.display()),
+            Err(e) => writeln!(stderr, "failed to write crash report to {}: {}\n{}", path.display(), e, report),
+        };
+    }));
+}
+
+/// Notes a key for the report; `key` should already hide typed text
+/// unless contents were opted in.
+pub fn note_key(key: String) {
+    if let Ok(mut state) = STATE.lock() {
+        if state.keys.len() == RECENT_KEYS {
+            state.keys.pop_front();
+        }
+        state.keys.push_back(key);
+    }
+}
+
+/// Notes what's being edited: a one-line description, and the text itself
+/// when opted in.
+pub fn note_buffer(description: String, contents: Option<String>) {
+    if let Ok(mut state) = STATE.lock() {
+        state.buffer = description;
+        state.contents = contents;
+    }
+}
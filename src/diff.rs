@@ -0,0 +1,201 @@
+//! Line diffs in unified format, using Myers' algorithm.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Same,
+    Delete,
+    Insert,
+}
+
+/// The shortest edit script turning `a` into `b`, in order.
+fn script<T: PartialEq>(a: &[T], b: &[T]) -> Vec<Op> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max = (n + m) as usize;
+    let offset = max as isize + 1;
+    let mut v = vec![0isize; 2 * max + 3];
+    let mut trace = Vec::new();
+    'outer: for d in 0..(max as isize + 1) {
+        trace.push(v.clone());
+        let mut k = -d;
+        while k <= d {
+            let idx = (k + offset) as usize;
+            let mut x = if k == -d || (k != d && v[idx - 1] < v[idx + 1]) {
+                v[idx + 1]
+            } else {
+                v[idx - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[idx] = x;
+            if x >= n && y >= m {
+                trace.push(v.clone());
+                break 'outer;
+            }
+            k += 2;
+        }
+    }
+
+    // Walk back through the saved frontiers to recover the path.
+    let mut ops = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for d in (1..trace.len() - 1).rev() {
+        let v = &trace[d];
+        let d = d as isize;
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && v[(k - 1 + offset) as usize] < v[(k + 1 + offset) as usize]) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = v[(prev_k + offset) as usize];
+        let prev_y = prev_x - prev_k;
+        while x > prev_x && y > prev_y {
+            ops.push(Op::Same);
+            x -= 1;
+            y -= 1;
+        }
+        ops.push(if x == prev_x { Op::Insert } else { Op::Delete });
+        x = prev_x;
+        y = prev_y;
+    }
+    while x > 0 && y > 0 {
+        ops.push(Op::Same);
+        x -= 1;
+        y -= 1;
+    }
+    ops.reverse();
+    ops
+}
+
+/// A run of changes with the lines around it: where it starts in `a` and
+/// `b`, how many lines it spans in each, and its lines as a unified diff
+/// prints them.
+pub struct Hunk {
+    pub a_start: usize,
+    pub a_len: usize,
+    pub b_start: usize,
+    pub b_len: usize,
+    pub lines: Vec<String>,
+}
+
+impl Hunk {
+    /// The `@@` line. An empty range starts at the line before it.
+    pub fn header(&self) -> String {
+        let start = |start: usize, len: usize| if len == 0 { start } else { start + 1 };
+        format!("@@ -{},{} +{},{} @@",
+                start(self.a_start, self.a_len), self.a_len, start(self.b_start, self.b_len), self.b_len)
+    }
+}
+
+/// The hunks turning `a` into `b`, with `context` lines around each change.
+pub fn hunks(a: &[&str], b: &[&str], context: usize) -> Vec<Hunk> {
+    let ops = script(a, b);
+    let mut hunks = Vec::new();
+    // Position in a and b before each op.
+    let mut pos = Vec::with_capacity(ops.len() + 1);
+    let (mut i, mut j) = (0, 0);
+    for op in &ops {
+        pos.push((i, j));
+        match *op {
+            Op::Same => { i += 1; j += 1; }
+            Op::Delete => i += 1,
+            Op::Insert => j += 1,
+        }
+    }
+    pos.push((i, j));
+
+    let mut start = 0;
+    while start < ops.len() {
+        let first = match ops[start..].iter().position(|&op| op != Op::Same) {
+            Some(p) => start + p,
+            None => break,
+        };
+        // Extend the hunk while changes are close enough to share context.
+        let mut last = first;
+        let mut k = first;
+        while k < ops.len() {
+            if ops[k] != Op::Same {
+                last = k;
+            } else if k - last > 2 * context {
+                break;
+            }
+            k += 1;
+        }
+        let from = first.saturating_sub(context).max(start);
+        let to = (last + 1 + context).min(ops.len());
+        let (a0, b0) = pos[from];
+        let (a1, b1) = pos[to];
+        let lines = (from..to).map(|k| {
+            let (i, j) = pos[k];
+            match ops[k] {
+                Op::Same => format!(" {}", a[i]),
+                Op::Delete => format!("-{}", a[i]),
+                Op::Insert => format!("+{}", b[j]),
+            }
+        }).collect();
+        hunks.push(Hunk { a_start: a0, a_len: a1 - a0, b_start: b0, b_len: b1 - b0, lines });
+        start = to;
+    }
+    hunks
+}
+
+/// A unified diff from `a` to `b` with `context` lines around each change,
+/// without the file header lines.
+pub fn unified(a: &[&str], b: &[&str], context: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    for hunk in hunks(a, b, context) {
+        lines.push(hunk.header());
+        lines.extend(hunk.lines);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn equal_inputs_have_no_hunks() {
+        assert!(hunks(&["a", "b"], &["a", "b"], 3).is_empty());
+        assert!(unified(&[], &[], 3).is_empty());
+    }
+
+    #[test]
+    fn single_change_with_context() {
+        let a = ["1", "2", "3", "4", "5"];
+        let b = ["1", "2", "x", "4", "5"];
+        assert_eq!(unified(&a, &b, 1), vec!["@@ -2,3 +2,3 @@", " 2", "-3", "+x", " 4"]);
+    }
+
+    #[test]
+    fn distant_changes_split_into_hunks() {
+        let a = ["a", "1", "2", "3", "4", "5", "b"];
+        let b = ["A", "1", "2", "3", "4", "5", "B"];
+        let hunks = hunks(&a, &b, 1);
+        assert_eq!(hunks.len(), 2);
+        assert_eq!((hunks[0].a_start, hunks[0].a_len, hunks[0].b_start, hunks[0].b_len), (0, 2, 0, 2));
+        assert_eq!((hunks[1].a_start, hunks[1].a_len, hunks[1].b_start, hunks[1].b_len), (5, 2, 5, 2));
+        // With enough context the two changes share one hunk.
+        assert_eq!(super::hunks(&a, &b, 3).len(), 1);
+    }
+
+    #[test]
+    fn empty_sides_start_at_the_line_before() {
+        assert_eq!(unified(&[], &["a", "b"], 3), vec!["@@ -0,0 +1,2 @@", "+a", "+b"]);
+        assert_eq!(unified(&["a"], &[], 3), vec!["@@ -1,1 +0,0 @@", "-a"]);
+    }
+
+    #[test]
+    fn script_is_shortest() {
+        let a: Vec<char> = "abcabba".chars().collect();
+        let b: Vec<char> = "cbabac".chars().collect();
+        let ops = script(&a, &b);
+        let edits = ops.iter().filter(|&&op| op != Op::Same).count();
+        assert_eq!(edits, 5);
+        assert_eq!(ops.iter().filter(|&&op| op != Op::Insert).count(), a.len());
+        assert_eq!(ops.iter().filter(|&&op| op != Op::Delete).count(), b.len());
+    }
+}
@@ -0,0 +1,1047 @@
+//! Changing the text: typing and deleting, undo, registers and the kill
+//! ring, selections and the operations on them.
+
+use std::{env, str};
+use std::io::Result;
+use std::path::Path;
+
+use unicode_normalization::{UnicodeNormalization, is_nfc};
+use unicode_normalization::char::is_combining_mark;
+use unicode_segmentation::UnicodeSegmentation;
+
+use clipboard;
+use syntax::{self, Backend};
+use keymap::Action;
+use undo::{self, Edit};
+use editor::Editor;
+use input::{Key, complete_path};
+use text::TextBuffer;
+
+const CLIPBOARD_HISTORY: usize = 16;
+
+/// What `auto_pairs` closes, and with what.
+const PAIRS: &[(char, char)] = &[('(', ')'), ('[', ']'), ('{', '}'), ('"', '"'), ('\'', '\'')];
+
+/// Opening and closing strings for a surround spec: brackets pair up with
+/// their counterpart, `<tag>` with `</tag>`, anything else with itself.
+fn surround_pair(spec: &str) -> (String, String) {
+    let (open, close) = match spec {
+        "(" | ")" => ("(", ")"),
+        "[" | "]" => ("[", "]"),
+        "{" | "}" => ("{", "}"),
+        "<" | ">" => ("<", ">"),
+        _ if spec.len() > 2 && spec.starts_with('<') && spec.ends_with('>') => {
+            let name = spec[1..spec.len() - 1].split_whitespace().next().unwrap_or("");
+            return (spec.to_string(), format!("</{}>", name));
+        }
+        _ => (spec, spec),
+    };
+    (open.to_string(), close.to_string())
+}
+
+/// The byte offset of the character before `at`, taking a letter with its
+/// accents or an emoji sequence as one character.
+pub(crate) fn prev_boundary(row: &str, at: usize) -> usize {
+    row[..at].grapheme_indices(true).next_back().map_or(0, |(i, _)| i)
+}
+
+/// The byte offset of the character after `at`.
+pub(crate) fn next_boundary(row: &str, at: usize) -> usize {
+    row[at..].graphemes(true).next().map_or(at, |g| at + g.len())
+}
+
+/// Letters, digits and underscores make up words.
+pub(crate) fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+impl Editor {
+    pub(crate) fn insert_char(&mut self, c: char) {
+        if self.cy == self.rows.len() {
+            self.rows.push("".to_string());
+        }
+        self.record((self.cy, self.cx), String::new(), c.to_string());
+        self.rows[self.cy].insert(self.cx, c);
+        self.cx += c.len_utf8();
+
+        self.dirty = true;
+    }
+
+    /// Inserts a typed character. With `auto_pairs` on, an opening bracket
+    /// or quote brings its closer along, with the cursor between them, and
+    /// typing a closer just before the same one steps over it.
+    pub(crate) fn type_char(&mut self, c: char) {
+        if self.config.auto_pairs == Some(true) {
+            let row = self.rows.get(self.cy).map_or("", |row| row.as_str());
+            let next = row[self.cx..].chars().next();
+            let prev = row[..self.cx].chars().next_back();
+            if next == Some(c) && PAIRS.iter().any(|&(_, close)| close == c) {
+                self.cx += c.len_utf8();
+                return;
+            }
+            if let Some(&(open, close)) = PAIRS.iter().find(|&&(open, _)| open == c) {
+                // Not in front of a word, and no quote straight after one,
+                // where it's more likely an apostrophe.
+                let word_next = next.is_some_and(is_word_char);
+                let word_prev = open == close && prev.is_some_and(is_word_char);
+                if !word_next && !word_prev {
+                    self.insert_char(open);
+                    self.insert_char(close);
+                    self.cx -= close.len_utf8();
+                    return;
+                }
+            }
+        }
+        self.insert_char(c);
+    }
+
+    /// Deletes the character before the cursor. With `auto_pairs` on, an
+    /// opener right before its closer goes with it.
+    pub(crate) fn backspace(&mut self) {
+        if self.config.auto_pairs == Some(true) && self.cy < self.rows.len() {
+            let row = &self.rows[self.cy];
+            let pair = (row[..self.cx].chars().next_back(), row[self.cx..].chars().next());
+            if let (Some(open), Some(close)) = pair {
+                if PAIRS.contains(&(open, close)) {
+                    let (cy, cx) = (self.cy, self.cx);
+                    self.delete_region((cy, cx - open.len_utf8()), (cy, cx + close.len_utf8()));
+                    return;
+                }
+            }
+        }
+        self.del_char();
+    }
+
+    /// Inserts a character typed as several UTF-8 bytes, the first of which
+    /// is `lead`.
+    pub(crate) fn insert_utf8(&mut self, lead: u8) {
+        let len = match lead {
+            0xc0..=0xdf => 2,
+            0xe0..=0xef => 3,
+            0xf0..=0xf7 => 4,
+            _ => return,
+        };
+        let mut bytes = vec![lead];
+        while bytes.len() < len {
+            match self.read_char() {
+                Some(b) => bytes.push(b),
+                None => return,
+            }
+        }
+        let c = match str::from_utf8(&bytes).ok().and_then(|s| s.chars().next()) {
+            Some(c) => c,
+            None => return,
+        };
+        self.insert_char(c);
+        if self.normalize && is_combining_mark(c) {
+            self.compose_before_cursor();
+        }
+    }
+
+    /// Puts the character just typed together with the ones it modifies
+    /// into NFC.
+    fn compose_before_cursor(&mut self) {
+        let row = &self.rows[self.cy];
+        let mut start = self.cx;
+        while let Some(c) = row[..start].chars().next_back() {
+            start -= c.len_utf8();
+            if !is_combining_mark(c) {
+                break;
+            }
+        }
+        let old = row[start..self.cx].to_string();
+        let new: String = old.nfc().collect();
+        if new != old {
+            self.record((self.cy, start), old.clone(), new.clone());
+            self.rows[self.cy].replace_range(start..self.cx, &new);
+            self.cx = start + new.len();
+        }
+    }
+
+    /// Puts every line of the buffer into NFC.
+    pub(crate) fn normalize_buffer(&mut self) {
+        if self.read_only {
+            self.set_status_msg(tr!(ReadOnly));
+            return;
+        }
+        let mut changed = 0;
+        for y in 0..self.rows.len() {
+            if is_nfc(&self.rows[y]) {
+                continue;
+            }
+            let old = self.rows[y].clone();
+            let new: String = old.nfc().collect();
+            self.record((y, 0), old, new.clone());
+            self.rows[y] = new;
+            changed += 1;
+        }
+        if changed > 0 {
+            self.dirty = true;
+            self.cx = self.cx.min(self.rows.get(self.cy).map_or(0, |r| r.len()));
+            while !self.rows.get(self.cy).is_none_or(|r| r.is_char_boundary(self.cx)) {
+                self.cx -= 1;
+            }
+        }
+        self.set_status_msg(tr!(Normalized, changed));
+    }
+
+    pub(crate) fn del_char(&mut self) {
+        if self.cy == self.rows.len() { return; };
+        if self.cx == 0 && self.cy == 0 { return; };
+
+        if self.cx > 0 {
+            let end = self.cx;
+            self.cx = prev_boundary(&self.rows[self.cy], self.cx);
+            let removed = self.rows[self.cy][self.cx..end].to_string();
+            self.record((self.cy, self.cx), removed, String::new());
+            self.rows[self.cy].drain(self.cx..end);
+        } else {
+            self.cx = self.rows[self.cy - 1].len();
+            self.record((self.cy - 1, self.cx), "\n".to_string(), String::new());
+            let row = self.rows.remove(self.cy);
+            self.rows[self.cy - 1] += &row;
+            self.signs.lines_removed(self.cy, 1);
+            self.cy -= 1;
+        }
+        self.dirty = true;
+    }
+
+    pub(crate) fn insert_newline(&mut self) {
+        self.record((self.cy, self.cx), String::new(), "\n".to_string());
+        if self.cx == 0 {
+            self.rows.insert(self.cy, "".to_string());
+            self.signs.lines_inserted(self.cy, 1);
+        } else if self.cx == self.rows[self.cy].len() {
+            self.rows.insert(self.cy + 1, "".to_string());
+            self.signs.lines_inserted(self.cy + 1, 1);
+        } else {
+            let right = self.rows[self.cy].split_off(self.cx);
+            self.rows.insert(self.cy + 1, right);
+            self.signs.lines_inserted(self.cy + 1, 1);
+        }
+        self.cy += 1;
+        self.cx = 0;
+        self.dirty = true;
+    }
+
+    /// Starts a new line as Enter does: indented like the line the cursor
+    /// was on, unless `auto_indent` is off. Only the indentation before the
+    /// cursor is copied.
+    pub(crate) fn newline_and_indent(&mut self) {
+        let indent = match self.rows.get(self.cy) {
+            Some(row) if self.config.auto_indent != Some(false) => {
+                row[..Editor::indent_of(row).min(self.cx)].to_string()
+            }
+            _ => String::new(),
+        };
+        self.insert_newline();
+        if !indent.is_empty() {
+            self.record((self.cy, 0), String::new(), indent.clone());
+            self.rows[self.cy].insert_str(0, &indent);
+            self.cx = indent.len();
+        }
+    }
+
+    pub(crate) fn record(&mut self, at: (usize, usize), removed: String, inserted: String) {
+        self.highlighter.edited(at.0, &removed, &inserted);
+        self.changes += 1;
+        self.history.record(Edit { at, removed, inserted });
+    }
+
+    /// Swaps the text `remove` at `at` for `insert`, leaving the cursor
+    /// after it.
+    fn revert(&mut self, at: (usize, usize), remove: &str, insert: &str) {
+        let end = undo::end_of(at, remove);
+        self.highlighter.edited(at.0, remove, insert);
+        self.changes += 1;
+        self.rows.remove_text(at, end);
+        self.signs.lines_removed(at.0 + 1, remove.matches('\n').count());
+        let (cy, cx) = self.rows.insert_text(at, insert);
+        self.signs.lines_inserted(at.0 + 1, insert.matches('\n').count());
+        self.cy = cy;
+        self.cx = cx;
+        self.anchor = None;
+        self.dirty = true;
+    }
+
+    pub(crate) fn undo(&mut self) {
+        if self.read_only {
+            self.set_status_msg(tr!(ReadOnly));
+            return;
+        }
+        match self.history.undo() {
+            Some(group) => for edit in group.iter().rev() {
+                self.revert(edit.at, &edit.inserted, &edit.removed);
+            },
+            None => self.set_status_msg(tr!(NothingToUndo)),
+        }
+    }
+
+    pub(crate) fn redo(&mut self) {
+        if self.read_only {
+            self.set_status_msg(tr!(ReadOnly));
+            return;
+        }
+        match self.history.redo() {
+            Some(group) => for edit in &group {
+                self.revert(edit.at, &edit.removed, &edit.inserted);
+            },
+            None => self.set_status_msg(tr!(NothingToRedo)),
+        }
+    }
+
+    pub(crate) fn insert_text(&mut self, text: &str) {
+        for ch in text.chars() {
+            if ch == '\n' {
+                self.insert_newline();
+            } else {
+                self.insert_char(ch);
+            }
+        }
+    }
+
+    fn push_clipboard(&mut self, text: String) {
+        self.clipboard.retain(|t| *t != text);
+        self.clipboard.push(text);
+        if self.clipboard.len() > CLIPBOARD_HISTORY {
+            self.clipboard.remove(0);
+        }
+    }
+
+    fn copy_to_system(&mut self, text: &str) {
+        let res = match self.system_clipboard {
+            Some(ref system) => system.copy(text, &mut self.stdout),
+            None => return,
+        };
+        match res {
+            Ok(()) => {}
+            Err(clipboard::Error::TooLarge) => self.set_status_msg(tr!(ClipboardTooLarge)),
+            Err(e) => self.set_status_msg(tr!(ClipboardFailed, e)),
+        }
+    }
+
+    /// Kills from the cursor to the end of the line, or to its start when
+    /// not `forward`. At the end (or start) of a line the line break goes
+    /// instead, joining the lines.
+    pub(crate) fn kill_line(&mut self, forward: bool) {
+        if self.cy >= self.rows.len() {
+            return;
+        }
+        let (cy, cx) = (self.cy, self.cx);
+        let (start, end) = if forward {
+            match self.rows[cy].len() {
+                len if cx < len => ((cy, cx), (cy, len)),
+                _ if cy + 1 < self.rows.len() => ((cy, cx), (cy + 1, 0)),
+                _ => return,
+            }
+        } else {
+            match cx {
+                0 if cy > 0 => ((cy - 1, self.rows[cy - 1].len()), (cy, 0)),
+                0 => return,
+                _ => ((cy, 0), (cy, cx)),
+            }
+        };
+        let text = self.region_text(start, end);
+        let continues = matches!(self.last_action, Some(Action::KillLine) | Some(Action::KillLineBackward));
+        self.kill_ring.kill(text, continues, forward);
+        self.delete_region(start, end);
+        if let Some(kill) = self.kill_ring.newest().map(|kill| kill.to_string()) {
+            self.copy_to_system(&kill);
+        }
+    }
+
+    /// Puts back the newest kill, or straight after a yank, swaps what it
+    /// put back for the kill before. Text copied to the system clipboard
+    /// since the last kill counts as the newest, when it can be read.
+    pub(crate) fn yank(&mut self) {
+        let again = match self.yanked {
+            Some(region) if self.last_action == Some(Action::Yank) => Some(region),
+            _ => None,
+        };
+        if again.is_none() {
+            if let Some(Ok(text)) = self.system_clipboard.as_ref().and_then(|system| system.paste()) {
+                self.kill_ring.offer(text);
+            }
+        }
+        let text = match again {
+            Some(_) => self.kill_ring.rotate(),
+            None => self.kill_ring.yank(),
+        };
+        let text = match text {
+            Some(text) => text.to_string(),
+            None => {
+                self.set_status_msg(tr!(KillRingEmpty));
+                return;
+            }
+        };
+        if let Some((start, end)) = again {
+            self.delete_region(start, end);
+        }
+        let start = (self.cy, self.cx);
+        self.insert_text(&text);
+        self.yanked = Some((start, (self.cy, self.cx)));
+    }
+
+    /// Stores copied or deleted text, vim style: into the named register if
+    /// one was selected (appending for uppercase names), otherwise into
+    /// `"0` for copies and the numbered history `"1`-`"9` for deletions.
+    /// `"+` and unnamed copies also go to the system clipboard.
+    pub(crate) fn store_register(&mut self, register: Option<char>, text: String, deleted: bool) {
+        if register.is_none() || register == Some('+') {
+            self.copy_to_system(&text);
+        }
+        match register {
+            Some(r) if r.is_ascii_uppercase() => {
+                self.registers.entry(r.to_ascii_lowercase()).or_default().push_str(&text);
+            }
+            Some(r) => {
+                self.registers.insert(r, text.clone());
+            }
+            None if deleted => {
+                for n in (b'1'..b'9').rev() {
+                    if let Some(older) = self.registers.remove(&(n as char)) {
+                        self.registers.insert((n + 1) as char, older);
+                    }
+                }
+                self.registers.insert('1', text.clone());
+            }
+            None => {
+                self.registers.insert('0', text.clone());
+            }
+        }
+        self.push_clipboard(text);
+    }
+
+    pub(crate) fn copy_line(&mut self, register: Option<char>) {
+        if self.cy < self.rows.len() {
+            let line = self.rows[self.cy].clone() + "\n";
+            self.store_register(register, line, false);
+            self.set_status_msg(tr!(LineCopied));
+        }
+    }
+
+    pub(crate) fn cut_line(&mut self, register: Option<char>) {
+        if self.cy < self.rows.len() {
+            let line = self.rows.remove(self.cy) + "\n";
+            self.signs.lines_removed(self.cy, 1);
+            self.record((self.cy, 0), line.clone(), String::new());
+            self.store_register(register, line, true);
+            self.cx = 0;
+            self.dirty = true;
+        }
+    }
+
+    pub(crate) fn paste_register(&mut self, register: Option<char>) {
+        let text = match register {
+            // The terminal's own paste is how OSC 52 pastes, so that falls
+            // back to the last text copied here.
+            Some('+') => match self.system_clipboard.as_ref().and_then(|system| system.paste()) {
+                Some(Ok(text)) => Some(text),
+                Some(Err(e)) => {
+                    self.set_status_msg(tr!(ClipboardReadFailed, e));
+                    return;
+                }
+                None => self.registers.get(&'+').cloned(),
+            },
+            Some(r) => self.registers.get(&r.to_ascii_lowercase()).cloned(),
+            None => self.clipboard.last().cloned(),
+        };
+        match text {
+            Some(text) => self.paste(text),
+            None => self.set_status_msg(tr!(RegisterEmpty)),
+        }
+    }
+
+    pub(crate) fn select_register(&mut self) -> Result<()> {
+        self.set_status_msg("\"");
+        self.refresh_screen()?;
+        match self.read_key()? {
+            Key::Char(c) if (c as char).is_ascii_alphanumeric() || c == b'+' => {
+                self.pending_register = Some(c as char);
+                self.set_status_msg(format!("\"{}", c as char));
+            }
+            _ => self.set_status_msg(""),
+        }
+        Ok(())
+    }
+
+    /// Inserts a clipboard snippet. Whole lines go above the current line,
+    /// anything else at the cursor.
+    fn paste(&mut self, text: String) {
+        if text.ends_with('\n') {
+            self.cx = 0;
+        }
+        let text = if self.normalize { text.nfc().collect() } else { text };
+        self.insert_text(&text);
+    }
+
+    pub(crate) fn paste_from_history(&mut self) -> Result<()> {
+        let entries: Vec<String> = self.clipboard.iter().rev().cloned().collect();
+        if entries.is_empty() {
+            self.set_status_msg(tr!(ClipboardEmpty));
+            return Ok(());
+        }
+        if let Some(i) = self.pick(tr!(ClipboardHistory), &entries)? {
+            let text = entries[i].clone();
+            self.push_clipboard(text.clone());
+            self.paste(text);
+        }
+        Ok(())
+    }
+
+    /// The path-looking text just before the cursor, if any.
+    fn path_before_cursor(&self) -> Option<String> {
+        let row = self.rows.get(self.cy)?;
+        let before = &row[..self.cx.min(row.len())];
+        let start = before.rfind(|c: char| c.is_whitespace() || "\"'`()[]{}<>=,;".contains(c))
+            .map_or(0, |i| i + 1);
+        let token = &before[start..];
+        if token.contains('/') || token.starts_with('~') || token.starts_with('.') {
+            Some(token.to_string())
+        } else {
+            None
+        }
+    }
+
+    /// Completes the path before the cursor from the filesystem. Relative
+    /// paths are taken against the file's directory.
+    pub(crate) fn complete_path_in_buffer(&mut self) -> Result<()> {
+        let token = match self.path_before_cursor() {
+            Some(token) => token,
+            None => {
+                self.set_status_msg(tr!(NoPathHere));
+                return Ok(());
+            }
+        };
+        let expanded = match (token.starts_with("~/"), env::var("HOME")) {
+            (true, Ok(home)) => format!("{}{}", home, &token[1..]),
+            _ if token.starts_with('/') => token.clone(),
+            _ => {
+                let base = match self.filename {
+                    Some(ref f) => Path::new(f).parent().map(|p| p.to_path_buf()),
+                    None => None,
+                };
+                let base = match base {
+                    Some(base) => base,
+                    None => env::current_dir()?,
+                };
+                format!("{}/{}", base.display(), token)
+            }
+        };
+        // Candidates come back with the expanded directory in front; swap
+        // the one typed back in.
+        let dir_len = expanded.rfind('/').map_or(0, |i| i + 1);
+        let typed_len = token.rfind('/').map_or(0, |i| i + 1);
+        let candidates: Vec<String> = complete_path(&expanded).into_iter()
+            .map(|c| format!("{}{}", &token[..typed_len], &c[dir_len..]))
+            .collect();
+        let choice = match candidates.len() {
+            0 => {
+                self.set_status_msg(tr!(NoCompletions));
+                return Ok(());
+            }
+            1 => Some(0),
+            _ => self.pick(tr!(CompletePath), &candidates)?,
+        };
+        if let Some(i) = choice {
+            let suffix = candidates[i][token.len()..].to_string();
+            self.insert_text(&suffix);
+        }
+        Ok(())
+    }
+
+    /// Byte range of the word under (or just before) the cursor.
+    fn word_bounds(&self) -> Option<(usize, usize)> {
+        let row = self.rows.get(self.cy)?.as_bytes();
+        let is_word = |b: u8| b.is_ascii_alphanumeric() || b == b'_';
+        let mut start = self.cx.min(row.len());
+        if start == row.len() || !is_word(row[start]) {
+            if start == 0 || !is_word(row[start - 1]) {
+                return None;
+            }
+            start -= 1;
+        }
+        while start > 0 && is_word(row[start - 1]) {
+            start -= 1;
+        }
+        let mut end = start;
+        while end < row.len() && is_word(row[end]) {
+            end += 1;
+        }
+        Some((start, end))
+    }
+
+    /// Wraps the selection, or the word under the cursor, in a pair asked
+    /// for, as one undoable edit. The cursor stays on the same text.
+    pub(crate) fn surround(&mut self) -> Result<()> {
+        let (start, end) = match self.selection() {
+            Some(region) => region,
+            None => match self.word_bounds() {
+                Some((start, end)) => ((self.cy, start), (self.cy, end)),
+                None => {
+                    self.set_status_msg(tr!(NothingToSurround));
+                    return Ok(());
+                }
+            },
+        };
+        let (open, close) = match self.prompt(tr!(SurroundWith), |_, _, _| {})? {
+            Some(spec) => surround_pair(&spec),
+            None => return Ok(()),
+        };
+        let cursor = (self.cy, self.cx);
+        self.history.begin_group();
+        self.replace_region(end, end, &close);
+        self.replace_region(start, start, &open);
+        self.history.end_group();
+        self.cy = cursor.0;
+        self.cx = cursor.1;
+        if cursor.0 == start.0 && cursor.1 >= start.1 {
+            self.cx += open.len();
+        }
+        Ok(())
+    }
+
+    /// Finds the innermost `open`/`close` pair on the current line that
+    /// encloses the cursor, returning the byte offsets of both.
+    fn find_surrounding(&self, open: &str, close: &str) -> Option<(usize, usize)> {
+        let row = self.rows.get(self.cy)?.as_bytes();
+        let (open, close) = (open.as_bytes(), close.as_bytes());
+        let cx = self.cx.min(row.len());
+
+        let start = if open == close {
+            if row[cx..].starts_with(open) && !row[..cx].windows(open.len()).any(|w| w == open) {
+                cx
+            } else {
+                (0..cx).rev().find(|&p| row[p..].starts_with(open))?
+            }
+        } else {
+            let mut depth = 0;
+            let mut found = None;
+            for p in (0..cx + 1).rev() {
+                if p < cx && row[p..].starts_with(close) {
+                    depth += 1;
+                } else if row[p..].starts_with(open) {
+                    if depth == 0 {
+                        found = Some(p);
+                        break;
+                    }
+                    depth -= 1;
+                }
+            }
+            found?
+        };
+
+        let from = if open == close { cx.max(start + open.len()) } else { start + open.len() };
+        let mut depth = 0;
+        for p in from..row.len() {
+            if row[p..].starts_with(close) {
+                if depth == 0 || open == close {
+                    return Some((start, p));
+                }
+                depth -= 1;
+            } else if row[p..].starts_with(open) {
+                depth += 1;
+            }
+        }
+        None
+    }
+
+    fn replace_surrounding(&mut self, old: &str, new: Option<&str>) {
+        let (open, close) = surround_pair(old);
+        let (start, end) = match self.find_surrounding(&open, &close) {
+            Some(pos) => pos,
+            None => {
+                self.set_status_msg(tr!(NoSurrounding, open, close));
+                return;
+            }
+        };
+        let (new_open, new_close) = new.map_or((String::new(), String::new()), surround_pair);
+        self.record((self.cy, end), close.clone(), new_close.clone());
+        self.record((self.cy, start), open.clone(), new_open.clone());
+        let row = &mut self.rows[self.cy];
+        row.replace_range(end..end + close.len(), &new_close);
+        row.replace_range(start..start + open.len(), &new_open);
+        if self.cx >= end + close.len() {
+            self.cx = self.cx + new_open.len() + new_close.len() - open.len() - close.len();
+        } else if self.cx >= start + open.len() {
+            self.cx = self.cx + new_open.len() - open.len();
+        } else {
+            self.cx = start;
+        }
+        self.dirty = true;
+    }
+
+    pub(crate) fn delete_surrounding(&mut self) -> Result<()> {
+        if let Some(old) = self.prompt(tr!(DeleteSurrounding), |_, _, _| {})? {
+            self.replace_surrounding(&old, None);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn change_surrounding(&mut self) -> Result<()> {
+        let old = match self.prompt(tr!(ChangeSurrounding), |_, _, _| {})? {
+            Some(old) => old,
+            None => return Ok(()),
+        };
+        if let Some(new) = self.prompt(tr!(ChangeTo, old), |_, _, _| {})? {
+            self.replace_surrounding(&old, Some(&new));
+        }
+        Ok(())
+    }
+
+    /// The selected region as ordered (row, column) positions, end exclusive.
+    pub(crate) fn selection(&self) -> Option<((usize, usize), (usize, usize))> {
+        let anchor = self.anchor?;
+        let cursor = (self.cy, self.cx);
+        if anchor == cursor {
+            None
+        } else if anchor < cursor {
+            Some((anchor, cursor))
+        } else {
+            Some((cursor, anchor))
+        }
+    }
+
+    pub(crate) fn region_text(&self, start: (usize, usize), end: (usize, usize)) -> String {
+        self.rows.text_between(start, end)
+    }
+
+    pub(crate) fn delete_region(&mut self, start: (usize, usize), end: (usize, usize)) {
+        let removed = self.region_text(start, end);
+        self.record(start, removed, String::new());
+        let ((sy, sx), (ey, ex)) = (start, end);
+        let tail = if ey < self.rows.len() { self.rows[ey][ex..].to_string() } else { String::new() };
+        self.rows[sy].truncate(sx);
+        self.rows[sy] += &tail;
+        let last = ey.min(self.rows.len() - 1);
+        self.rows.drain(sy + 1..last + 1);
+        self.signs.lines_removed(sy + 1, last - sy);
+        self.cy = sy;
+        self.cx = sx;
+        self.anchor = None;
+        self.dirty = true;
+    }
+
+    /// Replaces the text between `start` and `end` with `text`, leaving the
+    /// cursor after it.
+    pub(crate) fn replace_region(&mut self, start: (usize, usize), end: (usize, usize), text: &str) {
+        let removed = self.region_text(start, end);
+        self.record(start, removed.clone(), text.to_string());
+        self.revert(start, &removed, text);
+    }
+
+    /// Takes spaces and tabs off the ends of lines, as one undoable edit,
+    /// returning how many lines had any. A read-only buffer is left alone.
+    pub(crate) fn strip_trailing(&mut self) -> usize {
+        if self.read_only {
+            return 0;
+        }
+        let mut stripped = 0;
+        self.history.begin_group();
+        for y in 0..self.rows.len() {
+            let len = self.rows[y].trim_end_matches([' ', '\t']).len();
+            if len < self.rows[y].len() {
+                let removed = self.rows[y][len..].to_string();
+                self.record((y, len), removed, String::new());
+                self.rows[y].truncate(len);
+                stripped += 1;
+            }
+        }
+        self.history.end_group();
+        if stripped > 0 {
+            self.cx = self.rows.get(self.cy).map_or(0, |row| self.cx.min(row.len()));
+            self.dirty = true;
+        }
+        stripped
+    }
+
+    /// What one level of indentation inserts.
+    pub(crate) fn indent_unit(&self) -> String {
+        match self.config.expand_tab {
+            Some(true) => " ".repeat(self.config.indent_width.unwrap_or(self.tab_stop)),
+            _ => "\t".to_string(),
+        }
+    }
+
+    /// What Tab inserts at the cursor: a tab, or with `expand_tab` the
+    /// spaces up to the next indentation stop.
+    pub(crate) fn soft_tab(&self) -> String {
+        match self.config.expand_tab {
+            Some(true) => {
+                let width = self.config.indent_width.unwrap_or(self.tab_stop).max(1);
+                let rx = self.rows.get(self.cy).map_or(0, |row| self.cx_to_rx(row, self.cx));
+                " ".repeat(width - rx % width)
+            }
+            _ => "\t".to_string(),
+        }
+    }
+
+    /// Takes one level of indentation off line `y`, if it has any.
+    pub(crate) fn outdent_line(&mut self, y: usize) {
+        let row = match self.rows.get(y) {
+            Some(row) => row,
+            None => return,
+        };
+        let n = if row.starts_with('\t') {
+            1
+        } else {
+            let width = self.config.indent_width.unwrap_or(self.tab_stop);
+            row.bytes().take(width).take_while(|&b| b == b' ').count()
+        };
+        if n == 0 {
+            return;
+        }
+        let removed = self.rows[y][..n].to_string();
+        self.record((y, 0), removed, String::new());
+        self.rows[y].drain(..n);
+        if self.cy == y {
+            self.cx = self.cx.saturating_sub(n);
+        }
+        self.dirty = true;
+    }
+
+    /// Indents or outdents every line the selection touches, then selects
+    /// those lines whole.
+    pub(crate) fn indent_selection(&mut self, indent: bool) {
+        let ((sy, _), (ey, ex)) = match self.selection() {
+            Some(selection) => selection,
+            None => return,
+        };
+        // A selection ending at the start of a line doesn't take it in.
+        let ey = if ex == 0 && ey > sy { ey - 1 } else { ey };
+        let ey = ey.min(self.rows.len().saturating_sub(1));
+        let unit = self.indent_unit();
+        for y in sy..ey + 1 {
+            if !indent {
+                self.outdent_line(y);
+            } else if !self.rows[y].is_empty() {
+                self.record((y, 0), String::new(), unit.clone());
+                self.rows[y].insert_str(0, &unit);
+                self.dirty = true;
+            }
+        }
+        self.anchor = Some((sy, 0));
+        self.cy = ey;
+        self.cx = self.rows.get(ey).map_or(0, |row| row.len());
+    }
+
+    /// The first and last rows the selection touches, or the cursor's row.
+    pub(crate) fn touched_rows(&self) -> Option<(usize, usize)> {
+        let (sy, ey) = match self.selection() {
+            // As when indenting, a selection ending at the start of a line
+            // doesn't take it in.
+            Some(((sy, _), (ey, ex))) => (sy, if ex == 0 && ey > sy { ey - 1 } else { ey }),
+            None => (self.cy, self.cy),
+        };
+        if sy >= self.rows.len() {
+            return None;
+        }
+        Some((sy, ey.min(self.rows.len() - 1)))
+    }
+
+    /// Moves the lines the selection touches, or the cursor's line, up or
+    /// down past the line next to them. The cursor and selection go along.
+    pub(crate) fn move_lines(&mut self, up: bool) {
+        let (sy, ey) = match self.touched_rows() {
+            Some(rows) => rows,
+            None => return,
+        };
+        if (up && sy == 0) || (!up && ey + 1 >= self.rows.len()) {
+            return;
+        }
+        let (first, last) = if up { (sy - 1, ey) } else { (sy, ey + 1) };
+        let mut lines: Vec<String> = (first..last + 1).map(|y| self.rows[y].clone()).collect();
+        if up {
+            lines.rotate_left(1);
+        } else {
+            lines.rotate_right(1);
+        }
+        let (cy, cx, anchor) = (self.cy, self.cx, self.anchor);
+        let shift = |y: usize| if up { y - 1 } else { y + 1 };
+        self.replace_region((first, 0), (last + 1, 0), &(lines.join("\n") + "\n"));
+        self.cy = shift(cy);
+        self.cx = cx;
+        self.anchor = anchor.map(|(y, x)| (shift(y), x));
+    }
+
+    /// Puts a copy of the lines the selection touches, or the cursor's
+    /// line, below them, moving the cursor and selection onto the copy.
+    pub(crate) fn duplicate_lines(&mut self) {
+        let (sy, ey) = match self.touched_rows() {
+            Some(rows) => rows,
+            None => return,
+        };
+        let lines: Vec<&str> = (sy..ey + 1).map(|y| self.rows[y].as_str()).collect();
+        let text = lines.join("\n") + "\n";
+        let n = ey + 1 - sy;
+        let (cy, cx, anchor) = (self.cy, self.cx, self.anchor);
+        // Inserted above, as there's always a line there to insert at; the
+        // two copies are the same.
+        self.replace_region((sy, 0), (sy, 0), &text);
+        self.cy = cy + n;
+        self.cx = cx;
+        self.anchor = anchor.map(|(y, x)| (y + n, x));
+    }
+
+    /// Deletes the lines the selection touches, or the cursor's line, wherever
+    /// the cursor is in it. The cursor stays in the same column on the line
+    /// that takes their place, or the one above when the last line went.
+    pub(crate) fn delete_lines(&mut self) {
+        let (sy, ey) = match self.touched_rows() {
+            Some(rows) => rows,
+            None => return,
+        };
+        let rx = self.cx_to_rx(&self.rows[self.cy.min(ey)], self.cx);
+        self.replace_region((sy, 0), (ey + 1, 0), "");
+        self.cy = sy.min(self.rows.len().saturating_sub(1));
+        self.cx = self.rows.get(self.cy).map_or(0, |row| self.rx_to_cx(row, rx));
+    }
+
+    /// What starts a line comment in the current file, if anything.
+    fn comment_leader(&self) -> Option<&'static str> {
+        let syntax = match self.highlighter.syntax {
+            Some(Backend::Builtin(syntax)) => Some(syntax),
+            // Sublime definitions don't say; the filetype database may.
+            _ => self.filename.as_ref().and_then(|name| syntax::for_file(name)),
+        };
+        syntax.map(|syntax| syntax.comment).filter(|leader| !leader.is_empty())
+    }
+
+    /// Comments out the lines the selection touches, or the cursor's line,
+    /// or uncomments them when they all are comments already. Comment
+    /// leaders line up at the smallest indentation among the lines; blank
+    /// lines are left alone.
+    pub(crate) fn toggle_comment(&mut self) {
+        let leader = match self.comment_leader() {
+            Some(leader) => leader,
+            None => {
+                self.set_status_msg(tr!(NoCommentSyntax));
+                return;
+            }
+        };
+        let (sy, ey) = match self.touched_rows() {
+            Some(rows) => rows,
+            None => return,
+        };
+        let lines: Vec<usize> = (sy..ey + 1).filter(|&y| !self.rows[y].trim().is_empty()).collect();
+        let commented = !lines.is_empty() &&
+            lines.iter().all(|&y| self.rows[y].trim_start().starts_with(leader));
+        let column = lines.iter().map(|&y| Editor::indent_of(&self.rows[y])).min().unwrap_or(0);
+        for y in lines {
+            let (at, removed, inserted) = if commented {
+                let at = Editor::indent_of(&self.rows[y]);
+                let mut end = at + leader.len();
+                if self.rows[y][end..].starts_with(' ') {
+                    end += 1;
+                }
+                (at, self.rows[y][at..end].to_string(), String::new())
+            } else {
+                (column, String::new(), format!("{} ", leader))
+            };
+            self.record((y, at), removed.clone(), inserted.clone());
+            self.rows[y].replace_range(at..at + removed.len(), &inserted);
+            // Keep the cursor and the selection on the same text.
+            let shift = |(py, px): (usize, usize)| {
+                if py != y || px < at {
+                    (py, px)
+                } else {
+                    (py, (px.max(at + removed.len()) - removed.len()) + inserted.len())
+                }
+            };
+            let (cy, cx) = shift((self.cy, self.cx));
+            self.cx = cx;
+            self.cy = cy;
+            self.anchor = self.anchor.map(shift);
+            self.dirty = true;
+        }
+    }
+
+    /// Changes the selection, or else the character under the cursor, to
+    /// upper or lower case.
+    pub(crate) fn change_case(&mut self, upper: bool) {
+        let (start, end) = match self.selection() {
+            Some(selection) => selection,
+            None if self.cy < self.rows.len() && self.cx < self.rows[self.cy].len() => {
+                ((self.cy, self.cx), (self.cy, next_boundary(&self.rows[self.cy], self.cx)))
+            }
+            None => return,
+        };
+        let text = self.region_text(start, end);
+        let changed = if upper { text.to_uppercase() } else { text.to_lowercase() };
+        let selected = self.anchor.is_some();
+        self.replace_region(start, end, &changed);
+        if selected {
+            self.anchor = Some(start);
+        }
+    }
+
+    fn indent_of(line: &str) -> usize {
+        line.len() - line.trim_start().len()
+    }
+
+    /// Candidate selections around the current one, smallest first: word,
+    /// lines, indentation block, paragraph and finally the whole buffer.
+    fn expansions(&self) -> Vec<((usize, usize), (usize, usize))> {
+        let mut candidates = vec![];
+        if self.rows.is_empty() {
+            return candidates;
+        }
+        let ((sy, _), (ey, _)) = self.selection()
+            .unwrap_or(((self.cy, self.cx), (self.cy, self.cx)));
+        let (sy, ey) = (sy.min(self.rows.len() - 1), ey.min(self.rows.len() - 1));
+        let line_end = |y: usize| (y, self.rows[y].len());
+
+        if let Some((start, end)) = self.word_bounds() {
+            candidates.push(((self.cy, start), (self.cy, end)));
+        }
+        candidates.push(((sy, 0), line_end(ey)));
+
+        let is_blank = |y: usize| self.rows[y].trim().is_empty();
+        let indent = (sy..ey + 1).filter(|&y| !is_blank(y))
+            .map(|y| Editor::indent_of(&self.rows[y])).min().unwrap_or(0);
+        if indent > 0 {
+            let inside = |y: usize| is_blank(y) || Editor::indent_of(&self.rows[y]) >= indent;
+            let (mut top, mut bottom) = (sy, ey);
+            while top > 0 && inside(top - 1) { top -= 1; }
+            while bottom + 1 < self.rows.len() && inside(bottom + 1) { bottom += 1; }
+            while top < sy && is_blank(top) { top += 1; }
+            while bottom > ey && is_blank(bottom) { bottom -= 1; }
+            candidates.push(((top, 0), line_end(bottom)));
+        }
+
+        let (mut top, mut bottom) = (sy, ey);
+        while top > 0 && !is_blank(top - 1) { top -= 1; }
+        while bottom + 1 < self.rows.len() && !is_blank(bottom + 1) { bottom += 1; }
+        candidates.push(((top, 0), line_end(bottom)));
+
+        candidates.push(((0, 0), line_end(self.rows.len() - 1)));
+        candidates
+    }
+
+    pub(crate) fn expand_selection(&mut self) {
+        let current = self.selection();
+        let next = self.expansions().into_iter().find(|&(start, end)| match current {
+            Some((cs, ce)) => start <= cs && end >= ce && (start, end) != (cs, ce),
+            None => start != end,
+        });
+        if let Some((start, end)) = next {
+            if let Some(current) = current {
+                self.expand_history.push(current);
+            }
+            self.anchor = Some(start);
+            self.cy = end.0;
+            self.cx = end.1;
+        }
+    }
+
+    pub(crate) fn shrink_selection(&mut self) {
+        match self.expand_history.pop() {
+            Some((start, end)) => {
+                self.anchor = Some(start);
+                self.cy = end.0;
+                self.cx = end.1;
+            }
+            None => self.anchor = None,
+        }
+    }
+}
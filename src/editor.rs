@@ -0,0 +1,2242 @@
+//! The editor: its state, the event loop, and the commands keys and the
+//! command prompt run. Drawing, input, editing and the other parts live in
+//! modules of their own, each adding to `Editor`.
+
+use std::{env, fs, io, mem, str};
+use std::io::{BufRead, BufReader, Read, Result, Seek, SeekFrom, Write};
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant, SystemTime};
+use std::ops::Sub;
+use std::sync::atomic::Ordering;
+
+use unicode_normalization::UnicodeNormalization;
+use termios::*;
+use libc;
+
+use args::FileArg;
+use atomic_save::{self, Saved};
+use clipboard::{self, Clipboard};
+use config::{self, Config};
+use keymap::{self, Action, Keymap};
+use kill_ring::KillRing;
+use loader::{self, Loader};
+use syntax::{self, Backend, Highlighter};
+use terminal::{self, PENDING_SIGNAL, RawMode, on_resize, on_terminate};
+use undo::{self, History};
+use calc;
+use complete::{CompletionMenu, WordCompletion};
+use crash;
+use diff;
+use hex::{self, HexView};
+use indent;
+use local_history;
+use lsp::{self, Diagnostic};
+use buffer::Buffer;
+use edit::{is_word_char, next_boundary, prev_boundary};
+use jumps::JumpList;
+use input::{Completion, Key, RawStdin, edits, is_mouse_or_focus, scrolls_back};
+use render::{Capabilities, Color, FrameStats, LineNumbers};
+use signs::{Sign, SignColumn, SignRegistry};
+use snippet::SnippetSession;
+use text::Rows;
+use vim::Vim;
+use watch::FileWatch;
+
+const TAB_STOP: usize = 8;
+const QUIT_TIMES: usize = 3;
+const STATUS_MSG_SECS: u64 = 5;
+const VIEW_STATE_ENTRIES: usize = 200;
+pub(crate) const FOLLOW_POLL_MS: u64 = 250;
+pub(crate) const SMOOTH_SCROLL_FRAME_MS: u64 = 16;
+/// Seconds after a change before the swap file is brought up to date.
+const SWAP_DELAY_SECS: u64 = 4;
+const MIB: u64 = 1 << 20;
+/// Commands offered when completing at the command prompt, besides the
+/// actions keys can be bound to. Short forms like `w` are left out.
+pub(crate) const COMMANDS: &[&str] = &[
+    "autoindent", "autopairs", "blame", "bnext", "bprev", "calc", "cd", "definition", "dropswap",
+    "edit", "endings", "expandtab", "filter", "follow", "format", "gomark", "grep", "hex",
+    "history", "hud", "indent", "list", "mark", "marks", "normalize", "number", "quit", "read",
+    "recover", "redo", "references", "relativenumber", "revert", "saveas", "scratch",
+    "screenreader", "set", "sign", "signcolumn", "smoothscroll", "speak", "stage", "steal",
+    "strip", "syntax", "tabbar", "tabstop", "trailing", "undo", "visualmove", "whitespace", "wq",
+    "wrap", "write",
+];
+/// Default cap on the memory a buffer may take, in MiB.
+const MEMORY_LIMIT_MIB: u64 = 1024;
+// Exit codes, following sysexits(3).
+pub(crate) const EXIT_USAGE: i32 = 64;
+const EXIT_IO: i32 = 74;
+const EXIT_TERMINAL: i32 = 71;
+
+/// Why the editor stopped: the status to exit with, and what to tell the
+/// user once the terminal is back to normal.
+pub struct Exit {
+    pub code: i32,
+    pub message: Option<String>,
+}
+
+impl Exit {
+    fn failed(message: String, code: i32) -> Exit {
+        Exit { code, message: Some(message) }
+    }
+}
+
+/// What the event loop does after a key or a signal.
+pub enum Flow {
+    Continue,
+    Quit(Exit),
+}
+
+pub struct Editor {
+    pub(crate) raw: RawMode,
+    pub(crate) stdin: RawStdin,
+    /// Readable once a signal has come in.
+    pub(crate) signal_wake: Option<File>,
+    pub(crate) stdout: io::Stdout,
+    pub(crate) numrows: usize,
+    pub(crate) screenrows: usize,
+    pub(crate) numcols: usize,
+    pub(crate) cx: usize,
+    pub(crate) cy: usize,
+    pub(crate) rx: usize,
+    pub(crate) rows: Rows,
+    pub(crate) rowoff: usize,
+    pub(crate) coloff: usize,
+    pub(crate) dirty: bool,
+    quit_times: usize,
+    /// Set by `:q` and the like, for the key that ran it to quit.
+    quit_requested: bool,
+    /// Presses of Ctrl-Q that quitting with unsaved changes takes.
+    quit_limit: usize,
+    pub(crate) tab_stop: usize,
+    pub(crate) status_secs: u64,
+    keymap: Keymap,
+    pub(crate) filename: Option<String>,
+    pub(crate) status_msg: String,
+    pub(crate) status_msg_time: Instant,
+    pub(crate) alt_screen: bool,
+    pub(crate) caps: Capabilities,
+    pub(crate) mouse: bool,
+    // Copied and cut snippets for this session, most recent last.
+    pub(crate) clipboard: Vec<String>,
+    pub(crate) registers: HashMap<char, String>,
+    /// Where copies also go, if anywhere.
+    pub(crate) system_clipboard: Option<Box<dyn Clipboard>>,
+    pub(crate) kill_ring: KillRing,
+    /// Where the last yank put its text, for the next yank to swap it.
+    pub(crate) yanked: Option<((usize, usize), (usize, usize))>,
+    /// The last word completion, for the next one to go on from.
+    pub(crate) word_completion: Option<WordCompletion>,
+    /// The language server's completions, while they're shown.
+    pub(crate) completion_menu: Option<CompletionMenu>,
+    /// The file and the start of the word completions were last asked
+    /// for in.
+    pub(crate) completion_asked: Option<(PathBuf, (usize, usize))>,
+    /// The snippet being filled in, if any.
+    pub(crate) snippet: Option<SnippetSession>,
+    /// Set when keys work as in vi.
+    pub(crate) vim: Option<Vim>,
+    pub(crate) jumps: JumpList,
+    /// The columns of the buffer's named marks; their lines are kept as
+    /// signs, which move with them.
+    pub(crate) mark_columns: HashMap<char, usize>,
+    pub(crate) last_action: Option<Action>,
+    pub(crate) pasted: String,
+    // Register chosen with Ctrl-G for the next copy, cut or paste.
+    pub(crate) pending_register: Option<char>,
+    // Selection anchor as (row, column); the cursor is the other end.
+    pub(crate) anchor: Option<(usize, usize)>,
+    // Selections replaced by expand-selection, for shrinking back.
+    pub(crate) expand_history: Vec<((usize, usize), (usize, usize))>,
+    // Modification time and size of the file when it was last read or
+    // written, to tell when someone else changes it.
+    pub(crate) file_stamp: Option<(SystemTime, u64)>,
+    save_on_blur: bool,
+    // When the last key was read, and whether one since the last autosave
+    // left changes.
+    pub(crate) last_input: Instant,
+    pub(crate) autosave_pending: bool,
+    // Directory of the first file opened, the default target of `cd`.
+    first_dir: Option<PathBuf>,
+    pub(crate) show_hud: bool,
+    pub(crate) show_trailing: bool,
+    /// Tabs and spaces are drawn as `→` and `·`.
+    pub(crate) show_whitespace: bool,
+    pub(crate) stats: FrameStats,
+    // Output written so far in the current frame.
+    pub(crate) frame_flush: Duration,
+    pub(crate) frame_bytes: usize,
+    // Each screen line as last drawn, so a frame only sends the ones that
+    // changed. Cleared when the whole screen has to be drawn again.
+    pub(crate) drawn: Vec<String>,
+    // Screen reader mode: plain sequential output instead of redraws.
+    pub(crate) accessible: bool,
+    // What was last announced in screen reader mode.
+    pub(crate) spoken_line: Option<(usize, String)>,
+    pub(crate) spoken_status: String,
+    pub signs: SignRegistry,
+    pub(crate) sign_column: SignColumn,
+    pub(crate) line_numbers: LineNumbers,
+    /// Long rows continue on the next screen row instead of scrolling
+    /// sideways.
+    pub(crate) wrap: bool,
+    /// Screen rows of the row at `rowoff` scrolled off the top while
+    /// wrapping.
+    pub(crate) wrapoff: usize,
+    /// While wrapping, Up, Down, Home and End go by screen rows.
+    visual_movement: bool,
+    pub(crate) history: History,
+    /// Counts changes to the text, for the language server to be sent it
+    /// only after one.
+    pub(crate) changes: u64,
+    /// Bytes a buffer may take before opening a file is refused; zero for
+    /// no limit.
+    memory_limit: u64,
+    /// Scratch buffers have no file and are never prompted about.
+    pub(crate) scratch: bool,
+    pub(crate) buffers: Vec<Buffer>,
+    /// Where the current buffer is among the tabs. The ones in `buffers`
+    /// follow it, wrapping round to the start.
+    pub(crate) tab: usize,
+    /// Whether to show the tabs when there's more than one buffer.
+    pub(crate) tab_bar: bool,
+    /// While following the file like `tail -f`, how far into it we've read.
+    pub(crate) follow: Option<u64>,
+    /// The rest of a large file, still being read in.
+    pub(crate) loading: Option<Loader>,
+    /// The lock file we hold on the open file.
+    pub(crate) lock: Option<PathBuf>,
+    /// The swap file we've written unsaved changes to, and when it's next
+    /// due to be brought up to date.
+    pub(crate) swap: Option<PathBuf>,
+    pub(crate) swap_due: Option<Instant>,
+    /// Set when another editor holds the lock; edits are refused.
+    pub(crate) read_only: bool,
+    /// Lines end in CR LF in the file, as on Windows.
+    pub(crate) crlf: bool,
+    /// Set for binary files, which are edited as bytes instead of rows.
+    pub(crate) hex: Option<HexView>,
+    /// Set when the buffer lists the directory `filename` names, one entry
+    /// a line.
+    pub(crate) listing: bool,
+    /// Set when the lines of the buffer start with `file:line:`, as grep
+    /// prints them.
+    pub(crate) locations: bool,
+    /// Who last changed each line, shown beside it until dismissed.
+    pub(crate) blame: Option<Vec<String>>,
+    /// What the language server found wrong with the buffer.
+    pub(crate) diagnostics: Vec<Diagnostic>,
+    /// Tells us when the open file changes, to reload it.
+    pub(crate) watch: Option<FileWatch>,
+    pub(crate) servers: lsp::Servers,
+    /// Typed and pasted text is put in NFC so composed and decomposed
+    /// accents don't get mixed.
+    pub(crate) normalize: bool,
+    global_config: Config,
+    /// The global settings with the project's `.kilo.toml` over them.
+    pub config: Config,
+    pub(crate) smooth_scroll: bool,
+    /// While a scroll is being animated, the first row on screen; `rowoff`
+    /// already holds where it will end up.
+    pub(crate) shown_rowoff: Option<usize>,
+    pub(crate) highlighter: Highlighter,
+}
+
+/// Where kilo keeps state between runs: `$XDG_STATE_HOME/kilo`.
+fn state_dir() -> Option<PathBuf> {
+    let dir = match env::var_os("XDG_STATE_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env::var_os("HOME")?).join(".local/state"),
+    };
+    Some(dir.join("kilo"))
+}
+
+/// Rough memory needed to hold a file of `size` bytes as rows, allowing
+/// for a `String` per line of about 32 bytes.
+fn estimated_memory(size: u64) -> u64 {
+    size + size / 32 * mem::size_of::<String>() as u64
+}
+
+/// The lock file for `path`: `.name.kilo-lock` beside it.
+fn lock_path(path: &Path) -> PathBuf {
+    let name = path.file_name().map_or(String::new(), |n| n.to_string_lossy().into_owned());
+    path.with_file_name(format!(".{}.kilo-lock", name))
+}
+
+fn lock_pid(lock: &Path) -> Option<libc::pid_t> {
+    fs::read_to_string(lock).ok()?.trim().parse().ok()
+}
+
+/// The pid of another live process holding the lock on `path`. Locks left
+/// behind by processes that are gone are ignored.
+fn lock_owner(path: &Path) -> Option<libc::pid_t> {
+    let pid = lock_pid(&lock_path(path))?;
+    if pid == std::process::id() as libc::pid_t {
+        return None;
+    }
+    let alive = unsafe { libc::kill(pid, 0) } == 0
+        || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM);
+    if alive { Some(pid) } else { None }
+}
+
+/// Where unsaved changes to `path` are kept until they're saved, in case
+/// the editor dies first.
+fn swap_path(path: &Path) -> PathBuf {
+    let name = path.file_name().map_or(String::new(), |n| n.to_string_lossy().into_owned());
+    path.with_file_name(format!(".{}.kilo-swp", name))
+}
+
+/// The entries of the directory `path` as listed in its buffer:
+/// directories first, each with a slash, and `../` above them.
+fn list_dir(path: &Path) -> Result<Rows> {
+    let mut entries: Vec<(bool, String)> = fs::read_dir(path)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| (!entry.path().is_dir(), entry.file_name().to_string_lossy().into_owned()))
+        .collect();
+    entries.sort();
+    let mut rows = Rows::new();
+    if path.parent().is_some() {
+        rows.push("../".to_string());
+    }
+    for (file, name) in entries {
+        rows.push(if file { name } else { name + "/" });
+    }
+    Ok(rows)
+}
+
+/// Whether the first line of the file at `path` ends in CR LF. The rest
+/// are taken to end the same way.
+fn first_line_crlf(path: &Path) -> bool {
+    let mut start = Vec::new();
+    if let Ok(file) = File::open(path) {
+        let _ = BufReader::new(file).take(64 << 10).read_until(b'\n', &mut start);
+    }
+    start.ends_with(b"\r\n")
+}
+
+fn env_usize(var: &str) -> Option<usize> {
+    env::var(var).ok().and_then(|v| v.parse().ok())
+}
+
+impl Drop for Editor {
+    fn drop(&mut self) {
+        self.release_locks();
+        self.restore_terminal();
+    }
+}
+
+impl Editor {
+    pub fn new() -> ::std::result::Result<Editor, Exit> {
+        let original = match Termios::from_fd(0) {
+            Ok(term) => term,
+            Err(e) => return Err(Exit::failed(tr!(TermAttrsFailed, e), EXIT_TERMINAL)),
+        };
+        let raw = match RawMode::enable(original) {
+            Ok(raw) => raw,
+            Err(e) => return Err(Exit::failed(tr!(RawModeFailed, e), EXIT_TERMINAL)),
+        };
+
+        Ok(Editor { 
+            raw,
+            stdin: RawStdin,
+            signal_wake: None,
+            stdout: io::stdout(),
+            numrows: 25,
+            screenrows: 27,
+            numcols: 80,
+            cx: 0,
+            cy: 0,
+            rx: 0,
+            rows: Rows::new(),
+            rowoff: 0,
+            coloff: 0,
+            dirty: false,
+            quit_times: QUIT_TIMES,
+            quit_requested: false,
+            quit_limit: QUIT_TIMES,
+            tab_stop: TAB_STOP,
+            status_secs: STATUS_MSG_SECS,
+            keymap: Keymap::default(),
+            filename: None,
+            status_msg: "".to_string(),
+            status_msg_time: Instant::now().sub(Duration::from_secs(100)),
+            alt_screen: true,
+            caps: Capabilities::detect(),
+            mouse: true,
+            clipboard: vec![],
+            registers: HashMap::new(),
+            system_clipboard: Some(clipboard::detect()),
+            kill_ring: KillRing::default(),
+            yanked: None,
+            word_completion: None,
+            completion_menu: None,
+            completion_asked: None,
+            snippet: None,
+            vim: None,
+            jumps: JumpList::default(),
+            mark_columns: HashMap::new(),
+            last_action: None,
+            pasted: String::new(),
+            pending_register: None,
+            anchor: None,
+            expand_history: vec![],
+            file_stamp: None,
+            save_on_blur: false,
+            last_input: Instant::now(),
+            autosave_pending: false,
+            first_dir: None,
+            show_hud: false,
+            show_trailing: false,
+            show_whitespace: false,
+            stats: FrameStats::default(),
+            frame_flush: Duration::from_secs(0),
+            frame_bytes: 0,
+            drawn: Vec::new(),
+            accessible: false,
+            spoken_line: None,
+            spoken_status: String::new(),
+            signs: SignRegistry::default(),
+            sign_column: SignColumn::Auto,
+            line_numbers: LineNumbers::Off,
+            wrap: false,
+            wrapoff: 0,
+            visual_movement: true,
+            history: History::new(undo::DEFAULT_MAX_EDITS, undo::DEFAULT_MAX_BYTES),
+            changes: 0,
+            memory_limit: MEMORY_LIMIT_MIB * MIB,
+            scratch: false,
+            buffers: Vec::new(),
+            tab: 0,
+            tab_bar: true,
+            follow: None,
+            loading: None,
+            lock: None,
+            swap: None,
+            swap_due: None,
+            read_only: false,
+            crlf: false,
+            hex: None,
+            listing: false,
+            locations: false,
+            blame: None,
+            diagnostics: Vec::new(),
+            watch: None,
+            servers: lsp::Servers::default(),
+            normalize: true,
+            global_config: Config::default(),
+            config: Config::default(),
+            smooth_scroll: false,
+            shown_rowoff: None,
+            highlighter: Highlighter::new(None),
+        })
+    }
+
+    /// Opens a file, refusing ones that would take more memory than the
+    /// configured limit.
+    pub fn open<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        if let Some(need) = self.over_memory_limit(path.as_ref()) {
+            let msg = tr!(TooLarge, need / MIB, self.memory_limit / MIB);
+            return Err(io::Error::other(msg));
+        }
+        self.open_anyway(path)
+    }
+
+    /// The estimated memory needed to edit `path`, when over the limit.
+    fn over_memory_limit(&self, path: &Path) -> Option<u64> {
+        let size = fs::metadata(path).ok()?.len();
+        let need = estimated_memory(size);
+        if self.memory_limit > 0 && need > self.memory_limit {
+            Some(need)
+        } else {
+            None
+        }
+    }
+
+    /// Approximate bytes held by the buffer and its undo history.
+    pub fn memory_usage(&self) -> u64 {
+        let rows: usize = self.rows.iter().map(|r| r.capacity()).sum();
+        let slots = self.rows.capacity() * mem::size_of::<String>();
+        (rows + slots + self.history.bytes()) as u64
+    }
+
+    pub fn open_anyway<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        // Relative paths are taken against the working directory, which `cd`
+        // may change later, so remember the absolute path.
+        let path: PathBuf = env::current_dir()?.join(path.as_ref()).components().collect();
+        // A file that doesn't exist yet is started empty and created on
+        // the first save.
+        let mut loading = None;
+        let mut hex = None;
+        let listing = path.is_dir();
+        let rows = if listing {
+            list_dir(&path)?
+        } else {
+            match File::open(&path) {
+                Ok(mut file) => {
+                    let size = file.metadata()?.len();
+                    let streamed = size > loader::STREAM_THRESHOLD;
+                    let mut data = Vec::new();
+                    if streamed {
+                        (&mut file).take(loader::FIRST_CHUNK).read_to_end(&mut data)?;
+                    } else {
+                        file.read_to_end(&mut data)?;
+                    }
+                    if hex::is_binary(&data) {
+                        file.read_to_end(&mut data)?;
+                        hex = Some(HexView::new(data));
+                        Rows::new()
+                    } else if streamed {
+                        file.seek(SeekFrom::Start(0))?;
+                        let mut loader = Loader::new(file, size);
+                        let mut rows = Rows::new();
+                        if !loader.load(&mut rows, loader::FIRST_CHUNK)? {
+                            loading = Some(loader);
+                        }
+                        rows
+                    } else {
+                        String::from_utf8_lossy(&data).lines().map(|line| line.to_string()).collect()
+                    }
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::NotFound => Rows::new(),
+                Err(e) => return Err(e),
+            }
+        };
+        self.save_view_state();
+        if self.first_dir.is_none() {
+            self.first_dir = path.parent().map(|p| p.to_path_buf());
+        }
+        self.filename = path.to_str().map(|x| x.to_string());
+        self.scratch = false;
+        self.follow = None;
+        self.release_lock();
+        self.remove_swap();
+        // Listings are never edited, so there's nothing to lock.
+        let owner = if listing { None } else { lock_owner(&path) };
+        self.read_only = owner.is_some() || listing;
+        self.listing = listing;
+        self.blame = None;
+        self.completion_menu = None;
+        self.snippet = None;
+        self.diagnostics.clear();
+        self.signs.clear_group(lsp::DIAGNOSTICS);
+        self.clear_marks();
+        if owner.is_none() && !listing {
+            self.take_lock(&path);
+        }
+        self.rows = rows;
+        self.loading = loading;
+        self.crlf = first_line_crlf(&path);
+        let binary = hex.is_some();
+        self.hex = hex;
+        self.history.clear();
+        self.changes += 1;
+        self.load_project_config(&path);
+        self.select_syntax();
+        self.dirty = false;
+        self.file_stamp = self.disk_stamp();
+        self.cx = 0;
+        self.cy = 0;
+        self.rowoff = 0;
+        self.coloff = 0;
+        self.anchor = None;
+        self.restore_view_state();
+        self.detect_indentation();
+        let usage = self.memory_usage();
+        if self.memory_limit > 0 && usage > self.memory_limit / 2 {
+            self.set_status_msg(tr!(MemoryHigh, usage / MIB, self.memory_limit / MIB));
+        }
+        if binary {
+            self.set_status_msg(tr!(BinaryFile));
+        }
+        if let Some(pid) = owner {
+            self.set_status_msg(tr!(LockedBy, pid));
+        } else if let Ok(modified) = fs::metadata(swap_path(&path)).and_then(|m| m.modified()) {
+            self.set_status_msg(tr!(SwapFound, local_history::age(modified)));
+        }
+        Ok(())
+    }
+
+    /// Reads the global settings and applies the ones that aren't per file.
+    fn load_global_config(&mut self) {
+        let path = match config::global_path() {
+            Some(path) => path,
+            None => return,
+        };
+        match Config::load(&path) {
+            Ok(config) => self.global_config = config,
+            Err(_) if !path.exists() => return,
+            Err(e) => {
+                self.set_status_msg(tr!(ConfigError, path.display(), e));
+                return;
+            }
+        }
+        self.config = self.global_config.clone();
+        let config = self.config.clone();
+        self.apply_settings(&config);
+        if let Some(on) = config.alt_screen {
+            self.alt_screen = on;
+        }
+        if let Some(on) = config.screen_reader {
+            self.accessible = on;
+        }
+        if let Some(edits) = config.undo_edits {
+            self.history.max_edits = edits;
+        }
+        if let Some(bytes) = config.undo_bytes {
+            self.history.max_bytes = bytes;
+        }
+    }
+
+    /// Applies the `KILO_*` environment variables, which go over the
+    /// settings files.
+    fn load_env(&mut self) {
+        if env::var_os("KILO_NO_ALT_SCREEN").is_some() {
+            self.alt_screen = false;
+        }
+        if env::var_os("KILO_SAVE_ON_BLUR").is_some() {
+            self.save_on_blur = true;
+        }
+        if env::var_os("KILO_SHOW_TRAILING").is_some() {
+            self.show_trailing = true;
+        }
+        if env::var_os("KILO_SCREEN_READER").is_some() {
+            self.accessible = true;
+        }
+        if env::var_os("KILO_SMOOTH_SCROLL").is_some() {
+            self.smooth_scroll = true;
+        }
+        if env::var_os("KILO_NO_NFC").is_some() {
+            self.normalize = false;
+        }
+        if let Some(edits) = env_usize("KILO_UNDO_EDITS") {
+            self.history.max_edits = edits;
+        }
+        if let Some(bytes) = env_usize("KILO_UNDO_BYTES") {
+            self.history.max_bytes = bytes;
+        }
+        if let Some(limit) = env_usize("KILO_MEMORY_LIMIT") {
+            self.memory_limit = limit as u64 * MIB;
+        }
+    }
+
+    /// Puts the settings `config` gives that the editor keeps outside of
+    /// `self.config` into effect.
+    fn apply_settings(&mut self, config: &Config) {
+        if let Some(times) = config.quit_times {
+            self.quit_limit = times;
+            self.quit_times = times;
+        }
+        if let Some(secs) = config.status_timeout {
+            self.status_secs = secs as u64;
+        }
+        match config.line_numbers.as_deref() {
+            Some("absolute") => self.line_numbers = LineNumbers::Absolute,
+            Some("relative") => self.line_numbers = LineNumbers::Relative,
+            Some(_) => self.line_numbers = LineNumbers::Off,
+            None => {}
+        }
+        if let Some(shown) = config.tab_bar {
+            self.tab_bar = shown;
+        }
+        if let Some(show) = config.show_trailing {
+            self.show_trailing = show;
+        }
+        if let Some(show) = config.show_whitespace {
+            self.show_whitespace = show;
+        }
+        if let Some(ref name) = config.clipboard {
+            self.system_clipboard = clipboard::by_name(name);
+        }
+        if let Some(width) = config.tab_stop {
+            self.tab_stop = width;
+        }
+        if config.syntax.is_some() {
+            self.select_syntax();
+        }
+        for &(key, action) in config.keys.iter().flatten() {
+            self.keymap.bind(key, action);
+        }
+        if let Some(on) = config.vim {
+            self.set_vim(on);
+        }
+        if let Some(on) = config.save_on_blur {
+            self.save_on_blur = on;
+        }
+        if let Some(on) = config.smooth_scroll {
+            self.smooth_scroll = on;
+        }
+        if let Some(on) = config.normalize {
+            self.normalize = on;
+        }
+        if let Some(limit) = config.memory_limit {
+            self.memory_limit = limit as u64 * MIB;
+        }
+    }
+
+    /// Applies the `.kilo.toml` of the project holding `path`, if any.
+    fn load_project_config(&mut self, path: &Path) {
+        self.config = self.global_config.clone();
+        if let Some(project) = path.parent().and_then(config::find_project) {
+            match Config::load(&project) {
+                Ok(local) => self.config.merge(local),
+                Err(e) => self.set_status_msg(tr!(ConfigError, project.display(), e)),
+            }
+        }
+        let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        let filetype = syntax::for_file(name).map_or("", |syntax| syntax.filetype);
+        self.config.merge_filetype(&[filetype, ext, name]);
+        self.tab_stop = self.config.tab_stop.unwrap_or(TAB_STOP);
+    }
+
+    /// Marks `path` as being edited by us with a lock file holding our pid.
+    /// Locking is advisory: failing to write the lock doesn't stop editing.
+    fn take_lock(&mut self, path: &Path) {
+        let lock = lock_path(path);
+        if fs::write(&lock, format!("{}\n", std::process::id())).is_ok() {
+            self.lock = Some(lock);
+        }
+    }
+
+    fn release_lock(&mut self) {
+        if let Some(lock) = self.lock.take() {
+            if lock_pid(&lock) == Some(std::process::id() as libc::pid_t) {
+                let _ = fs::remove_file(lock);
+            }
+        }
+    }
+
+    fn release_locks(&mut self) {
+        self.release_lock();
+        for buffer in &mut self.buffers {
+            if let Some(lock) = buffer.lock.take() {
+                if lock_pid(&lock) == Some(std::process::id() as libc::pid_t) {
+                    let _ = fs::remove_file(lock);
+                }
+            }
+        }
+    }
+
+    /// Takes the lock over from whoever holds it, making the buffer
+    /// editable.
+    fn steal_lock(&mut self) {
+        let path = match self.filename {
+            Some(ref f) => PathBuf::from(f),
+            None => return,
+        };
+        self.take_lock(&path);
+        self.read_only = false;
+        self.set_status_msg(tr!(LockStolen));
+    }
+
+    /// Per-file view state lives in one line per file, `path` followed by
+    /// tab separated `key=value` fields, most recently used last.
+    fn view_state_path() -> Option<PathBuf> {
+        Some(state_dir()?.join("filestate"))
+    }
+
+    fn read_view_states() -> Vec<String> {
+        Editor::view_state_path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map_or(vec![], |s| s.lines().map(|l| l.to_string()).collect())
+    }
+
+    fn save_view_state(&self) {
+        let (filename, path) = match (self.filename.as_ref(), Editor::view_state_path()) {
+            (Some(f), Some(p)) => (f, p),
+            _ => return,
+        };
+        let prefix = format!("{}\t", filename);
+        let mut states: Vec<String> = Editor::read_view_states().into_iter()
+            .filter(|l| !l.starts_with(&prefix))
+            .collect();
+        let numbers = match self.line_numbers {
+            LineNumbers::Off => "off",
+            LineNumbers::Absolute => "absolute",
+            LineNumbers::Relative => "relative",
+        };
+        states.push(format!(
+            "{}cy={}\tcx={}\trowoff={}\tcoloff={}\twrap={}\tnumbers={}",
+            prefix, self.cy, self.cx, self.rowoff, self.coloff, self.wrap as u8, numbers));
+        let skip = states.len().saturating_sub(VIEW_STATE_ENTRIES);
+        if let Some(dir) = path.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        // Losing the view state is harmless, so failures are ignored.
+        let _ = fs::write(path, states[skip..].join("\n") + "\n");
+    }
+
+    fn restore_view_state(&mut self) {
+        let prefix = match self.filename {
+            Some(ref f) => format!("{}\t", f),
+            None => return,
+        };
+        let states = Editor::read_view_states();
+        let line = match states.iter().rev().find(|l| l.starts_with(&prefix)) {
+            Some(line) => line,
+            None => return,
+        };
+        for field in line[prefix.len()..].split('\t') {
+            let mut kv = field.splitn(2, '=');
+            let (key, value) = match (kv.next(), kv.next()) {
+                (Some(k), Some(v)) => (k, v),
+                _ => continue,
+            };
+            match (key, value.parse().ok()) {
+                ("cy", Some(n)) => self.cy = n,
+                ("cx", Some(n)) => self.cx = n,
+                ("rowoff", Some(n)) => self.rowoff = n,
+                ("coloff", Some(n)) => self.coloff = n,
+                ("wrap", _) => {
+                    self.wrap = value == "1";
+                    self.wrapoff = 0;
+                }
+                ("numbers", _) => self.line_numbers = match value {
+                    "absolute" => LineNumbers::Absolute,
+                    "relative" => LineNumbers::Relative,
+                    _ => LineNumbers::Off,
+                },
+                _ => {}
+            }
+        }
+        self.cy = self.cy.min(self.rows.len());
+        self.cx = self.cx.min(self.rows.get(self.cy).map_or(0, |r| r.len()));
+        self.rowoff = self.rowoff.min(self.cy);
+    }
+
+    pub fn init(&mut self) -> ::std::result::Result<(), Exit> {
+       crash::install(self.raw.original(), state_dir());
+       // First, as some settings are for how the terminal is set up.
+       self.load_global_config();
+       self.load_env();
+       if self.alt_screen {
+           // Draw on the alternate screen so the shell's scrollback survives.
+           if let Err(e) = self.write("\x1b[?1049h") {
+               return Err(Exit::failed(tr!(ScreenFailed, e), EXIT_TERMINAL));
+           }
+       }
+       if let Err(e) = self.set_mouse_capture(true) {
+           return Err(Exit::failed(tr!(MouseFailed, e), EXIT_TERMINAL));
+       }
+       match terminal::signal_pipe() {
+           Ok(wake) => self.signal_wake = Some(wake),
+           Err(e) => return Err(Exit::failed(tr!(SignalFailed, e), EXIT_TERMINAL)),
+       }
+       let handlers: [(libc::c_int, extern "C" fn(libc::c_int)); 3] =
+           [(libc::SIGTERM, on_terminate), (libc::SIGHUP, on_terminate), (libc::SIGWINCH, on_resize)];
+       for &(signal, handler) in &handlers {
+           // No SA_RESTART, so that a blocked poll() returns and the event
+           // loop gets to see the signal.
+           let res = unsafe {
+               let mut action: libc::sigaction = mem::zeroed();
+               action.sa_sigaction = handler as usize;
+               libc::sigemptyset(&mut action.sa_mask);
+               libc::sigaction(signal, &action, std::ptr::null_mut())
+           };
+           if res != 0 {
+               let e = io::Error::last_os_error();
+               return Err(Exit::failed(tr!(SignalFailed, e), EXIT_TERMINAL));
+           }
+       }
+       if let Err(e) = self.write("\x1b[?1004h") {
+           return Err(Exit::failed(tr!(FocusFailed, e), EXIT_TERMINAL));
+       }
+       // Pastes arrive marked, so they aren't typed in key by key.
+       if let Err(e) = self.write("\x1b[?2004h") {
+           return Err(Exit::failed(tr!(PasteFailed, e), EXIT_TERMINAL));
+       }
+       match self.get_window_size() {
+           Ok(s) => self.set_window_size(s),
+           Err(e) => return Err(Exit::failed(tr!(WindowSizeFailed, e), EXIT_TERMINAL)),
+       }
+       Ok(())
+    }
+
+    /// Opens the files named on the command line, each in a buffer of its
+    /// own, or leaves the empty unnamed buffer when there are none.
+    pub fn open_files(&mut self, files: &[FileArg]) -> ::std::result::Result<(), Exit> {
+        for (i, file) in files.iter().enumerate() {
+            if i > 0 {
+                let buffer = self.take_buffer();
+                self.set_aside(buffer);
+            }
+            if let Err(e) = self.open(&file.path) {
+                return Err(Exit::failed(format!("{}: {}", file.path, e), EXIT_IO));
+            }
+            if let Some(line) = file.line {
+                self.go_to(line, file.col.unwrap_or(1));
+            }
+        }
+        if files.len() > 1 {
+            // Come back around to the first file.
+            self.next_buffer();
+        }
+        if self.status_msg.is_empty() {
+            self.set_status_msg(tr!(Help));
+        }
+        Ok(())
+    }
+
+    /// Puts the cursor on `line` at `col`, both counted from 1 in
+    /// characters, with the line in the middle of the screen.
+    pub(crate) fn go_to(&mut self, line: usize, col: usize) {
+        if self.loading.is_some() && line > self.rows.len() {
+            self.finish_loading();
+        }
+        self.cy = line.saturating_sub(1).min(self.rows.len().saturating_sub(1));
+        let row = self.rows.get(self.cy).map_or("", |row| row.as_str());
+        self.cx = row.char_indices().nth(col.saturating_sub(1)).map_or(row.len(), |(i, _)| i);
+        self.rowoff = self.cy.saturating_sub(self.numrows / 2);
+        self.wrapoff = 0;
+        self.coloff = 0;
+        self.anchor = None;
+    }
+
+    /// Runs the event loop until the user quits or something fails.
+    pub fn run(&mut self) -> Exit {
+        loop {
+            if let Err(e) = self.refresh_screen() {
+                return Exit::failed(tr!(DrawFailed, e), EXIT_TERMINAL);
+            }
+            match self.process_key() {
+                Ok(Flow::Continue) => {}
+                Ok(Flow::Quit(exit)) => return exit,
+                Err(e) => return Exit::failed(tr!(InputFailed, e), EXIT_TERMINAL),
+            }
+        }
+    }
+
+    pub(crate) fn move_cursor(&mut self, key: Key) {
+        match key {
+            Key::Up | Key::Down if self.wrap && self.visual_movement && self.cy < self.rows.len() => {
+                self.move_visual(key);
+                return;
+            }
+            Key::Left => {
+                if self.cx > 0 {
+                    self.cx = prev_boundary(&self.rows[self.cy], self.cx);
+                } else if self.cy > 0 {
+                    self.cy -= 1;
+                    self.cx = self.rows[self.cy].len();
+                }
+            }
+            Key::Right if self.cy < self.rows.len() => {
+                if self.cx < self.rows[self.cy].len() {
+                    self.cx = next_boundary(&self.rows[self.cy], self.cx);
+                } else {
+                    self.cy += 1;
+                    self.cx = 0;
+                }
+            }
+            Key::Up | Key::Down => {
+                // Keep to the same screen column; the same byte offset
+                // could be in the middle of a character on the new line.
+                let rx = self.rows.get(self.cy).map_or(0, |row| self.cx_to_rx(row, self.cx));
+                if key == Key::Up && self.cy > 0 {
+                    self.cy -= 1;
+                } else if key == Key::Down && self.cy < self.rows.len() {
+                    self.cy += 1;
+                }
+                self.cx = self.rows.get(self.cy).map_or(0, |row| self.rx_to_cx(row, rx));
+            }
+            _ => {}
+        };
+
+        let rowlen = if self.rows.len() > self.cy {
+            self.rows[self.cy].len()
+        } else {
+            0
+        };
+        if self.cx > rowlen {
+            self.cx = rowlen;
+        };
+    }
+
+    /// Moves past the next word, or back to the start of the one before:
+    /// first over anything that isn't part of a word, line breaks included,
+    /// then over the word.
+    pub(crate) fn move_word(&mut self, forward: bool) {
+        let mut in_word = false;
+        loop {
+            let row = self.rows.get(self.cy).map_or("", |row| row.as_str());
+            let c = if forward { row[self.cx..].chars().next() } else { row[..self.cx].chars().next_back() };
+            match c {
+                Some(c) => {
+                    let word = is_word_char(c);
+                    if in_word && !word {
+                        break;
+                    }
+                    in_word |= word;
+                    if forward { self.cx += c.len_utf8() } else { self.cx -= c.len_utf8() }
+                }
+                None if in_word => break,
+                None if forward && self.cy + 1 < self.rows.len() => {
+                    self.cy += 1;
+                    self.cx = 0;
+                }
+                None if !forward && self.cy > 0 => {
+                    self.cy -= 1;
+                    self.cx = self.rows[self.cy].len();
+                }
+                None => break,
+            }
+        }
+    }
+
+    /// Up or Down by one screen row of a wrapped row, keeping to the same
+    /// column within it.
+    fn move_visual(&mut self, key: Key) {
+        let rx = self.cx_to_rx(&self.rows[self.cy], self.cx);
+        let (seg, start, starts) = self.wrap_segment(self.cy, rx);
+        let offset = rx - start;
+        let target = if key == Key::Up {
+            if seg > 0 {
+                Some((starts[seg - 1] + offset).min(start - 1))
+            } else if self.cy > 0 {
+                self.cy -= 1;
+                self.wrap_points(self.cy).last().map(|&last| last + offset)
+            } else {
+                None
+            }
+        } else if let Some(&next) = starts.get(seg + 1) {
+            Some((next + offset).min(starts.get(seg + 2).map_or(usize::MAX, |&end| end - 1)))
+        } else {
+            self.cy += 1;
+            Some(offset)
+        };
+        if let Some(rx) = target {
+            self.cx = self.rows.get(self.cy).map_or(0, |row| self.rx_to_cx(row, rx));
+        }
+    }
+
+    pub fn process_key(&mut self) -> Result<Flow> {
+        self.update_watch();
+        self.lsp_sync();
+        let timeout = self.next_timeout();
+        if !self.poll_input(timeout)? {
+            if let Some(exit) = self.handle_signals() {
+                return Ok(Flow::Quit(exit));
+            }
+            // A resize interrupts the wait, so the redraw that follows
+            // already uses the new size.
+            self.handle_resize()?;
+            if self.follow.is_some() {
+                self.read_appended()?;
+            }
+            if self.loading.is_some() {
+                self.load_more(loader::IDLE_CHUNK);
+            }
+            self.reload_if_changed()?;
+            self.handle_lsp();
+            self.autosave();
+            self.update_swap(false);
+            self.animate_scroll();
+            // A timer expired or a signal arrived; let the caller redraw.
+            return Ok(Flow::Continue);
+        }
+        let ready = Instant::now();
+        // Typing never waits for an animation: it just ends.
+        self.shown_rowoff = None;
+        let c = self.read_key()?;
+        let decoded = Instant::now();
+        crash::note_key(match c {
+            Key::Char(_) if !crash::with_contents() => "Char(_)".to_string(),
+            c => format!("{:?}", c),
+        });
+        let dirty = self.dirty;
+        // Whatever one key does is undone in one step.
+        self.history.begin_group();
+        let res = self.handle_key(c);
+        self.history.end_group();
+        self.last_input = Instant::now();
+        if self.dirty {
+            self.autosave_pending = true;
+            if self.swap_due.is_none() {
+                self.swap_due = Some(self.last_input + Duration::from_secs(SWAP_DELAY_SECS));
+            }
+        }
+        self.update_swap(false);
+        crash::note_buffer(
+            format!("{} lines={} cursor={}:{} dirty={} scratch={} read_only={}",
+                    self.display_name(), self.rows.len(), self.cy + 1, self.cx + 1,
+                    self.dirty, self.scratch, self.read_only),
+            if crash::with_contents() { Some(self.rows_to_string()) } else { None });
+        if self.follow.is_some() && (self.dirty != dirty || scrolls_back(c, self.keymap.get(c))) {
+            self.follow = None;
+            self.set_status_msg(tr!(FollowPaused));
+        }
+        self.stats.decode = decoded.duration_since(ready);
+        self.stats.update = decoded.elapsed();
+        res
+    }
+
+    fn handle_key(&mut self, c: Key) -> Result<Flow> {
+        let register = self.pending_register.take();
+        let action = self.keymap.get(c);
+
+        // Only looking around goes ahead of a file still loading; anything
+        // else may need all of it.
+        let browsing = match action {
+            Some(Action::Up) | Some(Action::Down) | Some(Action::Left) | Some(Action::Right) |
+            Some(Action::WordLeft) | Some(Action::WordRight) | Some(Action::PageUp) | Some(Action::PageDown) | Some(Action::Home) | Some(Action::End) |
+            Some(Action::Quit) | Some(Action::Redraw) | Some(Action::SpeakPosition) => true,
+            Some(_) => false,
+            None => !edits(c, action),
+        };
+        if self.loading.is_some() && !browsing {
+            self.finish_loading();
+        }
+
+        if self.listing && action == Some(Action::Newline) {
+            self.open_listed()?;
+            return Ok(Flow::Continue);
+        }
+        if self.locations && action == Some(Action::Newline) {
+            self.open_location()?;
+            return Ok(Flow::Continue);
+        }
+
+        if self.vim_key(c, action)? {
+            self.quit_times = self.quit_limit;
+            return Ok(Flow::Continue);
+        }
+
+        // Edits would leave the annotations beside the wrong lines.
+        if self.blame.is_some() && (c == Key::Char(b'\x1b') || edits(c, action) ||
+                                    action == Some(Action::Undo) || action == Some(Action::Redo)) {
+            self.blame = None;
+            if c == Key::Char(b'\x1b') {
+                return Ok(Flow::Continue);
+            }
+        }
+
+        if self.read_only && edits(c, action) {
+            self.set_status_msg(tr!(ReadOnly));
+            return Ok(Flow::Continue);
+        }
+
+        if self.hex.is_some() && self.hex_key(c, action) {
+            self.quit_times = self.quit_limit;
+            return Ok(Flow::Continue);
+        }
+
+        if self.completion_key(c, action) || self.snippet_key(c, action) {
+            return Ok(Flow::Continue);
+        }
+
+        match action {
+            Some(Action::Quit) => {
+                if self.unsaved() && self.quit_times > 0 {
+                    let s = tr!(QuitUnsaved, keymap::key_name(c), self.quit_times);
+                    self.set_status_msg(s);
+                    self.quit_times -= 1;
+                    return Ok(Flow::Continue);
+                }
+                return Ok(self.quit());
+            }
+            Some(action) => self.run_action(action, register)?,
+            None => match c {
+                Key::Mouse(event) => self.handle_mouse(event),
+                Key::FocusOut => self.on_focus_lost(),
+                Key::FocusIn => self.on_focus_gained(),
+                Key::Paste => {
+                    let text = mem::take(&mut self.pasted);
+                    let text = if self.normalize { text.nfc().collect() } else { text };
+                    self.insert_text(&text);
+                }
+                Key::Char(b'\x1b') => {}
+                Key::Char(c) if c >= 0x80 => self.insert_utf8(c),
+                Key::Char(c) => self.type_char(c as char),
+                _ => {}
+            },
+        }
+        match action {
+            Some(Action::ExpandSelection) | Some(Action::ShrinkSelection) => {}
+            Some(Action::SelectRegister) => self.expand_history.clear(),
+            Some(action) if action.keeps_selection() => self.expand_history.clear(),
+            None if is_mouse_or_focus(c) => self.expand_history.clear(),
+            _ => {
+                self.anchor = None;
+                self.expand_history.clear();
+            }
+        }
+        self.update_completion(c, action);
+        self.last_action = action;
+        self.quit_times = self.quit_limit;
+        if mem::replace(&mut self.quit_requested, false) {
+            return Ok(self.quit());
+        }
+        Ok(Flow::Continue)
+    }
+
+    fn quit(&mut self) -> Flow {
+        self.save_view_state();
+        // Changes left unsaved now are meant to be thrown away.
+        self.remove_swaps();
+        Flow::Quit(Exit { code: 0, message: None })
+    }
+
+    /// Runs what a key is bound to, other than quitting.
+    pub(crate) fn run_action(&mut self, action: Action, register: Option<char>) -> Result<()> {
+        match action {
+            Action::Quit => {}
+            Action::Up => self.move_cursor(Key::Up),
+            Action::Down => self.move_cursor(Key::Down),
+            Action::Left => self.move_cursor(Key::Left),
+            Action::Right => self.move_cursor(Key::Right),
+            Action::WordLeft => self.move_word(false),
+            Action::WordRight => self.move_word(true),
+            Action::PageUp | Action::PageDown => {
+                let here = (self.cy, self.cx);
+                self.jump_from(here);
+                if action == Action::PageUp {
+                    self.cy = self.rowoff;
+                } else {
+                    self.cy = self.rowoff + self.numrows - 1;
+                    if self.cy > self.rows.len() {
+                        self.cy = self.rows.len();
+                    }
+                }
+                for _ in 0..self.numrows {
+                    self.move_cursor(if action == Action::PageUp { Key::Up } else { Key::Down });
+                }
+            }
+            Action::Home | Action::End if self.wrap && self.visual_movement && self.cy < self.rows.len() => {
+                let rx = self.cx_to_rx(&self.rows[self.cy], self.cx);
+                let (seg, start, starts) = self.wrap_segment(self.cy, rx);
+                self.cx = match starts.get(seg + 1) {
+                    _ if action == Action::Home => self.rx_to_cx(&self.rows[self.cy], start),
+                    Some(&next) => self.rx_to_cx(&self.rows[self.cy], next - 1),
+                    None => self.rows[self.cy].len(),
+                };
+            }
+            Action::Home => self.cx = 0,
+            Action::End  => {
+                if self.cy < self.rows.len() {
+                    self.cx = self.rows[self.cy].len();
+                } else {
+                    self.cx = 0;
+                }
+            }
+            Action::Save if self.changed_on_disk() => self.resolve_disk_change()?,
+            Action::Save => {
+                let res = self.save();
+                self.report_save(res);
+            }
+            Action::SaveAs => {
+                let res = self.save_as(None);
+                self.report_save(res);
+            }
+            Action::Find => self.find()?,
+            Action::Replace => self.replace(None, None, false)?,
+            Action::Undo => self.undo(),
+            Action::Redo => self.redo(),
+            Action::FindRegex => self.find_regex()?,
+            Action::SpeakPosition => self.speak_position()?,
+            Action::Open => self.open_prompt()?,
+            Action::FindFile => self.find_file()?,
+            Action::Grep => self.grep("")?,
+            Action::Command => self.command_prompt()?,
+            Action::ToggleMouse => {
+                let enabled = !self.mouse;
+                self.set_mouse_capture(enabled)?;
+                self.set_status_msg(if enabled { tr!(MouseOn) } else { tr!(MouseOff) });
+            }
+            Action::SetMark => {
+                self.anchor = match self.anchor {
+                    Some(_) => None,
+                    None => Some((self.cy, self.cx)),
+                };
+                self.set_status_msg(if self.anchor.is_some() { tr!(MarkSet) } else { tr!(MarkCleared) });
+            }
+            Action::SelectUp | Action::SelectDown | Action::SelectLeft |
+            Action::SelectRight | Action::SelectHome | Action::SelectEnd |
+            Action::SelectWordLeft | Action::SelectWordRight => {
+                if self.anchor.is_none() {
+                    self.anchor = Some((self.cy, self.cx));
+                }
+                let motion = match action {
+                    Action::SelectUp => Action::Up,
+                    Action::SelectDown => Action::Down,
+                    Action::SelectLeft => Action::Left,
+                    Action::SelectRight => Action::Right,
+                    Action::SelectHome => Action::Home,
+                    Action::SelectWordLeft => Action::WordLeft,
+                    Action::SelectWordRight => Action::WordRight,
+                    _ => Action::End,
+                };
+                self.run_action(motion, register)?;
+            }
+            Action::Indent => match self.selection() {
+                Some(_) => self.indent_selection(true),
+                None => {
+                    let unit = self.soft_tab();
+                    self.insert_text(&unit);
+                }
+            },
+            Action::Outdent => match self.selection() {
+                Some(_) => self.indent_selection(false),
+                None => {
+                    let y = self.cy;
+                    self.outdent_line(y);
+                }
+            },
+            Action::Upcase | Action::Downcase => self.change_case(action == Action::Upcase),
+            Action::KillLine => self.kill_line(true),
+            Action::KillLineBackward => self.kill_line(false),
+            Action::MoveLinesUp => self.move_lines(true),
+            Action::MoveLinesDown => self.move_lines(false),
+            Action::DuplicateLines => self.duplicate_lines(),
+            Action::DeleteLines => self.delete_lines(),
+            Action::ToggleComment => self.toggle_comment(),
+            Action::Yank => self.yank(),
+            Action::NextBuffer => self.next_buffer(),
+            Action::PrevBuffer => self.prev_buffer(),
+            Action::SelectRegister => self.select_register()?,
+            Action::Copy => match self.selection() {
+                Some((start, end)) => {
+                    let text = self.region_text(start, end);
+                    self.store_register(register, text, false);
+                    self.set_status_msg(tr!(SelectionCopied));
+                }
+                None => self.copy_line(register),
+            },
+            Action::Cut => match self.selection() {
+                Some((start, end)) => {
+                    let text = self.region_text(start, end);
+                    self.delete_region(start, end);
+                    self.store_register(register, text, true);
+                }
+                None => self.cut_line(register),
+            },
+            Action::ExpandSelection => self.expand_selection(),
+            Action::ShrinkSelection => self.shrink_selection(),
+            Action::Paste => self.paste_register(register),
+            Action::PasteHistory => self.paste_from_history()?,
+            Action::Surround => self.surround()?,
+            Action::DeleteSurrounding => self.delete_surrounding()?,
+            Action::ChangeSurrounding => self.change_surrounding()?,
+            Action::CompletePath => self.complete_path_in_buffer()?,
+            Action::CompleteWord => self.complete_word(),
+            Action::GotoDefinition => self.lsp_ask("textDocument/definition"),
+            Action::FindReferences => self.lsp_ask("textDocument/references"),
+            Action::JumpBack => self.jump_back()?,
+            Action::JumpForward => self.jump_forward()?,
+            Action::Newline => self.newline_and_indent(),
+            Action::Backspace | Action::Delete => match self.selection() {
+                Some((start, end)) => self.delete_region(start, end),
+                None if action == Action::Backspace => self.backspace(),
+                None => {
+                    self.move_cursor(Key::Right);
+                    self.del_char();
+                }
+            },
+            Action::Redraw => self.redraw()?,
+        }
+        Ok(())
+    }
+
+    /// Follows the indentation the file already has, saying what it is.
+    fn detect_indentation(&mut self) {
+        if self.config.detect_indent == Some(false) {
+            return;
+        }
+        let style = match indent::detect(&self.rows) {
+            Some(style) => style,
+            None => return,
+        };
+        match style {
+            indent::Style::Tabs => self.config.expand_tab = Some(false),
+            indent::Style::Spaces(width) => {
+                self.config.expand_tab = Some(true);
+                self.config.indent_width = Some(width);
+            }
+        }
+        self.report_indentation();
+    }
+
+    /// Says in the status bar what Tab inserts and how tabs are shown.
+    pub(crate) fn report_indentation(&mut self) {
+        let unit = match self.config.expand_tab {
+            Some(true) => tr!(IndentSpaces, self.config.indent_width.unwrap_or(self.tab_stop)),
+            _ => tr!(IndentTabs),
+        };
+        self.set_status_msg(tr!(Indentation, unit, self.tab_stop));
+    }
+
+    fn report_save(&mut self, res: Result<Option<Saved>>) {
+        match res {
+            Ok(Some(Saved { bytes, in_place: None })) => self.set_status_msg(tr!(BytesWritten, bytes)),
+            Ok(Some(Saved { bytes, in_place: Some(e) })) => self.set_status_msg(tr!(WrittenInPlace, bytes, e)),
+            Ok(None) => self.set_status_msg(tr!(SaveAborted)),
+            Err(e) => self.set_status_msg(tr!(SaveFailed, e)),
+        }
+    }
+
+    fn command_prompt(&mut self) -> Result<()> {
+        if let Some(command) = self.prompt_with(":", Completion::Commands, |_, _, _| {})? {
+            self.execute_command(command.trim());
+        }
+        Ok(())
+    }
+
+    fn execute_command(&mut self, command: &str) {
+        if let Err(e) = self.run_command(command) {
+            self.set_status_msg(tr!(CommandFailed, e));
+        }
+    }
+
+    fn run_command(&mut self, command: &str) -> Result<()> {
+        // `s/pattern/replacement/` with an optional `g` to skip asking.
+        if let Some(rest) = command.strip_prefix("s/") {
+            let parts: Vec<&str> = rest.splitn(3, '/').collect();
+            return match parts.as_slice() {
+                [pattern, replacement, flags] if !pattern.is_empty() => {
+                    self.replace(Some(pattern.to_string()), Some(replacement.to_string()), *flags == "g")
+                }
+                _ => {
+                    self.set_status_msg(tr!(BadArgument, "s", command));
+                    Ok(())
+                }
+            };
+        }
+        if let Some(shell) = command.strip_prefix('!') {
+            return self.shell_command(shell.trim());
+        }
+        // A line number on its own goes there, as in vi.
+        if let Ok(line) = command.parse() {
+            let here = (self.cy, self.cx);
+            self.jump_from(here);
+            self.go_to(line, 1);
+            return Ok(());
+        }
+        let (name, arg) = match command.find(char::is_whitespace) {
+            Some(i) => (&command[..i], command[i..].trim()),
+            None => (command, ""),
+        };
+        match name {
+            "w" | "write" if arg.is_empty() => self.run_action(Action::Save, None)?,
+            "w" | "write" | "saveas" => {
+                let res = self.save_as(if arg.is_empty() { None } else { Some(arg.to_string()) });
+                self.report_save(res);
+            }
+            "q" | "quit" => self.request_quit(false),
+            "q!" | "quit!" => self.request_quit(true),
+            "wq" | "x" => {
+                self.run_action(Action::Save, None)?;
+                if !self.dirty {
+                    self.request_quit(false);
+                }
+            }
+            "e" | "edit" if arg.is_empty() => self.open_prompt()?,
+            "e" | "edit" if self.dirty && !self.scratch => self.set_status_msg(tr!(OpenUnsaved)),
+            "e" | "edit" => self.open_path(arg.to_string(), false)?,
+            "set" => self.set_option(arg),
+            "grep" => self.grep(arg)?,
+            "blame" => self.toggle_blame(),
+            "stage" => self.stage_hunk(),
+            "definition" => self.lsp_ask("textDocument/definition"),
+            "references" => self.lsp_ask("textDocument/references"),
+            "revert" => self.revert_hunk(),
+            "filter" => self.filter(arg)?,
+            "r" | "read" if arg.starts_with('!') => self.read_command(arg[1..].trim())?,
+            "r" | "read" => self.set_status_msg(tr!(BadArgument, name, arg)),
+            "cd" => self.change_dir(arg),
+            "s" => self.replace(None, None, false)?,
+            "hud" => self.show_hud = !self.show_hud,
+            "trailing" => self.show_trailing = !self.show_trailing,
+            "endings" => match arg {
+                "lf" | "crlf" if self.read_only => self.set_status_msg(tr!(ReadOnly)),
+                "lf" | "crlf" => {
+                    let crlf = arg == "crlf";
+                    if crlf != self.crlf {
+                        self.crlf = crlf;
+                        self.dirty = true;
+                    }
+                    self.set_status_msg(tr!(LineEndings, arg.to_uppercase()));
+                }
+                _ => self.set_status_msg(tr!(BadArgument, name, arg)),
+            },
+            "hex" => self.toggle_hex(),
+            "whitespace" | "list" => self.show_whitespace = !self.show_whitespace,
+            "strip" if self.read_only => self.set_status_msg(tr!(ReadOnly)),
+            "strip" => {
+                let lines = self.strip_trailing();
+                self.set_status_msg(tr!(Stripped, lines));
+            }
+            "scratch" => self.new_scratch(),
+            "follow" => self.toggle_follow()?,
+            "steal" => self.steal_lock(),
+            "recover" => self.recover_swap()?,
+            "dropswap" => self.drop_swap(),
+            "calc" => self.calculate(arg)?,
+            "normalize" => self.normalize_buffer(),
+            "format" => self.format_buffer()?,
+            "history" => self.browse_local_history()?,
+            "smoothscroll" => self.smooth_scroll = !self.smooth_scroll,
+            "syntax" => match arg {
+                "" => self.select_syntax(),
+                "off" => self.highlighter = Highlighter::new(None),
+                _ => match Backend::by_name(arg) {
+                    Some(syntax) => self.highlighter = Highlighter::new(Some(syntax)),
+                    None => self.set_status_msg(tr!(UnknownFiletype, arg)),
+                },
+            },
+            "bnext" | "bn" => self.next_buffer(),
+            "bprev" | "bp" => self.prev_buffer(),
+            "tabbar" => self.tab_bar = !self.tab_bar,
+            "speak" => self.speak_position()?,
+            "screenreader" => self.toggle_accessible()?,
+            "undo" => self.undo(),
+            "redo" => self.redo(),
+            "wrap" => {
+                self.wrap = !self.wrap;
+                self.wrapoff = 0;
+            }
+            "visualmove" => self.visual_movement = !self.visual_movement,
+            "tabstop" | "indent" => match arg.parse() {
+                Ok(width) if width > 0 => {
+                    if name == "tabstop" {
+                        self.tab_stop = width;
+                        self.config.tab_stop = Some(width);
+                    } else {
+                        self.config.indent_width = Some(width);
+                    }
+                    self.report_indentation();
+                }
+                _ => self.set_status_msg(tr!(BadArgument, name, arg)),
+            },
+            "expandtab" => {
+                self.config.expand_tab = Some(self.config.expand_tab != Some(true));
+                self.report_indentation();
+            }
+            "autoindent" => self.config.auto_indent = Some(self.config.auto_indent == Some(false)),
+            "autopairs" => self.config.auto_pairs = Some(self.config.auto_pairs != Some(true)),
+            "number" | "nu" => self.line_numbers = match self.line_numbers {
+                LineNumbers::Off => LineNumbers::Absolute,
+                _ => LineNumbers::Off,
+            },
+            "relativenumber" | "rnu" => self.line_numbers = match self.line_numbers {
+                LineNumbers::Relative => LineNumbers::Absolute,
+                _ => LineNumbers::Relative,
+            },
+            "signcolumn" => match arg {
+                "auto" => self.sign_column = SignColumn::Auto,
+                "yes" => self.sign_column = SignColumn::Always,
+                "no" => self.sign_column = SignColumn::Never,
+                _ => self.set_status_msg(tr!(BadArgument, name, arg)),
+            },
+            "mark" | "gomark" => match (arg.chars().next(), arg.chars().count()) {
+                (Some(mark), 1) if name == "mark" => self.set_mark(mark),
+                (Some(mark), 1) => self.go_to_mark(mark),
+                _ => self.set_status_msg(tr!(BadArgument, name, arg)),
+            },
+            "marks" => self.list_marks(),
+            "sign" => match arg.chars().next() {
+                Some(glyph) => {
+                    let sign = Sign { group: "user".to_string(), glyph, color: Color::Cyan, priority: 0 };
+                    self.signs.place(self.cy, sign);
+                }
+                None => self.signs.remove(self.cy, "user"),
+            },
+            // Anything a key can be bound to runs by the same name.
+            _ => match Action::from_name(name) {
+                Some(Action::Quit) => self.request_quit(false),
+                Some(action) if action.edits() && self.read_only => self.set_status_msg(tr!(ReadOnly)),
+                Some(action) if action.edits() && self.hex.is_some() => self.set_status_msg(tr!(NotInHex)),
+                Some(action) => self.run_action(action, None)?,
+                None => self.set_status_msg(tr!(UnknownCommand, name)),
+            },
+        }
+        Ok(())
+    }
+
+    /// Quits once the command is done, unless there are unsaved changes
+    /// and not `force`.
+    fn request_quit(&mut self, force: bool) {
+        if self.unsaved() && !force {
+            self.set_status_msg(tr!(UnsavedQuit));
+        } else {
+            self.quit_requested = true;
+        }
+    }
+
+    /// `set name=value`, with the names and values of the config file, for
+    /// this buffer. `set name` and `set noname` turn a switch on and off.
+    fn set_option(&mut self, arg: &str) {
+        let (name, value) = match arg.find('=') {
+            Some(i) => (arg[..i].trim(), arg[i + 1..].trim()),
+            None if arg.starts_with("no") => (&arg[2..], "false"),
+            None => (arg, "true"),
+        };
+        // A bare word is taken as a string: `set line_numbers=relative`.
+        let over = Config::parse(&format!("{} = {}", name, value))
+            .or_else(|_| Config::parse(&format!("{} = {:?}", name, value)));
+        match over {
+            Ok(ref over) if *over == Config::default() => self.set_status_msg(tr!(UnknownSetting, name)),
+            Ok(over) => {
+                self.config.merge(over.clone());
+                self.apply_settings(&over);
+            }
+            Err(e) => self.set_status_msg(tr!(BadArgument, "set", e)),
+        }
+    }
+
+    /// Evaluates an arithmetic expression and inserts the result. Without
+    /// one, the selection is evaluated and replaced, or the expression is
+    /// asked for.
+    fn calculate(&mut self, expr: &str) -> Result<()> {
+        let selection = if expr.is_empty() { self.selection() } else { None };
+        let expr = match selection {
+            Some((start, end)) => self.region_text(start, end),
+            None if expr.is_empty() => match self.prompt(tr!(Calculate), |_, _, _| {})? {
+                Some(expr) => expr,
+                None => return Ok(()),
+            },
+            None => expr.to_string(),
+        };
+        let answer = match calc::eval(&expr) {
+            Ok(answer) => answer,
+            Err(e) => {
+                self.set_status_msg(tr!(CalcFailed, e));
+                return Ok(());
+            }
+        };
+        if self.read_only {
+            self.set_status_msg(tr!(ReadOnly));
+            return Ok(());
+        }
+        if let Some((start, end)) = selection {
+            self.delete_region(start, end);
+        }
+        self.insert_text(&answer.to_string());
+        Ok(())
+    }
+
+    /// Replaces the buffer with the output of the configured formatter.
+    fn format_buffer(&mut self) -> Result<()> {
+        let formatter = match self.config.formatter {
+            Some(ref f) => f.clone(),
+            None => {
+                self.set_status_msg(tr!(NoFormatter));
+                return Ok(());
+            }
+        };
+        if self.read_only {
+            self.set_status_msg(tr!(ReadOnly));
+            return Ok(());
+        }
+        let mut child = Command::new("sh").arg("-c").arg(&formatter)
+            .stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped())
+            .spawn()?;
+        let old = self.rows_to_string();
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(old.as_bytes())?;
+        }
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            let err = String::from_utf8_lossy(&output.stderr);
+            let err = err.lines().next().unwrap_or("").to_string();
+            self.set_status_msg(tr!(FormatFailed, output.status, err));
+            return Ok(());
+        }
+        let mut new = String::from_utf8_lossy(&output.stdout).into_owned();
+        if !new.ends_with('\n') {
+            new.push('\n');
+        }
+        self.replace_all(new);
+        Ok(())
+    }
+
+    /// Picks the highlighting for the file being edited by its name.
+    pub(crate) fn select_syntax(&mut self) {
+        let syntax = match self.config.syntax {
+            Some(false) => None,
+            _ if self.listing => None,
+            _ => self.filename.as_ref().and_then(|name| Backend::for_file(name)),
+        };
+        self.highlighter = Highlighter::new(syntax);
+    }
+
+    /// Replaces the whole buffer with `new`, as one undoable edit.
+    pub(crate) fn replace_all(&mut self, new: String) {
+        let old = self.rows_to_string();
+        if new == old {
+            return;
+        }
+        self.record((0, 0), old, new.clone());
+        self.rows = new.lines().map(|l| l.to_string()).collect();
+        self.cy = self.cy.min(self.rows.len());
+        self.cx = 0;
+        self.anchor = None;
+        self.dirty = true;
+    }
+
+    /// Shows `lines` in a new read-only scratch buffer.
+    pub(crate) fn show_in_scratch(&mut self, lines: Vec<String>) {
+        self.new_scratch();
+        self.rows = Rows::from(lines);
+        self.read_only = true;
+    }
+
+    /// Lists the versions of the file kept by earlier saves and views,
+    /// diffs or restores the one picked.
+    fn browse_local_history(&mut self) -> Result<()> {
+        let (file, root) = match (self.filename.clone(), state_dir()) {
+            (Some(file), Some(root)) => (file, root),
+            _ => {
+                self.set_status_msg(tr!(NoLocalHistory));
+                return Ok(());
+            }
+        };
+        let versions = local_history::versions(&root, &file);
+        if versions.is_empty() {
+            self.set_status_msg(tr!(NoLocalHistory));
+            return Ok(());
+        }
+        let items: Vec<String> = versions.iter()
+            .map(|v| tr!(HistoryEntry, local_history::age(v.time), v.size))
+            .collect();
+        let (version, item) = match self.pick(tr!(LocalHistory), &items)? {
+            Some(i) => (&versions[i], &items[i]),
+            None => return Ok(()),
+        };
+        let actions = vec![tr!(HistoryRestore), tr!(HistoryDiff), tr!(HistoryView)];
+        let action = match self.pick(item, &actions)? {
+            Some(action) => action,
+            None => return Ok(()),
+        };
+        let text = fs::read_to_string(&version.path)?;
+        match action {
+            0 if self.read_only => self.set_status_msg(tr!(ReadOnly)),
+            0 => self.replace_all(text),
+            1 => {
+                let old: Vec<&str> = text.lines().collect();
+                let new: Vec<&str> = self.rows.iter().map(|row| row.as_str()).collect();
+                let mut lines = vec![
+                    format!("--- {} ({})", file, local_history::age(version.time)),
+                    format!("+++ {}", file),
+                ];
+                lines.extend(diff::unified(&old, &new, 3));
+                self.show_in_scratch(lines);
+            }
+            _ => self.show_in_scratch(text.lines().map(|l| l.to_string()).collect()),
+        }
+        Ok(())
+    }
+
+    /// Changes the working directory. Without an argument it goes to the
+    /// root of the git repository holding the first file, or that file's
+    /// directory outside of git.
+    fn change_dir(&mut self, arg: &str) {
+        let target = if arg.is_empty() {
+            match self.first_dir {
+                Some(ref dir) => dir.ancestors()
+                    .find(|d| d.join(".git").exists())
+                    .unwrap_or(dir)
+                    .to_path_buf(),
+                None => {
+                    self.set_status_msg(tr!(CdNoFile));
+                    return;
+                }
+            }
+        } else {
+            PathBuf::from(arg)
+        };
+        match env::set_current_dir(&target) {
+            Ok(()) => {
+                let cwd = env::current_dir().unwrap_or(target);
+                self.set_status_msg(tr!(Cwd, cwd.display()));
+            }
+            Err(e) => self.set_status_msg(tr!(CdFailed, target.display(), e)),
+        }
+    }
+
+    fn open_prompt(&mut self) -> Result<()> {
+        if self.dirty && !self.scratch {
+            self.set_status_msg(tr!(OpenUnsaved));
+            return Ok(());
+        }
+        if let Some(path) = self.prompt_path(tr!(Open))? {
+            self.open_path(path, false)?;
+        }
+        Ok(())
+    }
+
+    /// Opens the file or directory on the cursor line of a listing in its
+    /// place.
+    fn open_listed(&mut self) -> Result<()> {
+        let dir = match self.filename {
+            Some(ref dir) => PathBuf::from(dir),
+            None => return Ok(()),
+        };
+        let entry = match self.rows.get(self.cy) {
+            Some(entry) => entry.trim_end_matches('/').to_string(),
+            None => return Ok(()),
+        };
+        let path = match entry.as_str() {
+            ".." => dir.parent().unwrap_or(&dir).to_path_buf(),
+            _ => dir.join(entry),
+        };
+        self.open_path(path.to_string_lossy().into_owned(), false)
+    }
+
+    /// Opens `path` in a buffer of its own, or switches to the one it's
+    /// already in. An empty buffer without a name is replaced.
+    pub(crate) fn open_in_buffer(&mut self, path: String) -> Result<()> {
+        let absolute: PathBuf = env::current_dir()?.join(&path).components().collect();
+        if self.switch_to_file(&absolute.to_string_lossy()) {
+            return Ok(());
+        }
+        let unused = self.filename.is_none() && !self.dirty && self.rows.is_empty();
+        self.open_path(path, !unused)
+    }
+
+    /// Opens `path` in place of the current buffer, which has no unsaved
+    /// changes, or with `keep` in a new one after it. Asks first when the
+    /// file is over the memory limit.
+    fn open_path(&mut self, path: String, keep: bool) -> Result<()> {
+        if let Some(need) = self.over_memory_limit(Path::new(&path)) {
+            let question = tr!(OpenAnyway, path, need / MIB, self.memory_limit / MIB);
+            match self.prompt(question, |_, _, _| {})? {
+                Some(ref answer) if answer.starts_with('y') || answer.starts_with('Y') => {}
+                _ => return Ok(()),
+            }
+        }
+        // Keep scratch buffers around rather than replacing them.
+        let scratch = if self.scratch || keep { Some(self.take_buffer()) } else { None };
+        if let Err(e) = self.open_anyway(&path) {
+            self.set_status_msg(tr!(CantOpen, path, e));
+            if let Some(buffer) = scratch {
+                self.put_buffer(buffer);
+            }
+        } else if let Some(buffer) = scratch {
+            self.set_aside(buffer);
+        }
+        Ok(())
+    }
+
+    /// Starts or stops following the file as it grows. Starting reloads it
+    /// so nothing written since it was opened is missed.
+    fn toggle_follow(&mut self) -> Result<()> {
+        if self.follow.is_some() {
+            self.follow = None;
+            self.set_status_msg(tr!(FollowPaused));
+            return Ok(());
+        }
+        let path = match self.filename {
+            Some(ref f) => f.clone(),
+            None => {
+                self.set_status_msg(tr!(FollowNoFile));
+                return Ok(());
+            }
+        };
+        if self.dirty {
+            self.set_status_msg(tr!(FollowUnsaved));
+            return Ok(());
+        }
+        self.open_anyway(&path)?;
+        self.follow = Some(fs::metadata(&path)?.len());
+        self.follow_bottom();
+        self.set_status_msg(tr!(FollowOn));
+        Ok(())
+    }
+
+    /// Reads about `bytes` more of a file still loading. If reading fails
+    /// the buffer is made read-only, so saving can't cut the file short.
+    fn load_more(&mut self, bytes: u64) {
+        let mut loader = match self.loading.take() {
+            Some(loader) => loader,
+            None => return,
+        };
+        match loader.load(&mut self.rows, bytes) {
+            Ok(true) => {}
+            Ok(false) => self.loading = Some(loader),
+            Err(e) => {
+                self.read_only = true;
+                self.set_status_msg(tr!(LoadFailed, e));
+            }
+        }
+        self.changes += 1;
+    }
+
+    /// Reads the rest of a file still loading.
+    pub(crate) fn finish_loading(&mut self) {
+        self.load_more(u64::MAX);
+    }
+
+    fn follow_bottom(&mut self) {
+        self.cy = self.rows.len().saturating_sub(1);
+        self.cx = 0;
+    }
+
+    /// Appends whole lines written to the file since the last read. A file
+    /// that shrank was truncated or rotated and is read again from the top.
+    fn read_appended(&mut self) -> Result<()> {
+        let (path, offset) = match (self.filename.clone(), self.follow) {
+            (Some(path), Some(offset)) => (path, offset),
+            _ => return Ok(()),
+        };
+        let mut file = File::open(&path)?;
+        let len = file.metadata()?.len();
+        if len < offset {
+            self.open_anyway(&path)?;
+            self.follow = Some(len);
+            self.follow_bottom();
+            return Ok(());
+        }
+        if len == offset {
+            return Ok(());
+        }
+        file.seek(SeekFrom::Start(offset))?;
+        let mut data = Vec::new();
+        file.take(len - offset).read_to_end(&mut data)?;
+        // Leave a line that is still being written for the next read.
+        let complete = match data.iter().rposition(|&b| b == b'\n') {
+            Some(i) => i + 1,
+            None => return Ok(()),
+        };
+        let text = String::from_utf8_lossy(&data[..complete]);
+        self.rows.extend(text.lines().map(|l| l.to_string()));
+        self.changes += 1;
+        self.follow = Some(offset + complete as u64);
+        self.file_stamp = self.disk_stamp();
+        self.follow_bottom();
+        Ok(())
+    }
+
+    /// Where unsaved changes go when we are killed: next to the file as
+    /// `name.save`, like nano, or `kilo.save` for unnamed buffers.
+    fn recovery_path(&self) -> PathBuf {
+        match self.filename {
+            Some(ref f) => PathBuf::from(format!("{}.save", f)),
+            None => PathBuf::from("kilo.save"),
+        }
+    }
+
+    /// Quits on SIGTERM or SIGHUP, first writing unsaved changes to a
+    /// recovery file.
+    pub(crate) fn handle_signals(&mut self) -> Option<Exit> {
+        let signal = PENDING_SIGNAL.swap(0, Ordering::SeqCst) as libc::c_int;
+        if signal == 0 {
+            return None;
+        }
+        let mut message = None;
+        if self.dirty && !self.scratch {
+            let path = self.recovery_path();
+            message = Some(match fs::write(&path, self.file_bytes()) {
+                Ok(()) => tr!(RecoveryWritten, path.display()),
+                Err(e) => tr!(RecoveryFailed, path.display(), e),
+            });
+        }
+        Some(Exit { code: 128 + signal, message })
+    }
+
+    fn disk_stamp(&self) -> Option<(SystemTime, u64)> {
+        let path = self.filename.as_ref()?;
+        let meta = fs::metadata(path).ok()?;
+        Some((meta.modified().ok()?, meta.len()))
+    }
+
+    /// Whether the file was changed, or removed, by someone else since we
+    /// last read or wrote it.
+    fn changed_on_disk(&self) -> bool {
+        self.file_stamp.is_some() && self.disk_stamp() != self.file_stamp
+    }
+
+    /// Asks what to do about changes made to the file by someone else
+    /// before saving over them.
+    fn resolve_disk_change(&mut self) -> Result<()> {
+        let choices = vec![tr!(ChangedOverwrite), tr!(ChangedReload), tr!(ChangedDiff)];
+        match self.pick(tr!(ChangedSinceRead), &choices)? {
+            Some(0) => {
+                let res = self.save();
+                self.report_save(res);
+            }
+            Some(1) => self.reload()?,
+            Some(2) => {
+                let path = self.filename.clone().unwrap_or_default();
+                let disk = fs::read_to_string(&path).unwrap_or_default();
+                let old: Vec<&str> = disk.lines().collect();
+                let new: Vec<&str> = self.rows.iter().map(|row| row.as_str()).collect();
+                let mut lines = vec![format!("--- {} (on disk)", path), format!("+++ {}", path)];
+                lines.extend(diff::unified(&old, &new, 3));
+                self.show_in_scratch(lines);
+            }
+            _ => self.set_status_msg(tr!(SaveAborted)),
+        }
+        Ok(())
+    }
+
+    /// Reads the file again over the buffer, as one undoable edit.
+    fn reload(&mut self) -> Result<()> {
+        let path = match self.filename.clone() {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        match self.hex {
+            Some(ref mut hex) => hex.replace(fs::read(&path)?),
+            None if self.listing => {
+                self.rows = list_dir(Path::new(&path))?;
+                self.cy = self.cy.min(self.rows.len().saturating_sub(1));
+                self.cx = 0;
+            }
+            None => {
+                let text = fs::read_to_string(&path)?;
+                self.replace_all(text);
+            }
+        }
+        self.dirty = false;
+        self.file_stamp = self.disk_stamp();
+        self.remove_swap();
+        self.set_status_msg(tr!(Reloaded, self.display_name()));
+        Ok(())
+    }
+
+    /// Watches the file of the current buffer, whichever that is now.
+    fn update_watch(&mut self) {
+        let path = self.filename.as_ref().map(PathBuf::from);
+        if self.watch.as_ref().map(|w| &w.path) == path.as_ref() {
+            return;
+        }
+        // Running out of inotify watches only costs the live reload.
+        self.watch = path.and_then(|path| FileWatch::new(&path).ok());
+    }
+
+    /// Reloads the file when someone else changed it and the buffer has
+    /// nothing to lose; with unsaved changes it only warns.
+    fn reload_if_changed(&mut self) -> Result<()> {
+        if !self.watch.as_mut().is_some_and(|w| w.changed()) {
+            return Ok(());
+        }
+        // Our own saves leave the stamp matching, and a file removed has
+        // nothing to reload.
+        if self.follow.is_some() || self.loading.is_some() || !self.changed_on_disk() ||
+            self.disk_stamp().is_none() {
+            return Ok(());
+        }
+        if self.dirty {
+            self.set_status_msg(tr!(ChangedOnDisk));
+            return Ok(());
+        }
+        self.reload()?;
+        self.set_status_msg(tr!(ReloadedChanged, self.display_name()));
+        Ok(())
+    }
+
+    /// Writes the unsaved changes to the swap file when they're due, or
+    /// straight away with `now`.
+    pub(crate) fn update_swap(&mut self, now: bool) {
+        match self.swap_due {
+            Some(due) if now || Instant::now() >= due => self.swap_due = None,
+            _ => return,
+        }
+        let path = match self.filename {
+            Some(ref file) if self.dirty && !self.scratch && !self.read_only => swap_path(Path::new(file)),
+            _ => return,
+        };
+        let data = match self.hex {
+            Some(ref hex) => hex.bytes.clone(),
+            None => self.rows_to_string().into_bytes(),
+        };
+        match atomic_save::save(&path, &data) {
+            Ok(_) => self.swap = Some(path),
+            Err(e) => self.set_status_msg(tr!(SwapFailed, path.display(), e)),
+        }
+    }
+
+    fn remove_swap(&mut self) {
+        self.swap_due = None;
+        if let Some(swap) = self.swap.take() {
+            let _ = fs::remove_file(swap);
+        }
+    }
+
+    fn remove_swaps(&mut self) {
+        self.remove_swap();
+        for buffer in &mut self.buffers {
+            if let Some(swap) = buffer.swap.take() {
+                let _ = fs::remove_file(swap);
+            }
+        }
+    }
+
+    /// Puts the changes from a swap file left behind into the buffer, as
+    /// one undoable edit.
+    fn recover_swap(&mut self) -> Result<()> {
+        let path = match self.filename {
+            Some(ref file) => swap_path(Path::new(file)),
+            None => {
+                self.set_status_msg(tr!(NoSwap));
+                return Ok(());
+            }
+        };
+        if self.read_only {
+            self.set_status_msg(tr!(ReadOnly));
+            return Ok(());
+        }
+        let data = match fs::read(&path) {
+            Ok(data) => data,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => {
+                self.set_status_msg(tr!(NoSwap));
+                return Ok(());
+            }
+            Err(e) => return Err(e),
+        };
+        match self.hex {
+            Some(ref mut hex) => {
+                hex.replace(data);
+                self.dirty = true;
+            }
+            None => self.replace_all(String::from_utf8_lossy(&data).into_owned()),
+        }
+        // It's ours now, kept until the changes are saved.
+        self.swap = Some(path.clone());
+        self.set_status_msg(tr!(Recovered, path.display()));
+        Ok(())
+    }
+
+    fn drop_swap(&mut self) {
+        let path = match self.filename {
+            Some(ref file) => swap_path(Path::new(file)),
+            None => {
+                self.set_status_msg(tr!(NoSwap));
+                return;
+            }
+        };
+        match fs::remove_file(&path) {
+            Ok(()) => self.set_status_msg(tr!(SwapDropped, path.display())),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => self.set_status_msg(tr!(NoSwap)),
+            Err(e) => self.set_status_msg(tr!(CommandFailed, e)),
+        }
+        if self.swap.as_ref() == Some(&path) {
+            self.swap = None;
+        }
+    }
+
+    /// How long a buffer with changes sits without a key press before it
+    /// is saved, when `autosave` is set.
+    pub(crate) fn autosave_delay(&self) -> Option<Duration> {
+        match self.config.autosave {
+            Some(secs) if secs > 0 => Some(Duration::from_secs(secs as u64)),
+            _ => None,
+        }
+    }
+
+    /// Saves the changes once the keyboard has been idle for the autosave
+    /// delay. A buffer without a name goes to its recovery file instead.
+    fn autosave(&mut self) {
+        let delay = match self.autosave_delay() {
+            Some(delay) => delay,
+            None => return,
+        };
+        if !self.autosave_pending || self.last_input.elapsed() < delay {
+            return;
+        }
+        self.autosave_pending = false;
+        if !self.dirty || self.scratch || self.read_only {
+            return;
+        }
+        if self.changed_on_disk() {
+            self.set_status_msg(tr!(ChangedOnDisk));
+        } else if self.filename.is_some() {
+            match self.save() {
+                Ok(_) => {
+                    let name = self.display_name();
+                    self.set_status_msg(tr!(Autosaved, name));
+                }
+                Err(e) => self.set_status_msg(tr!(AutosaveFailed, e)),
+            }
+        } else {
+            let path = self.recovery_path();
+            match fs::write(&path, self.file_bytes()) {
+                Ok(()) => self.set_status_msg(tr!(Autosaved, path.display())),
+                Err(e) => self.set_status_msg(tr!(AutosaveFailed, e)),
+            }
+        }
+    }
+
+    fn on_focus_lost(&mut self) {
+        if self.save_on_blur && self.dirty && self.filename.is_some() {
+            if self.changed_on_disk() {
+                self.set_status_msg(tr!(ChangedOnDisk));
+                return;
+            }
+            match self.save() {
+                Ok(_) => self.set_status_msg(tr!(SavedOnBlur)),
+                Err(e) => self.set_status_msg(tr!(SaveFailed, e)),
+            }
+        }
+    }
+
+    /// Warns when the file was modified by someone else while we were away.
+    fn on_focus_gained(&mut self) {
+        if self.changed_on_disk() {
+            self.set_status_msg(tr!(ChangedOnDisk));
+        }
+    }
+
+    pub fn save(&mut self) -> Result<Option<Saved>> {
+        if self.read_only {
+            return Err(io::Error::other(tr!(ReadOnly)));
+        }
+        let path = match self.filename {
+            Some(ref path) => path.to_owned(),
+            None => return self.save_as(None),
+        };
+        if self.config.strip_trailing == Some(true) {
+            self.strip_trailing();
+        }
+        let data = self.file_bytes();
+        let saved = atomic_save::save(Path::new(&path), &data)?;
+        self.dirty = false;
+        self.remove_swap();
+        self.file_stamp = self.disk_stamp();
+        self.lsp_sync();
+        self.lsp_saved();
+        if let (Some(file), Some(root)) = (self.filename.as_ref(), state_dir()) {
+            // The save itself went fine; a missing history copy is not
+            // worth failing it over. Binary files aren't kept.
+            if let Ok(text) = str::from_utf8(&data) {
+                let _ = local_history::record(&root, file, text);
+            }
+        }
+        Ok(Some(saved))
+    }
+
+    /// Saves under a new name, asking for it when `path` is `None`. The
+    /// buffer is renamed: later saves go to the new file.
+    pub fn save_as(&mut self, path: Option<String>) -> Result<Option<Saved>> {
+        if self.read_only {
+            return Err(io::Error::other(tr!(ReadOnly)));
+        }
+        let path = match path {
+            Some(path) => path,
+            None => match self.prompt_path(tr!(SaveAs))? {
+                Some(path) => path,
+                None => return Ok(None),
+            },
+        };
+        let path: PathBuf = env::current_dir()?.join(path).components().collect();
+        let old = self.filename.replace(path.to_string_lossy().into_owned());
+        let old_scratch = mem::replace(&mut self.scratch, false);
+        match self.save() {
+            Ok(written) => {
+                self.release_lock();
+                self.take_lock(&path);
+                self.load_project_config(&path);
+                self.select_syntax();
+                Ok(written)
+            }
+            Err(e) => {
+                self.filename = old;
+                self.scratch = old_scratch;
+                Err(e)
+            }
+        }
+    }
+}
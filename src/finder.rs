@@ -0,0 +1,302 @@
+//! Finding a file by typing parts of its name. The files under the working
+//! directory are listed once when the finder opens, leaving out what git
+//! ignores, hidden directories and those the `exclude` setting names; each
+//! key typed then filters and ranks them again.
+
+use std::fs;
+use std::io::Result;
+use std::path::Path;
+
+use regex::Regex;
+
+use editor::Editor;
+use input::Key;
+
+/// Files listed at most, so a huge tree doesn't hold the finder up.
+const MAX_FILES: usize = 100_000;
+
+/// One line of a `.gitignore`.
+struct Rule {
+    regex: Regex,
+    negated: bool,
+    dir_only: bool,
+}
+
+/// The rules of a `.gitignore`, which apply below the directory it's in.
+struct Ignore {
+    /// That directory, relative to where the listing started, with a
+    /// trailing slash unless it's the top.
+    dir: String,
+    rules: Vec<Rule>,
+}
+
+impl Ignore {
+    fn read(dir: &str) -> Option<Ignore> {
+        let text = fs::read_to_string(Path::new(".").join(dir).join(".gitignore")).ok()?;
+        let rules = text.lines().filter_map(Rule::parse).collect();
+        Some(Ignore { dir: dir.to_string(), rules })
+    }
+}
+
+impl Rule {
+    fn parse(line: &str) -> Option<Rule> {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let (negated, pattern) = match line.strip_prefix('!') {
+            Some(pattern) => (true, pattern),
+            None => (false, line),
+        };
+        let dir_only = pattern.ends_with('/');
+        let pattern = pattern.trim_end_matches('/');
+        // A slash anywhere but the end ties the pattern to the directory
+        // of the `.gitignore`; without one it matches at any depth.
+        let anchored = pattern.contains('/');
+        let pattern = pattern.trim_start_matches('/');
+        let body = glob_to_regex(pattern);
+        let regex = if anchored { format!("^{}$", body) } else { format!("(?:^|/){}$", body) };
+        Some(Rule { regex: Regex::new(&regex).ok()?, negated, dir_only })
+    }
+}
+
+fn glob_to_regex(glob: &str) -> String {
+    let mut re = String::new();
+    let chars: Vec<char> = glob.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                if chars.get(i + 2) == Some(&'/') {
+                    re += "(?:.*/)?";
+                    i += 1;
+                } else {
+                    re += ".*";
+                }
+                i += 1;
+            }
+            '*' => re += "[^/]*",
+            '?' => re += "[^/]",
+            '[' => match chars[i..].iter().position(|&c| c == ']') {
+                Some(len) => {
+                    let class: String = chars[i + 1..i + len].iter().collect();
+                    re += &format!("[{}]", class.replacen('!', "^", 1));
+                    i += len;
+                }
+                None => re += "\\[",
+            },
+            c => re += &regex::escape(&c.to_string()),
+        }
+        i += 1;
+    }
+    re
+}
+
+/// Whether `path`, relative to the top, is ignored by the `.gitignore`s
+/// above it. Deeper files and later lines win, as with git.
+fn ignored(ignores: &[Ignore], path: &str, is_dir: bool) -> bool {
+    let mut ignored = false;
+    for ignore in ignores {
+        if !path.starts_with(&ignore.dir) {
+            continue;
+        }
+        let rel = &path[ignore.dir.len()..];
+        for rule in &ignore.rules {
+            if (!rule.dir_only || is_dir) && rule.regex.is_match(rel) {
+                ignored = !rule.negated;
+            }
+        }
+    }
+    ignored
+}
+
+/// The files under the working directory, by their paths relative to it.
+pub fn scan(exclude: &[String]) -> Vec<String> {
+    let mut files = Vec::new();
+    let mut ignores = Vec::new();
+    walk("", exclude, &mut ignores, &mut files);
+    files.sort();
+    files
+}
+
+fn walk(dir: &str, exclude: &[String], ignores: &mut Vec<Ignore>, files: &mut Vec<String>) {
+    let entries = match fs::read_dir(Path::new(".").join(dir)) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    let pushed = match Ignore::read(dir) {
+        Some(ignore) => {
+            ignores.push(ignore);
+            true
+        }
+        None => false,
+    };
+    let mut entries: Vec<_> = entries.filter_map(|e| e.ok()).collect();
+    entries.sort_by_key(|e| e.file_name());
+    for entry in entries {
+        if files.len() >= MAX_FILES {
+            break;
+        }
+        let name = match entry.file_name().into_string() {
+            Ok(name) => name,
+            Err(_) => continue,
+        };
+        // Symlinks aren't followed, so a loop can't catch us.
+        let is_dir = entry.file_type().is_ok_and(|t| t.is_dir());
+        let path = format!("{}{}", dir, name);
+        if ignored(ignores, &path, is_dir) {
+            continue;
+        }
+        if is_dir {
+            if !name.starts_with('.') && !exclude.contains(&name) {
+                walk(&format!("{}/", path), exclude, ignores, files);
+            }
+        } else {
+            files.push(path);
+        }
+    }
+    if pushed {
+        ignores.pop();
+    }
+}
+
+/// How well `query` matches `path`, higher being better, when all its
+/// characters are in it in order, ignoring case. Matches that start a
+/// word, follow the one before or fall in the file name count for more.
+pub fn score(query: &str, path: &str) -> Option<i64> {
+    let name_start = path.rfind('/').map_or(0, |i| i + 1);
+    let mut score = 0;
+    let mut rest = path.char_indices();
+    let mut before = None;
+    // Where the last match ended.
+    let mut matched_to = None;
+    for q in query.chars().flat_map(|c| c.to_lowercase()) {
+        let (prev, i, c) = loop {
+            let prev = before;
+            let (i, c) = rest.next()?;
+            before = Some(c);
+            if c.to_lowercase().eq(Some(q)) {
+                break (prev, i, c);
+            }
+        };
+        score += 1;
+        match prev {
+            None => score += 8,
+            Some(p) if !p.is_alphanumeric() || (p.is_lowercase() && c.is_uppercase()) => score += 8,
+            _ => {}
+        }
+        if i >= name_start {
+            score += 2;
+        }
+        if matched_to == Some(i) {
+            score += 4;
+        }
+        matched_to = Some(i + c.len_utf8());
+    }
+    Some(score)
+}
+
+impl Editor {
+    /// Lists the files under the working directory and opens the one
+    /// picked, filtering the list by what's typed.
+    pub(crate) fn find_file(&mut self) -> Result<()> {
+        let exclude = self.config.exclude.clone().unwrap_or_default();
+        let files = scan(&exclude);
+        if files.is_empty() {
+            self.set_status_msg(tr!(NoFiles));
+            return Ok(());
+        }
+        let mut query = String::new();
+        let mut selected = 0;
+        loop {
+            let mut matches: Vec<(i64, &String)> = files.iter()
+                .filter_map(|file| score(&query, file).map(|score| (score, file)))
+                .collect();
+            matches.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.len().cmp(&b.1.len())));
+            selected = selected.min(matches.len().saturating_sub(1));
+            self.set_status_msg(tr!(FinderHint, query, matches.len(), files.len()));
+            self.refresh_screen()?;
+            let shown: Vec<&str> = matches.iter().map(|&(_, file)| file.as_str()).collect();
+            self.draw_list(&shown, selected)?;
+
+            match self.read_key()? {
+                Key::Up | Key::Ctrl(b'p') if selected > 0 => selected -= 1,
+                Key::Down | Key::Ctrl(b'n') if selected + 1 < matches.len() => selected += 1,
+                Key::Return if !matches.is_empty() => {
+                    self.set_status_msg("");
+                    let path = matches[selected].1.clone();
+                    return self.open_in_buffer(path);
+                }
+                Key::Char(b'\x1b') => {
+                    self.set_status_msg("");
+                    return Ok(());
+                }
+                Key::Backspace | Key::Del | Key::Ctrl(b'h') => {
+                    query.pop();
+                    selected = 0;
+                }
+                Key::Char(c) if c > 31 && c < 127 => {
+                    query.push(c as char);
+                    selected = 0;
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `paths` in the order the finder shows them for `query`.
+    fn ranked<'a>(query: &str, paths: &[&'a str]) -> Vec<&'a str> {
+        let mut scored: Vec<(i64, &str)> = paths.iter()
+            .filter_map(|&path| score(query, path).map(|s| (s, path)))
+            .collect();
+        scored.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(b.1)));
+        scored.into_iter().map(|(_, path)| path).collect()
+    }
+
+    #[test]
+    fn every_character_has_to_be_there_in_order() {
+        assert!(score("edr", "src/editor.rs").is_some());
+        assert!(score("EDR", "src/editor.rs").is_some());
+        assert!(score("", "src/editor.rs").is_some());
+        assert_eq!(score("rde", "src/editor.rs"), None);
+        assert_eq!(score("editorz", "src/editor.rs"), None);
+    }
+
+    #[test]
+    fn file_names_beat_directories() {
+        assert_eq!(ranked("main", &["main/lib.rs", "src/main.rs"]), ["src/main.rs", "main/lib.rs"]);
+    }
+
+    #[test]
+    fn word_starts_and_runs_count_for_more() {
+        assert_eq!(ranked("ed", &["src/speed.rs", "src/edit.rs"]), ["src/edit.rs", "src/speed.rs"]);
+        assert_eq!(ranked("fb", &["src/fib.rs", "src/FooBar.rs"]), ["src/FooBar.rs", "src/fib.rs"]);
+        assert_eq!(ranked("key", &["src/kxexy.rs", "src/keymap.rs"]), ["src/keymap.rs", "src/kxexy.rs"]);
+    }
+
+    #[test]
+    fn gitignore_patterns() {
+        let ignores = vec![
+            Ignore { dir: String::new(), rules: ["target/", "*.o", "/build", "!keep.o", "docs/**/*.html"]
+                .iter().filter_map(|line| Rule::parse(line)).collect() },
+            Ignore { dir: "sub/".to_string(), rules: Rule::parse("local").into_iter().collect() },
+        ];
+        assert!(ignored(&ignores, "target", true));
+        assert!(!ignored(&ignores, "target", false));
+        assert!(ignored(&ignores, "src/x.o", false));
+        assert!(!ignored(&ignores, "src/keep.o", false));
+        assert!(ignored(&ignores, "build", true));
+        assert!(!ignored(&ignores, "src/build", true));
+        assert!(ignored(&ignores, "docs/a/b/c.html", false));
+        assert!(ignored(&ignores, "docs/c.html", false));
+        assert!(ignored(&ignores, "sub/local", false));
+        assert!(!ignored(&ignores, "local", false));
+        assert!(Rule::parse("# comment").is_none());
+        assert!(Rule::parse("").is_none());
+    }
+}
@@ -0,0 +1,228 @@
+//! Asking git about the file being edited. `:blame` shows who last changed
+//! each line, and when, in a column beside the text that scrolls with it.
+//! The buffer is blamed as it is, so unsaved lines show as not committed.
+//!
+//! `:stage` puts the change under the cursor in the index, as `git add -p`
+//! would, and `:revert` puts the lines back the way they are in HEAD. Both
+//! compare with the buffer rather than the file on disk.
+
+use std::collections::HashMap;
+use std::io::{self, Result, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+
+use diff::{self, Hunk};
+use editor::Editor;
+
+/// Characters of the author's name shown.
+const AUTHOR_WIDTH: usize = 12;
+
+/// Runs git with `args` in `dir`, feeding it `input`, and returns what it
+/// prints, or the first line of its complaint when it fails.
+fn run(dir: &Path, args: &[&str], input: String) -> Result<String> {
+    let mut child = Command::new("git").args(args).current_dir(dir)
+        .stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped())
+        .spawn()?;
+    let mut stdin = child.stdin.take().unwrap();
+    let writer = thread::spawn(move || stdin.write_all(input.as_bytes()));
+    let output = child.wait_with_output()?;
+    let _ = writer.join();
+    if !output.status.success() {
+        let err = String::from_utf8_lossy(&output.stderr);
+        let err = err.lines().next().unwrap_or("").trim_start_matches("fatal: ").to_string();
+        return Err(io::Error::other(err));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// The directory git runs in for `path`, and the file's name in it.
+fn split(path: &Path) -> (&Path, &str) {
+    let dir = path.parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    (dir, path.file_name().and_then(|n| n.to_str()).unwrap_or(""))
+}
+
+/// The file at `path` as it is in `rev`; an empty `rev` means the index.
+pub fn show(path: &Path, rev: &str) -> Result<String> {
+    let (dir, name) = split(path);
+    run(dir, &["show", &format!("{}:./{}", rev, name)], String::new())
+}
+
+/// Puts `hunk`, a change from the index to the file at `path` made
+/// without context lines, in the index.
+pub fn stage(path: &Path, hunk: &Hunk, eol: &str) -> Result<()> {
+    let (dir, name) = split(path);
+    // Patches name files from the top of the repository.
+    let file = run(dir, &["rev-parse", "--show-prefix"], String::new())?.trim_end().to_string() + name;
+    let mut patch = format!("--- a/{0}{2}+++ b/{0}{2}{1}{2}", file, hunk.header(), eol);
+    for line in &hunk.lines {
+        patch += line;
+        patch += eol;
+    }
+    run(dir, &["apply", "--cached", "--unidiff-zero", "-"], patch).map(|_| ())
+}
+
+/// One annotation per line of `contents`, taken to be the file at `path`:
+/// the commit, its author and the day it was made.
+pub fn blame(path: &Path, contents: String) -> Result<Vec<String>> {
+    let (dir, name) = split(path);
+    let output = run(dir, &["blame", "--porcelain", "--contents", "-", "--", name], contents)?;
+
+    // Each line starts with a header naming its commit; the first time a
+    // commit comes up the header is followed by what's known about it.
+    let mut commits: HashMap<&str, (String, u64)> = HashMap::new();
+    let mut lines = Vec::new();
+    let mut current = "";
+    for line in output.lines() {
+        if line.starts_with('\t') {
+            lines.push(current);
+            continue;
+        }
+        let mut words = line.splitn(2, ' ');
+        let (key, value) = (words.next().unwrap_or(""), words.next().unwrap_or(""));
+        if key.len() == 40 && key.bytes().all(|b| b.is_ascii_hexdigit()) {
+            current = key;
+            commits.entry(key).or_insert_with(|| (String::new(), 0));
+        } else if let Some(commit) = commits.get_mut(current) {
+            match key {
+                "author" => commit.0 = value.to_string(),
+                "author-time" => commit.1 = value.parse().unwrap_or(0),
+                _ => {}
+            }
+        }
+    }
+    Ok(lines.iter().map(|&hash| {
+        let (ref author, time) = commits[hash];
+        // Lines changed since the last commit have a hash of all zeros.
+        let (hash, author, day) = if hash.bytes().all(|b| b == b'0') {
+            ("", tr!(NotCommitted), String::new())
+        } else {
+            (&hash[..8], author.clone(), date(time))
+        };
+        let author: String = author.chars().take(AUTHOR_WIDTH).collect();
+        format!("{:8} {:3$} {:10}", hash, author, day, AUTHOR_WIDTH)
+    }).collect())
+}
+
+/// The day `secs` after the epoch fell on, as `YYYY-MM-DD` in UTC.
+fn date(secs: u64) -> String {
+    // Days to a civil date, after Howard Hinnant's `civil_from_days`.
+    let z = (secs / 86400) as i64 + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+impl Editor {
+    /// Shows who last changed each line beside the text, or hides it when
+    /// it's shown already.
+    pub(crate) fn toggle_blame(&mut self) {
+        if self.blame.take().is_some() {
+            return;
+        }
+        let path = match self.git_path() {
+            Some(path) => path,
+            None => return,
+        };
+        match blame(Path::new(&path), self.rows_to_string()) {
+            Ok(lines) => {
+                self.blame = Some(lines);
+                self.set_status_msg(tr!(BlameHint));
+            }
+            Err(e) => self.set_status_msg(tr!(BlameFailed, e)),
+        }
+    }
+
+    /// The file the buffer holds, for git to look up, with all of it loaded.
+    fn git_path(&mut self) -> Option<String> {
+        match self.filename {
+            Some(ref name) if !self.scratch && !self.listing && self.hex.is_none() => {
+                let name = name.clone();
+                self.finish_loading();
+                Some(name)
+            }
+            _ => {
+                self.set_status_msg(tr!(NotInGit));
+                None
+            }
+        }
+    }
+
+    /// The change from the file in `rev` to the buffer that the cursor is
+    /// on, or just below when lines were taken out, and the file's path.
+    fn cursor_hunk(&mut self, rev: &str) -> Option<(String, Hunk)> {
+        let path = self.git_path()?;
+        let old = match show(Path::new(&path), rev) {
+            Ok(old) => old,
+            Err(e) => {
+                self.set_status_msg(tr!(GitFailed, e));
+                return None;
+            }
+        };
+        let hunk = {
+            let old: Vec<&str> = old.lines().collect();
+            let new: Vec<&str> = self.rows.iter().map(|row| row.as_str()).collect();
+            let cy = self.cy;
+            diff::hunks(&old, &new, 0).into_iter().find(|hunk| if hunk.b_len == 0 {
+                cy == hunk.b_start || cy + 1 == hunk.b_start
+            } else {
+                hunk.b_start <= cy && cy < hunk.b_start + hunk.b_len
+            })
+        };
+        match hunk {
+            Some(hunk) => Some((path, hunk)),
+            None => {
+                self.set_status_msg(tr!(NoHunk, if rev.is_empty() { "index" } else { rev }));
+                None
+            }
+        }
+    }
+
+    /// Stages the change under the cursor.
+    pub(crate) fn stage_hunk(&mut self) {
+        let (path, hunk) = match self.cursor_hunk("") {
+            Some(found) => found,
+            None => return,
+        };
+        let eol = if self.crlf { "\r\n" } else { "\n" };
+        match stage(Path::new(&path), &hunk, eol) {
+            Ok(()) => self.set_status_msg(tr!(HunkStaged, hunk.lines.len())),
+            Err(e) => self.set_status_msg(tr!(GitFailed, e)),
+        }
+    }
+
+    /// Puts the lines of the change under the cursor back as they are in
+    /// HEAD, as one undoable edit.
+    pub(crate) fn revert_hunk(&mut self) {
+        if self.read_only {
+            self.set_status_msg(tr!(ReadOnly));
+            return;
+        }
+        let (_, hunk) = match self.cursor_hunk("HEAD") {
+            Some(found) => found,
+            None => return,
+        };
+        let old: String = hunk.lines.iter()
+            .filter(|line| line.starts_with('-'))
+            .map(|line| line[1..].to_string() + "\n")
+            .collect();
+        let (start, end) = (hunk.b_start, hunk.b_start + hunk.b_len);
+        if start < self.rows.len() {
+            self.replace_region((start, 0), (end, 0), &old);
+        } else if let Some(last) = self.rows.len().checked_sub(1) {
+            // Lines taken off the end go back after the last one.
+            let x = self.rows[last].len();
+            self.replace_region((last, x), (last, x), &("\n".to_string() + old.trim_end_matches('\n')));
+        } else {
+            self.replace_all(old);
+        }
+        self.cy = start.min(self.rows.len().saturating_sub(1));
+        self.cx = 0;
+    }
+}
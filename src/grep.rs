@@ -0,0 +1,114 @@
+//! Searching every file in the project for a regular expression. Matches
+//! go into a read-only buffer, one `file:line: text` line each, where
+//! Enter opens the file at the line. The files searched are the ones the
+//! finder lists, so ignored and excluded ones are left out.
+
+use std::fs;
+use std::io::Result;
+
+use regex::Regex;
+
+use editor::Editor;
+use finder;
+use hex;
+
+/// Matching lines listed at most.
+const MAX_MATCHES: usize = 10_000;
+/// Files larger than this are passed over.
+const MAX_FILE_SIZE: u64 = 8 << 20;
+
+pub struct Results {
+    pub lines: Vec<String>,
+    pub files: usize,
+    /// Whether there were more matches than listed.
+    pub truncated: bool,
+}
+
+/// The lines matching `re` in the files under the working directory.
+pub fn search(re: &Regex, exclude: &[String]) -> Results {
+    let mut results = Results { lines: Vec::new(), files: 0, truncated: false };
+    for path in finder::scan(exclude) {
+        if fs::metadata(&path).map_or(true, |meta| meta.len() > MAX_FILE_SIZE) {
+            continue;
+        }
+        let data = match fs::read(&path) {
+            Ok(data) => data,
+            Err(_) => continue,
+        };
+        if hex::is_binary(&data) {
+            continue;
+        }
+        let before = results.lines.len();
+        for (n, line) in String::from_utf8_lossy(&data).lines().enumerate() {
+            if !re.is_match(line) {
+                continue;
+            }
+            if results.lines.len() == MAX_MATCHES {
+                results.truncated = true;
+                return results;
+            }
+            results.lines.push(format!("{}:{}: {}", path, n + 1, line));
+        }
+        if results.lines.len() > before {
+            results.files += 1;
+        }
+    }
+    results
+}
+
+/// The file and line a results line points at.
+fn location(line: &str) -> Option<(String, usize)> {
+    let re = Regex::new(r"^(.+?):(\d+):").unwrap();
+    let caps = re.captures(line)?;
+    Some((caps[1].to_string(), caps[2].parse().ok()?))
+}
+
+impl Editor {
+    /// Searches the project for `pattern`, asking for it when empty, and
+    /// lists the matches.
+    pub(crate) fn grep(&mut self, pattern: &str) -> Result<()> {
+        let pattern = match pattern {
+            "" => match self.prompt(tr!(GrepPrompt), |_, _, _| {})? {
+                Some(pattern) => pattern,
+                None => return Ok(()),
+            },
+            pattern => pattern.to_string(),
+        };
+        let re = match Regex::new(&pattern) {
+            Ok(re) => re,
+            Err(e) => {
+                self.set_status_msg(tr!(BadArgument, "grep", e));
+                return Ok(());
+            }
+        };
+        let exclude = self.config.exclude.clone().unwrap_or_default();
+        let results = search(&re, &exclude);
+        if results.lines.is_empty() {
+            self.set_status_msg(tr!(NoMatches, pattern));
+            return Ok(());
+        }
+        let (count, files) = (results.lines.len(), results.files);
+        self.show_in_scratch(results.lines);
+        self.locations = true;
+        if results.truncated {
+            self.set_status_msg(tr!(GrepTruncated, count));
+        } else {
+            self.set_status_msg(tr!(GrepFound, count, files));
+        }
+        Ok(())
+    }
+
+    /// Opens the file a results line points at, at that line.
+    pub(crate) fn open_location(&mut self) -> Result<()> {
+        let (path, line) = match self.rows.get(self.cy).and_then(|row| location(row)) {
+            Some(location) => location,
+            None => return Ok(()),
+        };
+        self.open_in_buffer(path)?;
+        // Still here when the file couldn't be opened.
+        if !self.locations {
+            self.go_to(line, 1);
+        }
+        Ok(())
+    }
+}
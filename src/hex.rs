@@ -0,0 +1,398 @@
+//! Binary files, shown and edited a byte at a time rather than as lines of
+//! text, which they would not survive. Each screen line has the offset,
+//! sixteen bytes in hex and the same bytes as ASCII, as `hexdump -C` does.
+//!
+//! Typing hex digits overwrites the byte under the cursor a half at a time;
+//! Tab moves typing over to the ASCII column and back. Enter makes room for
+//! a new byte at the cursor, Delete and Backspace take one out, and typing
+//! past the last byte adds to the end.
+
+use std::str;
+
+use editor::Editor;
+use input::Key;
+use keymap::Action;
+use text::Rows;
+
+pub const BYTES_PER_LINE: usize = 16;
+/// Bytes looked at for a NUL; text files don't have any.
+const SAMPLE: usize = 8 << 10;
+/// Where the hex column starts, after the offset.
+const HEX_START: usize = 10;
+/// Where the bytes start in the ASCII column, after the `|`.
+const ASCII_START: usize = HEX_START + BYTES_PER_LINE * 3 + 3;
+
+/// Whether `data` is something other than text: it has a NUL near the start
+/// or isn't UTF-8. A character cut off at the end of `data` doesn't count,
+/// so the start of a file will do.
+pub fn is_binary(data: &[u8]) -> bool {
+    if data[..data.len().min(SAMPLE)].contains(&0) {
+        return true;
+    }
+    match str::from_utf8(data) {
+        Ok(_) => false,
+        Err(e) => e.error_len().is_some(),
+    }
+}
+
+/// One change, undone by swapping what was removed and inserted.
+struct Change {
+    pos: usize,
+    removed: Option<u8>,
+    inserted: Option<u8>,
+}
+
+pub struct HexView {
+    pub bytes: Vec<u8>,
+    /// The byte the cursor is on; one past the last byte to add to the end.
+    pub pos: usize,
+    /// Whether the next digit typed goes into the low half of the byte.
+    pub low: bool,
+    /// Whether typing goes into the ASCII column.
+    pub ascii: bool,
+    undo: Vec<Change>,
+    redo: Vec<Change>,
+}
+
+impl HexView {
+    pub fn new(bytes: Vec<u8>) -> HexView {
+        HexView {
+            bytes,
+            pos: 0,
+            low: false,
+            ascii: false,
+            undo: Vec::new(),
+            redo: Vec::new(),
+        }
+    }
+
+    /// Puts new contents in, as after reading the file again.
+    pub fn replace(&mut self, bytes: Vec<u8>) {
+        self.bytes = bytes;
+        self.pos = self.pos.min(self.bytes.len());
+        self.low = false;
+        self.undo.clear();
+        self.redo.clear();
+    }
+
+    /// Screen lines needed, counting the one the end is on.
+    pub fn lines(&self) -> usize {
+        self.bytes.len() / BYTES_PER_LINE + 1
+    }
+
+    pub fn cursor_line(&self) -> usize {
+        self.pos / BYTES_PER_LINE
+    }
+
+    /// The screen column of the cursor on its line.
+    pub fn cursor_column(&self) -> usize {
+        let i = self.pos % BYTES_PER_LINE;
+        if self.ascii {
+            ASCII_START + i
+        } else {
+            hex_column(i) + self.low as usize
+        }
+    }
+
+    /// Moves the cursor by `delta` bytes, stopping at either end.
+    pub fn move_by(&mut self, delta: isize) {
+        let pos = self.pos as isize + delta;
+        self.pos = pos.max(0).min(self.bytes.len() as isize) as usize;
+        self.low = false;
+    }
+
+    pub fn line_start(&mut self) {
+        self.pos -= self.pos % BYTES_PER_LINE;
+        self.low = false;
+    }
+
+    pub fn line_end(&mut self) {
+        let end = self.pos - self.pos % BYTES_PER_LINE + BYTES_PER_LINE - 1;
+        self.pos = end.min(self.bytes.len());
+        self.low = false;
+    }
+
+    /// Types a character, returning whether it meant anything here.
+    pub fn type_char(&mut self, c: u8) -> bool {
+        if self.ascii {
+            if !(0x20..0x7f).contains(&c) {
+                return false;
+            }
+            self.overwrite(c);
+            self.pos += 1;
+            return true;
+        }
+        let digit = match (c as char).to_digit(16) {
+            Some(digit) => digit as u8,
+            None => return false,
+        };
+        let old = self.bytes.get(self.pos).cloned().unwrap_or(0);
+        if self.low {
+            self.overwrite(old & 0xf0 | digit);
+            self.pos += 1;
+        } else {
+            self.overwrite(old & 0x0f | digit << 4);
+        }
+        self.low = !self.low;
+        true
+    }
+
+    fn overwrite(&mut self, byte: u8) {
+        let removed = self.bytes.get(self.pos).cloned();
+        self.apply(Change { pos: self.pos, removed, inserted: Some(byte) });
+    }
+
+    /// Makes room for a new byte at the cursor.
+    pub fn insert(&mut self) {
+        self.apply(Change { pos: self.pos, removed: None, inserted: Some(0) });
+        self.low = false;
+    }
+
+    /// Takes out the byte under the cursor, or before it with `backward`.
+    pub fn delete(&mut self, backward: bool) -> bool {
+        if backward {
+            if self.pos == 0 {
+                return false;
+            }
+            self.pos -= 1;
+        }
+        if self.pos >= self.bytes.len() {
+            return false;
+        }
+        let removed = self.bytes[self.pos];
+        self.apply(Change { pos: self.pos, removed: Some(removed), inserted: None });
+        self.low = false;
+        true
+    }
+
+    fn apply(&mut self, change: Change) {
+        self.redo.clear();
+        self.undo.push(change);
+        let change = self.undo.last().unwrap();
+        apply(&mut self.bytes, change.pos, change.removed, change.inserted);
+    }
+
+    pub fn undo(&mut self) -> bool {
+        let change = match self.undo.pop() {
+            Some(change) => change,
+            None => return false,
+        };
+        apply(&mut self.bytes, change.pos, change.inserted, change.removed);
+        self.pos = change.pos.min(self.bytes.len());
+        self.low = false;
+        self.redo.push(change);
+        true
+    }
+
+    pub fn redo(&mut self) -> bool {
+        let change = match self.redo.pop() {
+            Some(change) => change,
+            None => return false,
+        };
+        apply(&mut self.bytes, change.pos, change.removed, change.inserted);
+        self.pos = change.pos.min(self.bytes.len());
+        self.low = false;
+        self.undo.push(change);
+        true
+    }
+
+    /// Screen line `line`: offset, hex and ASCII.
+    pub fn line(&self, line: usize) -> String {
+        let start = line * BYTES_PER_LINE;
+        let bytes = &self.bytes[start.min(self.bytes.len())..(start + BYTES_PER_LINE).min(self.bytes.len())];
+        let mut s = format!("{:08x}  ", start);
+        for i in 0..BYTES_PER_LINE {
+            match bytes.get(i) {
+                Some(b) => s += &format!("{:02x} ", b),
+                None => s += "   ",
+            }
+            if i == BYTES_PER_LINE / 2 - 1 {
+                s.push(' ');
+            }
+        }
+        s.push(' ');
+        s.push('|');
+        s.extend(bytes.iter().map(|&b| if (0x20..0x7f).contains(&b) { b as char } else { '.' }));
+        s.push('|');
+        s
+    }
+}
+
+impl Editor {
+    /// Handles a key in the hex view, returning false for the ones that
+    /// work as they do on text, like saving and quitting.
+    pub(crate) fn hex_key(&mut self, c: Key, action: Option<Action>) -> bool {
+        let line = BYTES_PER_LINE as isize;
+        let page = line * self.numrows.max(1) as isize;
+        let hex = match self.hex {
+            Some(ref mut hex) => hex,
+            None => return false,
+        };
+        let edited = match action {
+            Some(Action::Newline) => {
+                hex.insert();
+                true
+            }
+            Some(Action::Backspace) => hex.delete(true),
+            Some(Action::Delete) => hex.delete(false),
+            Some(Action::Undo) => hex.undo(),
+            Some(Action::Redo) => hex.redo(),
+            Some(Action::Indent) => {
+                hex.ascii = !hex.ascii;
+                hex.low = false;
+                false
+            }
+            Some(action) if action.edits() => {
+                self.set_status_msg(tr!(NotInHex));
+                return true;
+            }
+            Some(motion) => {
+                match motion {
+                    Action::Up => hex.move_by(-line),
+                    Action::Down => hex.move_by(line),
+                    Action::Left => hex.move_by(-1),
+                    Action::Right => hex.move_by(1),
+                    Action::PageUp => hex.move_by(-page),
+                    Action::PageDown => hex.move_by(page),
+                    Action::Home => hex.line_start(),
+                    Action::End => hex.line_end(),
+                    _ => return false,
+                }
+                false
+            }
+            None => match c {
+                Key::Char(c) => hex.type_char(c),
+                _ => return false,
+            },
+        };
+        if edited {
+            self.dirty = true;
+        }
+        true
+    }
+
+    /// Switches the buffer between text and the hex view. Text that isn't
+    /// UTF-8 has to stay in hex. Undo starts afresh either way.
+    pub(crate) fn toggle_hex(&mut self) {
+        let text = match self.hex {
+            Some(ref hex) => match str::from_utf8(&hex.bytes) {
+                Ok(text) => Some(text.to_string()),
+                Err(_) => {
+                    self.set_status_msg(tr!(NotText));
+                    return;
+                }
+            },
+            None => None,
+        };
+        match text {
+            Some(text) => {
+                self.hex = None;
+                self.crlf = text.split('\n').next().is_some_and(|line| line.ends_with('\r'));
+                self.rows = text.lines().map(|line| line.to_string()).collect();
+            }
+            None => {
+                self.finish_loading();
+                self.hex = Some(HexView::new(self.file_bytes()));
+                self.rows = Rows::new();
+            }
+        }
+        self.history.clear();
+        self.changes += 1;
+        self.cx = 0;
+        self.cy = 0;
+        self.rowoff = 0;
+        self.coloff = 0;
+        self.anchor = None;
+    }
+}
+
+fn hex_column(i: usize) -> usize {
+    HEX_START + i * 3 + if i >= BYTES_PER_LINE / 2 { 1 } else { 0 }
+}
+
+fn apply(bytes: &mut Vec<u8>, pos: usize, removed: Option<u8>, inserted: Option<u8>) {
+    match (removed, inserted) {
+        (Some(_), Some(byte)) => bytes[pos] = byte,
+        (None, Some(byte)) => bytes.insert(pos, byte),
+        (Some(_), None) => {
+            bytes.remove(pos);
+        }
+        (None, None) => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binary_is_told_from_text() {
+        assert!(!is_binary(b"plain text\n"));
+        assert!(!is_binary("größer\n".as_bytes()));
+        assert!(is_binary(b"ELF\0\x01"));
+        assert!(is_binary(b"\xff\xfe latin-1"));
+        // Cut off in the middle of a character by the sample.
+        assert!(!is_binary(&"ü".as_bytes()[..1]));
+    }
+
+    #[test]
+    fn lines_look_like_hexdump() {
+        let view = HexView::new(b"Hello, world!\n\x00\x01\xff".to_vec());
+        assert_eq!(view.lines(), 2);
+        assert_eq!(view.line(0),
+                   "00000000  48 65 6c 6c 6f 2c 20 77  6f 72 6c 64 21 0a 00 01  |Hello, world!...|");
+        assert_eq!(view.line(1),
+                   "00000010  ff                                                |.|");
+    }
+
+    #[test]
+    fn digits_fill_a_byte_a_half_at_a_time() {
+        let mut view = HexView::new(vec![0x00, 0x11]);
+        assert!(view.type_char(b'a'));
+        assert_eq!((view.bytes[0], view.pos, view.low), (0xa0, 0, true));
+        assert!(view.type_char(b'B'));
+        assert_eq!((view.bytes[0], view.pos, view.low), (0xab, 1, false));
+        assert!(!view.type_char(b'g'));
+        view.ascii = true;
+        assert!(view.type_char(b'z'));
+        assert!(view.type_char(b'!'));
+        assert_eq!(view.bytes, b"\xabz!");
+        assert!(!view.type_char(b'\n'));
+    }
+
+    #[test]
+    fn inserts_and_deletes_undo() {
+        let mut view = HexView::new(vec![1, 2, 3]);
+        view.move_by(1);
+        view.insert();
+        assert_eq!(view.bytes, [1, 0, 2, 3]);
+        assert!(view.delete(false));
+        assert!(view.delete(true));
+        assert_eq!(view.bytes, [2, 3]);
+        assert!(!view.delete(true));
+        assert!(view.undo());
+        assert!(view.undo());
+        assert_eq!(view.bytes, [1, 0, 2, 3]);
+        assert!(view.redo());
+        assert_eq!(view.bytes, [1, 2, 3]);
+        assert!(view.undo());
+        assert!(view.undo());
+        assert!(!view.undo());
+        assert_eq!(view.bytes, [1, 2, 3]);
+    }
+
+    #[test]
+    fn cursor_stays_in_bounds() {
+        let mut view = HexView::new(vec![0; 20]);
+        view.move_by(-5);
+        assert_eq!(view.pos, 0);
+        view.move_by(100);
+        assert_eq!((view.pos, view.cursor_line()), (20, 1));
+        view.line_start();
+        assert_eq!(view.pos, 16);
+        view.move_by(-10);
+        view.line_end();
+        assert_eq!(view.pos, 15);
+        assert_eq!(view.cursor_column(), hex_column(15));
+    }
+}
@@ -0,0 +1,48 @@
+//! Guessing how a file is indented from the lines in it, so Tab keeps to
+//! the file's style rather than the configured one.
+
+/// Lines looked at; the start of a file is enough to tell.
+const SAMPLE_LINES: usize = 1000;
+/// Indented lines needed before trusting the guess.
+const MIN_EVIDENCE: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    Tabs,
+    Spaces(usize),
+}
+
+/// How `rows` are indented, if they show it clearly enough. Spaces are
+/// measured by how far each line steps in from the one before.
+pub fn detect<'a, I: IntoIterator<Item = &'a String>>(rows: I) -> Option<Style> {
+    let (mut tabs, mut spaces) = (0, 0);
+    // How often each step in, by its width in spaces.
+    let mut steps = [0usize; 9];
+    let mut previous = 0;
+    for row in rows.into_iter().take(SAMPLE_LINES) {
+        if row.trim().is_empty() {
+            continue;
+        }
+        if row.starts_with('\t') {
+            tabs += 1;
+            continue;
+        }
+        let width = row.len() - row.trim_start_matches(' ').len();
+        // ` * ` continues a block comment; it isn't a step.
+        if width > 0 && !row[width..].starts_with('*') {
+            spaces += 1;
+            if width > previous && width - previous < steps.len() {
+                steps[width - previous] += 1;
+            }
+        }
+        previous = width;
+    }
+    if tabs + spaces < MIN_EVIDENCE {
+        return None;
+    }
+    if tabs > spaces {
+        return Some(Style::Tabs);
+    }
+    // Ties go to the wider step, the last of them.
+    (2..steps.len()).max_by_key(|&w| steps[w]).filter(|&w| steps[w] > 0).map(Style::Spaces)
+}
@@ -0,0 +1,595 @@
+//! Reading keys from the terminal, and the prompts and pickers built on
+//! them.
+
+use std::{fs, io};
+use std::io::{Read, Result};
+use std::os::unix::io::AsRawFd;
+use std::time::{Duration, Instant};
+
+use libc;
+
+use clipboard;
+use config;
+use keymap::Action;
+use editor::{self, Editor, FOLLOW_POLL_MS, SMOOTH_SCROLL_FRAME_MS};
+
+const MOUSE_SCROLL_LINES: usize = 3;
+/// How long a paste may stall before what came of it is dropped.
+const PASTE_TIMEOUT_MS: libc::c_int = 5000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Key {
+    Char(u8),
+    Ctrl(u8),
+    Left,
+    Right,
+    Up,
+    Down,
+    Del,
+    Home,
+    End,
+    ShiftUp,
+    ShiftDown,
+    ShiftLeft,
+    ShiftRight,
+    ShiftHome,
+    ShiftEnd,
+    CtrlLeft,
+    CtrlRight,
+    CtrlShiftLeft,
+    CtrlShiftRight,
+    AltUp,
+    AltDown,
+    BackTab,
+    PageUp,
+    PageDown,
+    Return,
+    Backspace,
+    Alt(u8),
+    Mouse(MouseEvent),
+    FocusIn,
+    FocusOut,
+    /// A bracketed paste; the text is in `Editor::pasted`.
+    Paste,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MouseButton {
+    Left,
+    Middle,
+    Right,
+    WheelUp,
+    WheelDown,
+}
+
+/// A mouse report in SGR (1006) encoding. Coordinates are 1-based screen
+/// cells, as sent by the terminal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MouseEvent {
+    pub button: MouseButton,
+    pub pressed: bool,
+    pub x: usize,
+    pub y: usize,
+}
+
+/// Unbuffered stdin. `io::Stdin` reads ahead into a buffer that `poll()`
+/// can't see, which would leave typed-ahead bytes waiting for the next key.
+pub(crate) struct RawStdin;
+
+impl Read for RawStdin {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let n = unsafe { libc::read(0, buf.as_mut_ptr() as *mut libc::c_void, buf.len()) };
+        if n < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(n as usize)
+        }
+    }
+}
+
+/// File names completing `input`, with directories marked by a trailing
+/// slash. Hidden files are only offered when asked for explicitly.
+pub(crate) fn complete_path(input: &str) -> Vec<String> {
+    let (dir, prefix) = match input.rfind('/') {
+        Some(i) => (&input[..i + 1], &input[i + 1..]),
+        None => ("", input),
+    };
+    let entries = match fs::read_dir(if dir.is_empty() { "." } else { dir }) {
+        Ok(entries) => entries,
+        Err(_) => return vec![],
+    };
+    let mut candidates: Vec<String> = entries
+        .filter_map(|e| e.ok())
+        .filter_map(|e| {
+            let name = e.file_name().into_string().ok()?;
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+            let is_dir = e.path().is_dir();
+            Some(format!("{}{}{}", dir, name, if is_dir { "/" } else { "" }))
+        })
+        .collect();
+    candidates.sort();
+    candidates
+}
+
+/// Command lines completing `input`: the command name, or after it a file
+/// name or setting for the commands that take one.
+pub(crate) fn complete_command(input: &str) -> Vec<String> {
+    let (name, arg) = match input.find(' ') {
+        Some(i) => (&input[..i], input[i..].trim_start()),
+        None => {
+            let mut names: Vec<&str> = editor::COMMANDS.iter().cloned().chain(Action::names())
+                .filter(|name| name.starts_with(input))
+                .collect();
+            names.sort();
+            names.dedup();
+            return names.into_iter().map(|name| name.to_string()).collect();
+        }
+    };
+    let head = &input[..input.len() - arg.len()];
+    let candidates = match name {
+        "e" | "edit" | "w" | "write" | "saveas" | "cd" => complete_path(arg),
+        "set" => config::SETTINGS.iter()
+            .filter(|setting| setting.starts_with(arg))
+            .map(|setting| setting.to_string())
+            .collect(),
+        _ => vec![],
+    };
+    candidates.into_iter().map(|c| format!("{}{}", head, c)).collect()
+}
+
+/// The candidates as shown under a prompt, with the part they share with
+/// `input` left off and the one picked in brackets. Those that don't fit
+/// on the line are cut off by the message bar.
+fn candidate_list(candidates: &[String], picked: usize, input: &str) -> String {
+    // What's being completed starts after the last space or slash.
+    let start = input.rfind([' ', '/']).map_or(0, |i| i + 1);
+    let shown: Vec<String> = candidates.iter().enumerate().map(|(i, c)| {
+        let tail = c.get(start..).unwrap_or(c);
+        if i == picked { format!("[{}]", tail) } else { tail.to_string() }
+    }).collect();
+    shown.join(" ")
+}
+
+/// What Tab completes in a prompt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Completion {
+    Off,
+    Paths,
+    Commands,
+}
+
+/// Whether `key`, bound to `action`, changes the buffer. Unbound
+/// printable keys insert themselves.
+pub(crate) fn edits(key: Key, action: Option<Action>) -> bool {
+    match (action, key) {
+        (Some(action), _) => action.edits(),
+        (None, Key::Char(b'\x1b')) => false,
+        (None, Key::Char(_)) | (None, Key::Paste) => true,
+        _ => false,
+    }
+}
+
+pub(crate) fn is_mouse_or_focus(key: Key) -> bool {
+    matches!(key, Key::Mouse(_) | Key::FocusIn | Key::FocusOut)
+}
+
+pub(crate) fn scrolls_back(key: Key, action: Option<Action>) -> bool {
+    matches!((action, key),
+        (Some(Action::Up), _) | (Some(Action::PageUp), _) | (Some(Action::Home), _) |
+        (None, Key::Mouse(MouseEvent { button: MouseButton::WheelUp, .. })))
+}
+
+impl Editor {
+    /// Blocks until stdin becomes readable, `timeout` elapses or a signal
+    /// comes in. Returns whether input is ready to be read; a signal goes
+    /// first, so it's seen before the keys typed ahead of it.
+    pub(crate) fn poll_input(&self, timeout: Option<Duration>) -> Result<bool> {
+        // The watch pipe only wakes us up; it's read when we're idle.
+        let watch = self.watch.as_ref().map_or(-1, |w| w.fd());
+        let signals = self.signal_wake.as_ref().map_or(-1, |f| f.as_raw_fd());
+        let mut fds = [
+            libc::pollfd { fd: 0, events: libc::POLLIN, revents: 0 },
+            libc::pollfd { fd: watch, events: libc::POLLIN, revents: 0 },
+            libc::pollfd { fd: self.servers.fd(), events: libc::POLLIN, revents: 0 },
+            libc::pollfd { fd: signals, events: libc::POLLIN, revents: 0 },
+        ];
+        let millis = match timeout {
+            Some(t) => (t.as_secs() * 1000 + t.subsec_nanos() as u64 / 1_000_000) as libc::c_int,
+            None => -1,
+        };
+        let res = unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, millis) };
+        if res < 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::Interrupted {
+                return Ok(false);
+            }
+            return Err(err);
+        }
+        if fds[3].revents & libc::POLLIN != 0 {
+            let mut wake = self.signal_wake.as_ref().unwrap();
+            let mut buf = [0; 64];
+            while let Ok(n) = wake.read(&mut buf) {
+                if n == 0 {
+                    break;
+                }
+            }
+            return Ok(false);
+        }
+        Ok(res > 0 && fds[0].revents & libc::POLLIN != 0)
+    }
+
+    /// How long the event loop may sleep before something on screen has to
+    /// change on its own.
+    pub(crate) fn next_timeout(&self) -> Option<Duration> {
+        let shown = Instant::now().duration_since(self.status_msg_time);
+        let limit = Duration::from_secs(self.status_secs);
+        let status = if !self.status_msg.is_empty() && shown < limit {
+            Some(limit - shown)
+        } else {
+            None
+        };
+        let poll = if self.shown_rowoff.is_some() {
+            Some(Duration::from_millis(SMOOTH_SCROLL_FRAME_MS))
+        } else if self.loading.is_some() {
+            Some(Duration::from_millis(0))
+        } else if self.follow.is_some() {
+            Some(Duration::from_millis(FOLLOW_POLL_MS))
+        } else {
+            None
+        };
+        let autosave = match self.autosave_delay() {
+            Some(delay) if self.autosave_pending => {
+                Some(delay.checked_sub(self.last_input.elapsed()).unwrap_or(Duration::from_secs(0)))
+            }
+            _ => None,
+        };
+        let swap = self.swap_due.map(|due| due.saturating_duration_since(Instant::now()));
+        status.into_iter().chain(poll).chain(autosave).chain(swap).min()
+    }
+
+    pub fn read_key(&mut self) -> Result<Key> {
+        let mut buf = [0; 1];
+        loop {
+            if self.poll_input(None)? && self.stdin.read(&mut buf)? == 1 {
+                break;
+            }
+            self.handle_signals();
+            // Servers keep talking while a prompt waits for a key.
+            self.handle_lsp();
+        }
+
+        if buf[0] == b'\x1b' {
+            let mut s = [0;3];
+            match self.read_char() {
+                Some(c) => s[0] = c,
+                _ => return Ok(Key::Char(b'\x1b'))
+            }
+
+            match self.read_char() {
+                Some(c) => s[1] = c,
+                _ if s[0] != b'[' && s[0] != b'O' => return Ok(Key::Alt(s[0])),
+                _ => return Ok(Key::Char(b'\x1b'))
+            }
+
+            if s[0] == b'[' {
+                if s[1] == b'<' {
+                    return Ok(self.read_mouse().map_or(Key::Char(b'\x1b'), Key::Mouse));
+                } else if s[1] >= b'0' && s[1] <= b'9' {
+                    match self.read_char() {
+                        Some(c) => s[2] = c,
+                        _ => return Ok(Key::Char(b'\x1b'))
+                    }
+                    if &s[1..] == b"20" {
+                        return Ok(self.read_paste().unwrap_or(Key::Char(b'\x1b')));
+                    }
+                    if s[1] == b'1' && s[2] == b';' {
+                        return Ok(self.read_modified().unwrap_or(Key::Char(b'\x1b')));
+                    }
+                    if s[2] == b'~' {
+                        match s[1] {
+                            b'1' | b'7' => return Ok(Key::Home),
+                            b'2' | b'8' => return Ok(Key::End),
+                            b'3' => return Ok(Key::Del),
+                            b'5' => return Ok(Key::PageUp),
+                            b'6' => return Ok(Key::PageDown),
+                            _ => return Ok(Key::Char(b'\x1b'))
+                        }
+                    }
+                } else {
+                    match s[1] {
+                        b'A' => return Ok(Key::Up),
+                        b'B' => return Ok(Key::Down),
+                        b'C' => return Ok(Key::Right),
+                        b'D' => return Ok(Key::Left),
+                        b'H' => return Ok(Key::Home),
+                        b'F' => return Ok(Key::End),
+                        b'I' => return Ok(Key::FocusIn),
+                        b'O' => return Ok(Key::FocusOut),
+                        b'Z' => return Ok(Key::BackTab),
+                        _ => return Ok(Key::Char(b'\x1b')),
+                    }
+                }
+            } else if s[0] == b'O' {
+                match s[1] {
+                    b'H' => return Ok(Key::Home),
+                    b'F' => return Ok(Key::End),
+                    // rxvt's Ctrl-Right and Ctrl-Left.
+                    b'c' => return Ok(Key::CtrlRight),
+                    b'd' => return Ok(Key::CtrlLeft),
+                    _ => {}
+                }
+            }
+        }
+
+        if buf[0] == b'\r' {
+            return Ok(Key::Return);
+        }
+
+        if buf[0] == 127 {
+            return Ok(Key::Backspace);
+        }
+
+        if buf[0] & 0x1f == buf[0] {
+            return Ok(Key::Ctrl(buf[0] | 0x60));
+        }
+
+        Ok(Key::Char(buf[0]))
+    }
+
+    /// Reads the rest of a key with modifiers, `\x1b[1;` then the modifier
+    /// and the key. Shift, Ctrl and Alt are told apart on the arrows, Shift
+    /// elsewhere; other modifiers give the plain key.
+    fn read_modified(&mut self) -> Option<Key> {
+        let modifier = self.read_char()?;
+        let shift = modifier == b'2';
+        let key = match (modifier, self.read_char()?) {
+            (b'5', b'C') => Key::CtrlRight,
+            (b'5', b'D') => Key::CtrlLeft,
+            (b'6', b'C') => Key::CtrlShiftRight,
+            (b'6', b'D') => Key::CtrlShiftLeft,
+            (b'3', b'A') => Key::AltUp,
+            (b'3', b'B') => Key::AltDown,
+            (_, b'A') => if shift { Key::ShiftUp } else { Key::Up },
+            (_, b'B') => if shift { Key::ShiftDown } else { Key::Down },
+            (_, b'C') => if shift { Key::ShiftRight } else { Key::Right },
+            (_, b'D') => if shift { Key::ShiftLeft } else { Key::Left },
+            (_, b'H') => if shift { Key::ShiftHome } else { Key::Home },
+            (_, b'F') => if shift { Key::ShiftEnd } else { Key::End },
+            _ => return None,
+        };
+        Some(key)
+    }
+
+    /// Reads the rest of a bracketed paste, `\x1b[200~` then the text up to
+    /// `\x1b[201~`, into `pasted`.
+    fn read_paste(&mut self) -> Option<Key> {
+        if self.read_char()? != b'0' || self.read_char()? != b'~' {
+            return None;
+        }
+        let mut raw = Vec::new();
+        while !raw.ends_with(b"\x1b[201~") {
+            raw.push(self.read_paste_char()?);
+        }
+        raw.truncate(raw.len() - 6);
+        self.pasted = clipboard::pasted_text(&raw);
+        Some(Key::Paste)
+    }
+
+    /// The next byte of a paste. A big paste comes in pieces, so a read
+    /// timing out only means waiting for more; it's given up on at the end
+    /// of input, or when nothing comes for `PASTE_TIMEOUT_MS`.
+    fn read_paste_char(&mut self) -> Option<u8> {
+        let mut ready = false;
+        loop {
+            if let Some(c) = self.read_char() {
+                return Some(c);
+            }
+            // Readable, yet nothing read: the end of input.
+            if ready {
+                return None;
+            }
+            let mut fd = libc::pollfd { fd: 0, events: libc::POLLIN, revents: 0 };
+            let n = unsafe { libc::poll(&mut fd, 1, PASTE_TIMEOUT_MS) };
+            if n == 0 || n < 0 && io::Error::last_os_error().kind() != io::ErrorKind::Interrupted {
+                return None;
+            }
+            ready = n > 0;
+        }
+    }
+
+    /// Parses the rest of an SGR mouse report, `\x1b[<b;x;y` followed by
+    /// `M` on press or `m` on release.
+    fn read_mouse(&mut self) -> Option<MouseEvent> {
+        let mut report = String::new();
+        let pressed = loop {
+            match self.read_char()? {
+                b'M' => break true,
+                b'm' => break false,
+                c if report.len() < 32 => report.push(c as char),
+                _ => return None,
+            }
+        };
+
+        let fields: Vec<usize> = report.split(';').filter_map(|f| f.parse().ok()).collect();
+        if fields.len() != 3 {
+            return None;
+        }
+        let button = match fields[0] & !0x1c {
+            0 => MouseButton::Left,
+            1 => MouseButton::Middle,
+            2 => MouseButton::Right,
+            64 => MouseButton::WheelUp,
+            65 => MouseButton::WheelDown,
+            _ => return None,
+        };
+        Some(MouseEvent { button, pressed, x: fields[1], y: fields[2] })
+    }
+
+    pub(crate) fn handle_mouse(&mut self, event: MouseEvent) {
+        match event.button {
+            MouseButton::WheelUp | MouseButton::WheelDown => {
+                let key = if event.button == MouseButton::WheelUp { Key::Up } else { Key::Down };
+                for _ in 0..MOUSE_SCROLL_LINES {
+                    self.move_cursor(key);
+                }
+            }
+            MouseButton::Left if event.pressed && event.y == 1 && self.tab_bar_shown() => {
+                let x = event.x.saturating_sub(1);
+                if let Some(&(tab, _, _)) = self.tab_labels().iter()
+                    .find(|&&(_, start, ref label)| x >= start && x < start + label.chars().count()) {
+                    self.switch_to_tab(tab);
+                }
+            }
+            MouseButton::Left if event.pressed => {
+                let top = self.text_top();
+                if event.y <= top || event.y > top + self.numrows {
+                    return;
+                }
+                let (row, start, _) = self.screen_lines()[event.y - top - 1];
+                self.cy = row.min(self.rows.len());
+                self.cx = if self.cy < self.rows.len() {
+                    let row = &self.rows[self.cy];
+                    let x = event.x.saturating_sub(self.gutter_width() + 1);
+                    self.rx_to_cx(row, start + x)
+                } else {
+                    0
+                };
+            }
+            _ => {}
+        }
+    }
+
+    pub(crate) fn read_char(&mut self) -> Option<u8> {
+        let mut b = [0;1];
+        let c = self.stdin.read(&mut b).unwrap_or(0);
+        if c == 1 {
+            Some(b[0])
+        } else {
+            None
+        }
+    }
+
+    /// Shows `items` in a list over the text area and lets the user choose
+    /// one with the arrow keys. Returns the index of the chosen item.
+    /// Draws `items` over the top of the screen, scrolled so `selected`
+    /// shows, until the next frame.
+    pub(crate) fn draw_list<S: AsRef<str>>(&mut self, items: &[S], selected: usize) -> Result<()> {
+        let height = items.len().min(self.numrows);
+        let first = if selected >= height { selected + 1 - height } else { 0 };
+        let mut s = "\x1b[?25l".to_string();
+        for (y, item) in items[first..first + height].iter().enumerate() {
+            let label: String = item.as_ref().replace('\n', "\\n").replace('\t', " ")
+                .chars().take(self.numcols).collect();
+            s += &format!("\x1b[{};1H", y + 1);
+            if first + y == selected {
+                s += self.caps.reverse();
+            }
+            s += &label;
+            s += "\x1b[K";
+            s += self.caps.reset();
+        }
+        self.write(s)?;
+        self.overdrawn(0..height);
+        Ok(())
+    }
+
+    pub(crate) fn pick<S: AsRef<str>>(&mut self, title: S, items: &[String]) -> Result<Option<usize>> {
+        let mut selected = 0;
+        loop {
+            self.set_status_msg(tr!(PickHint, title.as_ref(), selected + 1, items.len()));
+            self.refresh_screen()?;
+
+            self.draw_list(items, selected)?;
+
+            match self.read_key()? {
+                Key::Up if selected > 0 => selected -= 1,
+                Key::Down if selected + 1 < items.len() => selected += 1,
+                Key::Return => {
+                    self.set_status_msg("");
+                    return Ok(Some(selected));
+                }
+                Key::Char(b'\x1b') => {
+                    self.set_status_msg("");
+                    return Ok(None);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    pub(crate) fn prompt<S: AsRef<str>, F>(&mut self, message: S, callback: F) -> Result<Option<String>>
+        where
+            F: FnMut(&mut Editor, &str, Key)
+    {
+        self.prompt_with(message.as_ref(), Completion::Off, callback)
+    }
+
+    /// Prompts for a file name, completing paths with Tab.
+    pub(crate) fn prompt_path<S: AsRef<str>>(&mut self, message: S) -> Result<Option<String>> {
+        self.prompt_with(message.as_ref(), Completion::Paths, |_, _, _| {})
+    }
+
+    pub(crate) fn prompt_with<F>(&mut self, message: &str, completion: Completion, mut callback: F) -> Result<Option<String>>
+        where
+            F: FnMut(&mut Editor, &str, Key)
+    {
+        let mut input = "".to_string();
+        // Candidates for the last Tab press and which one is shown.
+        let mut completions: Option<(Vec<String>, usize)> = None;
+        loop {
+            match completions {
+                Some((ref candidates, i)) => {
+                    let list = candidate_list(candidates, i, &input);
+                    self.set_status_msg(tr!(PromptCompletions, message, input, list));
+                }
+                None => self.set_status_msg(tr!(PromptHint, message, input)),
+            }
+            self.refresh_screen()?;
+            let c = self.read_key()?;
+            if c != Key::Ctrl(b'i') {
+                completions = None;
+            }
+            match c {
+                Key::Ctrl(b'i') if completion != Completion::Off => {
+                    completions = match completions.take() {
+                        Some((candidates, i)) => {
+                            let next = (i + 1) % candidates.len();
+                            Some((candidates, next))
+                        }
+                        None => {
+                            let candidates = match completion {
+                                Completion::Commands => complete_command(&input),
+                                _ => complete_path(&input),
+                            };
+                            if candidates.is_empty() { None } else { Some((candidates, 0)) }
+                        }
+                    };
+                    if let Some((ref candidates, i)) = completions {
+                        input = candidates[i].clone();
+                    }
+                    // A unique match is final; the next Tab descends into it.
+                    if completions.as_ref().is_some_and(|c| c.0.len() == 1) {
+                        completions = None;
+                    }
+                }
+                Key::Del | Key::Backspace | Key::Ctrl(b'h') => {
+                    input.pop();
+                }
+                Key::Return if !input.is_empty() => {
+                    self.set_status_msg("");
+                    callback(self, &input, c);
+                    return Ok(Some(input));
+                }
+                Key::Char(b'\x1b') => {
+                    callback(self, &input, c);
+                    return Ok(None);
+                }
+                Key::Char(c) if (32..127).contains(&c) => input.push(c as char),
+                _ => {}
+            }
+            callback(self, &input, c);
+        }
+    }
+}
@@ -0,0 +1,108 @@
+//! The jump list: where the cursor was before it jumped away, by a search,
+//! going to a line, paging or following a definition. Alt-O walks back
+//! through it and Alt-I forward again, into other files when the jump
+//! left one; in vi mode Ctrl-O and Ctrl-I do, as there.
+
+use std::io::Result;
+
+use editor::Editor;
+
+/// Places remembered at most; the oldest go first.
+const MAX_JUMPS: usize = 100;
+
+struct Jump {
+    filename: Option<String>,
+    at: (usize, usize),
+}
+
+#[derive(Default)]
+pub(crate) struct JumpList {
+    jumps: Vec<Jump>,
+    /// The place walked back to; the end of the list when not walking.
+    index: usize,
+}
+
+impl JumpList {
+    /// Adds a place, dropping any older one on the same line.
+    fn add(&mut self, jump: Jump) {
+        self.jumps.retain(|j| j.filename != jump.filename || j.at.0 != jump.at.0);
+        self.jumps.push(jump);
+        if self.jumps.len() > MAX_JUMPS {
+            self.jumps.remove(0);
+        }
+        self.index = self.jumps.len();
+    }
+}
+
+impl Editor {
+    /// Remembers `at` in the buffer as a place jumped away from. Places
+    /// walked back past are forgotten.
+    pub(crate) fn jump_from(&mut self, at: (usize, usize)) {
+        let index = self.jumps.index;
+        self.jumps.jumps.truncate(index);
+        let jump = Jump { filename: self.filename.clone(), at };
+        self.jumps.add(jump);
+    }
+
+    /// Goes back to where the cursor was before the last jump.
+    pub(crate) fn jump_back(&mut self) -> Result<()> {
+        if self.jumps.index == self.jumps.jumps.len() {
+            // Where we are goes on the end, to come forward to again.
+            let here = Jump { filename: self.filename.clone(), at: (self.cy, self.cx) };
+            self.jumps.add(here);
+            self.jumps.index -= 1;
+        }
+        if self.jumps.index == 0 {
+            self.set_status_msg(tr!(NoOlderJump));
+            return Ok(());
+        }
+        self.jumps.index -= 1;
+        self.go_to_jump()
+    }
+
+    /// Undoes a `jump_back`.
+    pub(crate) fn jump_forward(&mut self) -> Result<()> {
+        if self.jumps.index + 1 >= self.jumps.jumps.len() {
+            self.set_status_msg(tr!(NoNewerJump));
+            return Ok(());
+        }
+        self.jumps.index += 1;
+        self.go_to_jump()
+    }
+
+    /// Goes to the place walked to, switching buffers or opening its file
+    /// when it's in another.
+    fn go_to_jump(&mut self) -> Result<()> {
+        let (filename, (y, x)) = {
+            let jump = &self.jumps.jumps[self.jumps.index];
+            (jump.filename.clone(), jump.at)
+        };
+        if self.filename != filename {
+            let path = match filename {
+                Some(ref path) => path.clone(),
+                None => return Ok(()),
+            };
+            if !self.switch_to_file(&path) {
+                self.open_in_buffer(path)?;
+            }
+            // Still here when the file couldn't be opened.
+            if self.filename != filename {
+                return Ok(());
+            }
+        }
+        self.go_to_position((y, x));
+        Ok(())
+    }
+
+    /// Puts the cursor at (row, byte) `at`, or as near as the buffer now
+    /// allows.
+    pub(crate) fn go_to_position(&mut self, at: (usize, usize)) {
+        self.go_to(at.0 + 1, 1);
+        let row = self.rows.get(self.cy).map_or("", |row| row.as_str());
+        let mut x = at.1.min(row.len());
+        while !row.is_char_boundary(x) {
+            x -= 1;
+        }
+        self.cx = x;
+    }
+}
@@ -0,0 +1,426 @@
+//! Which key runs which command. Every key the editor handles itself goes
+//! through the table, so any of them can be moved or turned off from the
+//! `[keys]` table of the config file:
+//!
+//! ```toml
+//! [keys]
+//! "alt-q" = "quit"
+//! "ctrl-q" = "none"
+//! ```
+//!
+//! Keys that aren't bound insert themselves when they are printable and are
+//! ignored otherwise.
+
+use std::collections::HashMap;
+
+use input::Key;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    Quit,
+    Save,
+    SaveAs,
+    Open,
+    FindFile,
+    Grep,
+    Command,
+    Find,
+    FindRegex,
+    Replace,
+    Undo,
+    Redo,
+    Up,
+    Down,
+    Left,
+    Right,
+    WordLeft,
+    WordRight,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+    SetMark,
+    SelectUp,
+    SelectDown,
+    SelectLeft,
+    SelectRight,
+    SelectHome,
+    SelectEnd,
+    SelectWordLeft,
+    SelectWordRight,
+    Indent,
+    Outdent,
+    Upcase,
+    Downcase,
+    KillLine,
+    KillLineBackward,
+    MoveLinesUp,
+    MoveLinesDown,
+    DuplicateLines,
+    DeleteLines,
+    ToggleComment,
+    Yank,
+    NextBuffer,
+    PrevBuffer,
+    Newline,
+    Backspace,
+    Delete,
+    SelectRegister,
+    Copy,
+    Cut,
+    Paste,
+    PasteHistory,
+    ExpandSelection,
+    ShrinkSelection,
+    Surround,
+    DeleteSurrounding,
+    ChangeSurrounding,
+    CompletePath,
+    CompleteWord,
+    GotoDefinition,
+    FindReferences,
+    JumpBack,
+    JumpForward,
+    SpeakPosition,
+    ToggleMouse,
+    Redraw,
+}
+
+const NAMES: &[(&str, Action)] = &[
+    ("quit", Action::Quit),
+    ("save", Action::Save),
+    ("save-as", Action::SaveAs),
+    ("open", Action::Open),
+    ("find-file", Action::FindFile),
+    ("grep", Action::Grep),
+    ("command", Action::Command),
+    ("find", Action::Find),
+    ("find-regex", Action::FindRegex),
+    ("replace", Action::Replace),
+    ("undo", Action::Undo),
+    ("redo", Action::Redo),
+    ("up", Action::Up),
+    ("down", Action::Down),
+    ("left", Action::Left),
+    ("right", Action::Right),
+    ("word-left", Action::WordLeft),
+    ("word-right", Action::WordRight),
+    ("page-up", Action::PageUp),
+    ("page-down", Action::PageDown),
+    ("home", Action::Home),
+    ("end", Action::End),
+    ("set-mark", Action::SetMark),
+    ("select-up", Action::SelectUp),
+    ("select-down", Action::SelectDown),
+    ("select-left", Action::SelectLeft),
+    ("select-right", Action::SelectRight),
+    ("select-home", Action::SelectHome),
+    ("select-end", Action::SelectEnd),
+    ("select-word-left", Action::SelectWordLeft),
+    ("select-word-right", Action::SelectWordRight),
+    ("indent", Action::Indent),
+    ("outdent", Action::Outdent),
+    ("upcase", Action::Upcase),
+    ("downcase", Action::Downcase),
+    ("kill-line", Action::KillLine),
+    ("kill-line-backward", Action::KillLineBackward),
+    ("move-lines-up", Action::MoveLinesUp),
+    ("move-lines-down", Action::MoveLinesDown),
+    ("duplicate-lines", Action::DuplicateLines),
+    ("delete-lines", Action::DeleteLines),
+    ("toggle-comment", Action::ToggleComment),
+    ("yank", Action::Yank),
+    ("next-buffer", Action::NextBuffer),
+    ("prev-buffer", Action::PrevBuffer),
+    ("newline", Action::Newline),
+    ("backspace", Action::Backspace),
+    ("delete", Action::Delete),
+    ("select-register", Action::SelectRegister),
+    ("copy", Action::Copy),
+    ("cut", Action::Cut),
+    ("paste", Action::Paste),
+    ("paste-history", Action::PasteHistory),
+    ("expand-selection", Action::ExpandSelection),
+    ("shrink-selection", Action::ShrinkSelection),
+    ("surround", Action::Surround),
+    ("delete-surrounding", Action::DeleteSurrounding),
+    ("change-surrounding", Action::ChangeSurrounding),
+    ("complete-path", Action::CompletePath),
+    ("complete-word", Action::CompleteWord),
+    ("goto-definition", Action::GotoDefinition),
+    ("find-references", Action::FindReferences),
+    ("jump-back", Action::JumpBack),
+    ("jump-forward", Action::JumpForward),
+    ("speak-position", Action::SpeakPosition),
+    ("toggle-mouse", Action::ToggleMouse),
+    ("redraw", Action::Redraw),
+];
+
+const DEFAULTS: &[(Key, Action)] = &[
+    (Key::Ctrl(b'q'), Action::Quit),
+    (Key::Ctrl(b's'), Action::Save),
+    (Key::Alt(b'w'), Action::SaveAs),
+    (Key::Ctrl(b'o'), Action::Open),
+    (Key::Ctrl(b'p'), Action::FindFile),
+    (Key::Alt(b'g'), Action::Grep),
+    (Key::Ctrl(b'e'), Action::Command),
+    (Key::Ctrl(b'f'), Action::Find),
+    (Key::Alt(b'f'), Action::FindRegex),
+    (Key::Ctrl(b'r'), Action::Replace),
+    (Key::Ctrl(b'z'), Action::Undo),
+    (Key::Ctrl(b'y'), Action::Redo),
+    (Key::Up, Action::Up),
+    (Key::Down, Action::Down),
+    (Key::Left, Action::Left),
+    (Key::Right, Action::Right),
+    (Key::CtrlLeft, Action::WordLeft),
+    (Key::CtrlRight, Action::WordRight),
+    (Key::PageUp, Action::PageUp),
+    (Key::PageDown, Action::PageDown),
+    (Key::Home, Action::Home),
+    (Key::End, Action::End),
+    (Key::Ctrl(b'`'), Action::SetMark),
+    (Key::ShiftUp, Action::SelectUp),
+    (Key::ShiftDown, Action::SelectDown),
+    (Key::ShiftLeft, Action::SelectLeft),
+    (Key::ShiftRight, Action::SelectRight),
+    (Key::ShiftHome, Action::SelectHome),
+    (Key::ShiftEnd, Action::SelectEnd),
+    (Key::CtrlShiftLeft, Action::SelectWordLeft),
+    (Key::CtrlShiftRight, Action::SelectWordRight),
+    (Key::Ctrl(b'i'), Action::Indent),
+    (Key::BackTab, Action::Outdent),
+    (Key::Alt(b'u'), Action::Upcase),
+    (Key::Alt(b'U'), Action::Downcase),
+    (Key::Ctrl(b'k'), Action::KillLine),
+    (Key::Ctrl(b'u'), Action::KillLineBackward),
+    (Key::AltUp, Action::MoveLinesUp),
+    (Key::AltDown, Action::MoveLinesDown),
+    (Key::Ctrl(b'd'), Action::DuplicateLines),
+    (Key::Alt(b'k'), Action::DeleteLines),
+    (Key::Ctrl(b'\x7f'), Action::ToggleComment),
+    (Key::Alt(b'y'), Action::Yank),
+    (Key::Alt(b'.'), Action::NextBuffer),
+    (Key::Alt(b','), Action::PrevBuffer),
+    (Key::Return, Action::Newline),
+    (Key::Backspace, Action::Backspace),
+    (Key::Ctrl(b'h'), Action::Backspace),
+    (Key::Del, Action::Delete),
+    (Key::Ctrl(b'g'), Action::SelectRegister),
+    (Key::Ctrl(b'c'), Action::Copy),
+    (Key::Ctrl(b'x'), Action::Cut),
+    (Key::Ctrl(b'v'), Action::Paste),
+    (Key::Ctrl(b'b'), Action::PasteHistory),
+    (Key::Alt(b'='), Action::ExpandSelection),
+    (Key::Alt(b'-'), Action::ShrinkSelection),
+    (Key::Alt(b's'), Action::Surround),
+    (Key::Alt(b'd'), Action::DeleteSurrounding),
+    (Key::Alt(b'c'), Action::ChangeSurrounding),
+    (Key::Alt(b'p'), Action::CompletePath),
+    (Key::Ctrl(b'n'), Action::CompleteWord),
+    (Key::Alt(b'j'), Action::GotoDefinition),
+    (Key::Alt(b'r'), Action::FindReferences),
+    (Key::Alt(b'o'), Action::JumpBack),
+    (Key::Alt(b'i'), Action::JumpForward),
+    (Key::Alt(b'l'), Action::SpeakPosition),
+    (Key::Ctrl(b't'), Action::ToggleMouse),
+    (Key::Ctrl(b'l'), Action::Redraw),
+];
+
+impl Action {
+    pub fn from_name(name: &str) -> Option<Action> {
+        NAMES.iter().find(|&&(n, _)| n == name).map(|&(_, action)| action)
+    }
+
+    pub fn names() -> Vec<&'static str> {
+        NAMES.iter().map(|&(name, _)| name).collect()
+    }
+
+    /// Whether running it changes the buffer.
+    pub fn edits(self) -> bool {
+        matches!(self,
+            Action::Replace | Action::Newline | Action::Backspace | Action::Delete |
+            Action::Cut | Action::Paste | Action::PasteHistory | Action::Surround |
+            Action::DeleteSurrounding | Action::ChangeSurrounding | Action::CompletePath |
+            Action::CompleteWord | Action::Indent | Action::Outdent | Action::Upcase |
+            Action::Downcase | Action::KillLine | Action::KillLineBackward | Action::Yank |
+            Action::MoveLinesUp | Action::MoveLinesDown | Action::DuplicateLines |
+            Action::DeleteLines | Action::ToggleComment)
+    }
+
+    /// Whether the selection is still there afterwards, to move or work on
+    /// again.
+    pub fn keeps_selection(self) -> bool {
+        matches!(self,
+            Action::Up | Action::Down | Action::Left | Action::Right |
+            Action::WordLeft | Action::WordRight |
+            Action::PageUp | Action::PageDown | Action::Home | Action::End |
+            Action::SetMark | Action::SelectUp | Action::SelectDown | Action::SelectLeft |
+            Action::SelectRight | Action::SelectHome | Action::SelectEnd |
+            Action::SelectWordLeft | Action::SelectWordRight |
+            Action::Indent | Action::Outdent | Action::Upcase | Action::Downcase |
+            Action::MoveLinesUp | Action::MoveLinesDown | Action::DuplicateLines |
+            Action::ToggleComment | Action::SelectRegister)
+    }
+}
+
+pub struct Keymap {
+    bindings: HashMap<Key, Action>,
+}
+
+impl Default for Keymap {
+    fn default() -> Keymap {
+        Keymap { bindings: DEFAULTS.iter().cloned().collect() }
+    }
+}
+
+impl Keymap {
+    pub fn get(&self, key: Key) -> Option<Action> {
+        self.bindings.get(&key).cloned()
+    }
+
+    /// Binds `key` to `action`, or unbinds it when `action` is `None`.
+    pub fn bind(&mut self, key: Key, action: Option<Action>) {
+        match action {
+            Some(action) => self.bindings.insert(key, action),
+            None => self.bindings.remove(&key),
+        };
+    }
+}
+
+/// Parses key names like `ctrl-s`, `alt-w`, `pageup` or a single character.
+pub fn parse_key(name: &str) -> Option<Key> {
+    let lower = name.to_ascii_lowercase();
+    let single = |s: &str| if s.len() == 1 { Some(s.as_bytes()[0]) } else { None };
+    if lower == "ctrl-space" {
+        return Some(Key::Ctrl(b'`'));
+    }
+    // Terminals send Ctrl-/ as Ctrl-_.
+    if lower == "ctrl-/" || lower == "ctrl-_" {
+        return Some(Key::Ctrl(b'\x7f'));
+    }
+    if lower.len() == 6 && lower.starts_with("ctrl-") {
+        let c = lower.as_bytes()[5];
+        return if c.is_ascii_lowercase() { Some(Key::Ctrl(c)) } else { None };
+    }
+    if name.len() == 5 && lower.starts_with("alt-") {
+        return single(&name[4..]).map(Key::Alt);
+    }
+    Some(match lower.as_str() {
+        "up" => Key::Up,
+        "down" => Key::Down,
+        "left" => Key::Left,
+        "right" => Key::Right,
+        "home" => Key::Home,
+        "end" => Key::End,
+        "pageup" => Key::PageUp,
+        "pagedown" => Key::PageDown,
+        "del" | "delete" => Key::Del,
+        "backspace" => Key::Backspace,
+        "return" | "enter" => Key::Return,
+        "shift-up" => Key::ShiftUp,
+        "shift-down" => Key::ShiftDown,
+        "shift-left" => Key::ShiftLeft,
+        "shift-right" => Key::ShiftRight,
+        "shift-home" => Key::ShiftHome,
+        "shift-end" => Key::ShiftEnd,
+        "ctrl-left" => Key::CtrlLeft,
+        "ctrl-right" => Key::CtrlRight,
+        "ctrl-shift-left" => Key::CtrlShiftLeft,
+        "ctrl-shift-right" => Key::CtrlShiftRight,
+        "alt-up" => Key::AltUp,
+        "alt-down" => Key::AltDown,
+        "tab" => Key::Ctrl(b'i'),
+        "shift-tab" => Key::BackTab,
+        "esc" => Key::Char(b'\x1b'),
+        _ => return single(name).filter(|c| c.is_ascii_graphic()).map(Key::Char),
+    })
+}
+
+/// The name `parse_key` reads back as `key`.
+pub fn key_name(key: Key) -> String {
+    match key {
+        Key::Ctrl(b'i') => "tab".to_string(),
+        Key::Ctrl(b'`') => "ctrl-space".to_string(),
+        Key::Ctrl(b'\x7f') => "ctrl-/".to_string(),
+        Key::Ctrl(c) => format!("ctrl-{}", c as char),
+        Key::Alt(c) => format!("alt-{}", c as char),
+        Key::Char(b'\x1b') => "esc".to_string(),
+        Key::Char(c) => (c as char).to_string(),
+        Key::Up => "up".to_string(),
+        Key::Down => "down".to_string(),
+        Key::Left => "left".to_string(),
+        Key::Right => "right".to_string(),
+        Key::Home => "home".to_string(),
+        Key::End => "end".to_string(),
+        Key::PageUp => "pageup".to_string(),
+        Key::PageDown => "pagedown".to_string(),
+        Key::Del => "del".to_string(),
+        Key::Backspace => "backspace".to_string(),
+        Key::Return => "return".to_string(),
+        Key::ShiftUp => "shift-up".to_string(),
+        Key::ShiftDown => "shift-down".to_string(),
+        Key::ShiftLeft => "shift-left".to_string(),
+        Key::ShiftRight => "shift-right".to_string(),
+        Key::ShiftHome => "shift-home".to_string(),
+        Key::ShiftEnd => "shift-end".to_string(),
+        Key::CtrlLeft => "ctrl-left".to_string(),
+        Key::CtrlRight => "ctrl-right".to_string(),
+        Key::CtrlShiftLeft => "ctrl-shift-left".to_string(),
+        Key::CtrlShiftRight => "ctrl-shift-right".to_string(),
+        Key::AltUp => "alt-up".to_string(),
+        Key::AltDown => "alt-down".to_string(),
+        Key::BackTab => "shift-tab".to_string(),
+        _ => format!("{:?}", key),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_names_parse() {
+        assert_eq!(parse_key("ctrl-s"), Some(Key::Ctrl(b's')));
+        assert_eq!(parse_key("Ctrl-S"), Some(Key::Ctrl(b's')));
+        assert_eq!(parse_key("alt-w"), Some(Key::Alt(b'w')));
+        assert_eq!(parse_key("alt-W"), Some(Key::Alt(b'W')));
+        assert_eq!(parse_key("PageDown"), Some(Key::PageDown));
+        assert_eq!(parse_key("enter"), Some(Key::Return));
+        assert_eq!(parse_key("return"), Some(Key::Return));
+        assert_eq!(parse_key("delete"), Some(Key::Del));
+        assert_eq!(parse_key("tab"), Some(Key::Ctrl(b'i')));
+        assert_eq!(parse_key("esc"), Some(Key::Char(b'\x1b')));
+        assert_eq!(parse_key("x"), Some(Key::Char(b'x')));
+    }
+
+    #[test]
+    fn nonsense_is_refused() {
+        for name in &["", " ", "ctrl-1", "ctrl-", "ctrl-ab", "alt-", "hyper-x", "pagesideways", "é"] {
+            assert_eq!(parse_key(name), None, "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn names_read_back() {
+        let keys = [
+            Key::Ctrl(b'q'), Key::Alt(b'f'), Key::Char(b'a'), Key::Char(b'\x1b'), Key::Ctrl(b'i'),
+            Key::Up, Key::Down, Key::Left, Key::Right, Key::Home, Key::End, Key::PageUp,
+            Key::PageDown, Key::Del, Key::Backspace, Key::Return,
+        ];
+        for &key in &keys {
+            assert_eq!(parse_key(&key_name(key)), Some(key), "{}", key_name(key));
+        }
+    }
+
+    #[test]
+    fn bindings_can_be_changed() {
+        let mut keymap = Keymap::default();
+        assert_eq!(keymap.get(Key::Ctrl(b's')), Some(Action::Save));
+        keymap.bind(Key::Ctrl(b's'), None);
+        assert_eq!(keymap.get(Key::Ctrl(b's')), None);
+        keymap.bind(Key::Alt(b'q'), Action::from_name("quit"));
+        assert_eq!(keymap.get(Key::Alt(b'q')), Some(Action::Quit));
+        assert_eq!(Action::from_name("no-such-command"), None);
+    }
+}
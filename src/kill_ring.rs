@@ -0,0 +1,64 @@
+//! Text killed with the Emacs style line kills, kept apart from the copy
+//! registers. Yanking puts back the newest kill; yanking again straight
+//! after swaps it for the one before, going round the ring.
+
+use std::collections::VecDeque;
+
+/// Kills kept before the oldest ones are dropped.
+pub const MAX_KILLS: usize = 32;
+
+#[derive(Default)]
+pub struct KillRing {
+    /// Newest first.
+    kills: VecDeque<String>,
+    /// The kill the last yank put back.
+    yanked: usize,
+}
+
+impl KillRing {
+    /// Adds a kill. Kills straight after one another make one entry:
+    /// `append` adds to the end of the newest for forward kills, otherwise
+    /// to its start.
+    pub fn kill(&mut self, text: String, continues: bool, append: bool) {
+        match self.kills.front_mut() {
+            Some(newest) if continues => {
+                if append {
+                    newest.push_str(&text);
+                } else {
+                    newest.insert_str(0, &text);
+                }
+            }
+            _ => {
+                self.kills.push_front(text);
+                self.kills.truncate(MAX_KILLS);
+            }
+        }
+        self.yanked = 0;
+    }
+
+    /// Adds text copied elsewhere, unless it's the newest kill already.
+    pub fn offer(&mut self, text: String) {
+        if !text.is_empty() && self.kills.front() != Some(&text) {
+            self.kill(text, false, true);
+        }
+    }
+
+    pub fn newest(&self) -> Option<&str> {
+        self.kills.front().map(|kill| kill.as_str())
+    }
+
+    /// The newest kill, to yank.
+    pub fn yank(&mut self) -> Option<&str> {
+        self.yanked = 0;
+        self.newest()
+    }
+
+    /// The kill before the last one yanked, wrapping round to the newest.
+    pub fn rotate(&mut self) -> Option<&str> {
+        if self.kills.is_empty() {
+            return None;
+        }
+        self.yanked = (self.yanked + 1) % self.kills.len();
+        Some(&self.kills[self.yanked])
+    }
+}
@@ -0,0 +1,82 @@
+extern crate libc;
+extern crate notify;
+extern crate regex;
+#[macro_use]
+extern crate serde_json;
+#[cfg(feature = "syntect")]
+extern crate syntect;
+extern crate termios;
+extern crate termsize;
+extern crate toml;
+extern crate unicode_bidi;
+extern crate unicode_normalization;
+extern crate unicode_segmentation;
+extern crate unicode_width;
+
+#[macro_use]
+mod messages;
+mod args;
+mod atomic_save;
+mod bidi;
+mod buffer;
+mod calc;
+mod clipboard;
+mod complete;
+mod config;
+mod crash;
+mod diff;
+mod edit;
+mod editor;
+mod finder;
+mod git;
+mod grep;
+mod hex;
+mod indent;
+mod input;
+mod jumps;
+mod keymap;
+mod kill_ring;
+mod loader;
+mod local_history;
+mod lsp;
+mod marks;
+mod render;
+mod search;
+mod shell;
+mod signs;
+mod snippet;
+mod status;
+#[cfg(feature = "syntect")]
+mod sublime;
+mod syntax;
+mod terminal;
+mod text;
+mod undo;
+mod vim;
+mod watch;
+
+pub use editor::{Editor, Exit, Flow};
+
+/// Runs the editor on the files named on the command line and says how
+/// it ended.
+pub fn run(files: &[String]) -> Exit {
+    messages::init();
+    let args = match args::parse(files) {
+        Some(args) => args,
+        None => return Exit { code: editor::EXIT_USAGE, message: Some(tr!(Usage)) },
+    };
+
+    // The editor is dropped at the end of the block, which releases its
+    // locks and restores the terminal before anything is printed.
+    let mut editor = match Editor::new() {
+        Ok(editor) => editor,
+        Err(exit) => return exit,
+    };
+    if args.vim {
+        editor.set_vim(true);
+    }
+    match editor.init().and_then(|()| editor.open_files(&args.files)) {
+        Ok(()) => editor.run(),
+        Err(exit) => exit,
+    }
+}
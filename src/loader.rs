@@ -0,0 +1,57 @@
+//! Reading large files a piece at a time. The start of the file is read
+//! when it's opened so it can be shown straight away; the rest is read
+//! while the editor waits for keys, or all at once when something needs
+//! the whole file.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, Result};
+
+use text::Rows;
+
+/// Files larger than this are streamed in.
+pub const STREAM_THRESHOLD: u64 = 8 << 20;
+/// Bytes read on opening, plenty for the first screen.
+pub const FIRST_CHUNK: u64 = 256 << 10;
+/// Bytes read each time the editor is idle: a few milliseconds' work.
+pub const IDLE_CHUNK: u64 = 1 << 20;
+
+pub struct Loader {
+    reader: BufReader<File>,
+    size: u64,
+    read: u64,
+}
+
+impl Loader {
+    pub fn new(file: File, size: u64) -> Loader {
+        Loader { reader: BufReader::new(file), size, read: 0 }
+    }
+
+    /// Reads whole lines into `rows` until about `bytes` more have been
+    /// read, returning whether the end of the file was reached.
+    pub fn load(&mut self, rows: &mut Rows, bytes: u64) -> Result<bool> {
+        let target = self.read.saturating_add(bytes);
+        let mut line = String::new();
+        while self.read < target {
+            line.clear();
+            let n = self.reader.read_line(&mut line)?;
+            if n == 0 {
+                return Ok(true);
+            }
+            self.read += n as u64;
+            // The same lines `BufRead::lines` would give.
+            if line.ends_with('\n') {
+                line.pop();
+                if line.ends_with('\r') {
+                    line.pop();
+                }
+            }
+            rows.push(line.clone());
+        }
+        Ok(false)
+    }
+
+    /// How much of the file has been read, in percent.
+    pub fn percent(&self) -> u64 {
+        (self.read * 100).checked_div(self.size).map_or(100, |percent| percent.min(100))
+    }
+}
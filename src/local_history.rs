@@ -0,0 +1,83 @@
+//! Copies of files as they were saved, kept under the state directory so
+//! earlier versions can be looked at and brought back without git.
+//!
+//! Each file gets a directory named after its path, holding one copy per
+//! save named by the time of the save in milliseconds.
+
+use std::cmp::Reverse;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Versions kept per file.
+pub const MAX_VERSIONS: usize = 50;
+/// Bytes kept per file; the oldest versions go first.
+pub const MAX_BYTES: u64 = 20 << 20;
+
+pub struct Version {
+    pub path: PathBuf,
+    pub time: SystemTime,
+    pub size: u64,
+}
+
+fn dir_for(root: &Path, file: &str) -> PathBuf {
+    root.join("history").join(file.replace('%', "%25").replace('/', "%2F"))
+}
+
+/// Saved versions of `file`, newest first.
+pub fn versions(root: &Path, file: &str) -> Vec<Version> {
+    let entries = match fs::read_dir(dir_for(root, file)) {
+        Ok(entries) => entries,
+        Err(_) => return vec![],
+    };
+    let mut versions: Vec<Version> = entries
+        .filter_map(|e| e.ok())
+        .filter_map(|e| {
+            let millis: u64 = e.file_name().to_str()?.parse().ok()?;
+            Some(Version {
+                path: e.path(),
+                time: UNIX_EPOCH + Duration::from_millis(millis),
+                size: e.metadata().ok()?.len(),
+            })
+        })
+        .collect();
+    versions.sort_by_key(|v| Reverse(v.time));
+    versions
+}
+
+/// Keeps `contents` as the newest version of `file`, unless it's the same
+/// as the last one kept, and drops versions past the limits.
+pub fn record(root: &Path, file: &str, contents: &str) -> io::Result<()> {
+    let existing = versions(root, file);
+    if let Some(newest) = existing.first() {
+        if newest.size == contents.len() as u64 && fs::read_to_string(&newest.path)? == contents {
+            return Ok(());
+        }
+    }
+    let dir = dir_for(root, file);
+    fs::create_dir_all(&dir)?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let millis = now.as_secs() * 1000 + now.subsec_millis() as u64;
+    fs::write(dir.join(millis.to_string()), contents)?;
+
+    let mut total = contents.len() as u64;
+    for (i, version) in existing.iter().enumerate() {
+        total += version.size;
+        if i + 1 >= MAX_VERSIONS || total > MAX_BYTES {
+            fs::remove_file(&version.path)?;
+        }
+    }
+    Ok(())
+}
+
+/// How long ago `time` was, roughly.
+pub fn age(time: SystemTime) -> String {
+    let secs = SystemTime::now().duration_since(time).map(|d| d.as_secs()).unwrap_or(0);
+    match secs {
+        0..=59 => format!("{}s ago", secs),
+        60..=3599 => format!("{}m ago", secs / 60),
+        3600..=86399 => format!("{}h ago", secs / 3600),
+        _ => format!("{}d ago", secs / 86400),
+    }
+}
@@ -0,0 +1,803 @@
+//! Talking to language servers. A filetype with a `language_server` set
+//! gets that command started the first time one of its files is shown;
+//! files of the same filetype share it.
+//!
+//! ```toml
+//! [filetype.rust]
+//! language_server = "rust-analyzer"
+//! ```
+//!
+//! Messages are JSON-RPC with a `Content-Length` header, over the server's
+//! standard input and output. What the server sends is read on a thread of
+//! its own and handed to the event loop through a channel, with a byte
+//! written to a pipe to wake it up, as for file changes. What goes to it is
+//! written on another thread, so a server slow to read never holds up the
+//! editor. Documents are kept in sync by sending all of the text after
+//! each change to it.
+
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::str;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+use serde_json::{self, Value};
+
+use complete::CompletionItem;
+use edit::is_word_char;
+use editor::Editor;
+use render::Color;
+use signs::Sign;
+use text::Rows;
+use watch;
+
+/// The group of the signs diagnostics put in the gutter.
+pub const DIAGNOSTICS: &str = "diagnostics";
+
+/// A message from a server; `None` once it has gone away.
+pub struct Incoming {
+    pub server: String,
+    pub message: Option<Value>,
+}
+
+/// Something the server found wrong, with where it is as (row, byte)
+/// positions in the buffer.
+pub struct Diagnostic {
+    pub start: (usize, usize),
+    pub end: (usize, usize),
+    /// 1 for errors, then warnings, information and hints.
+    pub severity: u64,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn color(&self) -> Color {
+        match self.severity {
+            1 => Color::Red,
+            2 => Color::Yellow,
+            3 => Color::Blue,
+            _ => Color::Cyan,
+        }
+    }
+
+    fn sign(&self) -> Sign {
+        let glyph = match self.severity {
+            1 => 'E',
+            2 => 'W',
+            3 => 'I',
+            _ => 'H',
+        };
+        Sign { group: DIAGNOSTICS.to_string(), glyph, color: self.color(), priority: 10 }
+    }
+}
+
+/// The byte offset in `line` of a position counted in UTF-16 code units,
+/// as servers count them.
+pub fn byte_column(line: &str, utf16: usize) -> usize {
+    let mut units = 0;
+    for (i, c) in line.char_indices() {
+        if units >= utf16 {
+            return i;
+        }
+        units += c.len_utf16();
+    }
+    line.len()
+}
+
+/// A server position as (row, byte) in `rows`.
+fn position(rows: &Rows, position: &Value) -> (usize, usize) {
+    let row = position["line"].as_u64().unwrap_or(0) as usize;
+    let character = position["character"].as_u64().unwrap_or(0) as usize;
+    (row, rows.get(row).map_or(0, |line| byte_column(line, character)))
+}
+
+/// An open document: the version the server last got, and the count of
+/// changes to the buffer it was sent at.
+struct Document {
+    version: i64,
+    changes: u64,
+}
+
+pub struct Client {
+    child: Child,
+    /// Messages for the thread writing to the server.
+    writer: Sender<Value>,
+    next_id: u64,
+    /// Requests not answered yet, by id, with their method.
+    pending: HashMap<u64, &'static str>,
+    /// Documents opened on the server, by URI.
+    documents: HashMap<String, Document>,
+    /// Whether the server has answered `initialize`. Until then nothing
+    /// else may be sent, so it waits in `queued`.
+    ready: bool,
+    queued: Vec<Value>,
+    /// What the server said it can do.
+    pub capabilities: Value,
+}
+
+/// The servers running, by command.
+pub struct Servers {
+    clients: HashMap<String, Client>,
+    /// Commands that couldn't be started, so they aren't tried on every key.
+    failed: HashSet<String>,
+    sender: Sender<Incoming>,
+    receiver: Receiver<Incoming>,
+    /// The pipe the reading threads write to when a message comes in; made
+    /// when the first server starts.
+    wake: Option<(File, File)>,
+}
+
+impl Default for Servers {
+    fn default() -> Servers {
+        let (sender, receiver) = mpsc::channel();
+        Servers {
+            clients: HashMap::new(),
+            failed: HashSet::new(),
+            sender,
+            receiver,
+            wake: None,
+        }
+    }
+}
+
+/// `file://` URI for an absolute path.
+pub fn uri(path: &Path) -> String {
+    let mut uri = "file://".to_string();
+    for &b in path.to_string_lossy().as_bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'.' | b'_' | b'~' => uri.push(b as char),
+            _ => uri += &format!("%{:02X}", b),
+        }
+    }
+    uri
+}
+
+/// The path a `file://` URI names.
+pub fn path_from_uri(uri: &str) -> Option<PathBuf> {
+    if !uri.starts_with("file://") {
+        return None;
+    }
+    let mut bytes = Vec::new();
+    let mut rest = uri["file://".len()..].bytes();
+    while let Some(b) = rest.next() {
+        if b == b'%' {
+            let hex: Vec<u8> = rest.by_ref().take(2).collect();
+            bytes.push(u8::from_str_radix(str::from_utf8(&hex).ok()?, 16).ok()?);
+        } else {
+            bytes.push(b);
+        }
+    }
+    String::from_utf8(bytes).ok().map(PathBuf::from)
+}
+
+/// The places a definition or references answer names, as a path and a
+/// line and UTF-16 column counted from 0. It may be one location, a list
+/// of them or a list of links to them.
+fn locations(result: &Value) -> Vec<(PathBuf, usize, usize)> {
+    let list = match *result {
+        Value::Array(ref list) => list.clone(),
+        Value::Null => vec![],
+        ref one => vec![one.clone()],
+    };
+    list.iter().filter_map(|l| {
+        let (uri, start) = match l.get("targetUri") {
+            Some(uri) => (uri, &l["targetSelectionRange"]["start"]),
+            None => (&l["uri"], &l["range"]["start"]),
+        };
+        let path = path_from_uri(uri.as_str()?)?;
+        let line = start["line"].as_u64()? as usize;
+        Some((path, line, start["character"].as_u64().unwrap_or(0) as usize))
+    }).collect()
+}
+
+/// The name of a `CompletionItemKind`.
+fn kind_name(kind: u64) -> &'static str {
+    const KINDS: [&str; 25] = [
+        "text", "method", "function", "constructor", "field", "variable", "class", "interface",
+        "module", "property", "unit", "value", "enum", "keyword", "snippet", "color", "file",
+        "reference", "folder", "member", "constant", "struct", "event", "operator", "type",
+    ];
+    KINDS.get((kind as usize).wrapping_sub(1)).cloned().unwrap_or("")
+}
+
+/// The items of a completion answer, which may be a list of them or a
+/// list with a flag saying whether it's complete, in the order the server
+/// wants them.
+fn completion_items(rows: &Rows, result: &Value) -> Vec<CompletionItem> {
+    let list = match result.get("items") {
+        Some(items) => items,
+        None => result,
+    };
+    let mut items: Vec<(String, CompletionItem)> = list.as_array().map_or(&[][..], |l| &l[..]).iter().map(|item| {
+        let label = item["label"].as_str().unwrap_or("").to_string();
+        let text = |key: &str| item[key].as_str().map(|s| s.to_string());
+        // An edit either has a range, or one to insert over and one to
+        // replace; this inserts.
+        let edit = &item["textEdit"];
+        let range = edit.get("insert").unwrap_or(&edit["range"]);
+        let completion = CompletionItem {
+            kind: kind_name(item["kind"].as_u64().unwrap_or(0)),
+            detail: text("detail").unwrap_or_default(),
+            text: edit["newText"].as_str().map(|s| s.to_string())
+                .or_else(|| text("insertText")).unwrap_or_else(|| label.clone()),
+            start: range.get("start").map(|start| position(rows, start)),
+            filter: text("filterText").unwrap_or_else(|| label.clone()),
+            label: label.clone(),
+        };
+        (text("sortText").unwrap_or(label), completion)
+    }).collect();
+    items.sort_by(|a, b| a.0.cmp(&b.0));
+    items.into_iter().map(|(_, item)| item).collect()
+}
+
+/// Where the project holding `path` starts: the nearest directory with a
+/// `.git`, or the file's own directory.
+fn project_root(path: &Path) -> PathBuf {
+    let dir = path.parent().unwrap_or(Path::new("/"));
+    dir.ancestors().find(|d| d.join(".git").exists()).unwrap_or(dir).to_path_buf()
+}
+
+/// Reads one message, or `None` at the end of the stream.
+fn read_message<R: BufRead>(reader: &mut R) -> io::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(value) = line.strip_prefix("Content-Length:") {
+            length = value.trim().parse().ok();
+        }
+    }
+    let length = length.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "no Content-Length"))?;
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    serde_json::from_slice(&body).map(Some).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Writes one message with its header.
+fn write_message<W: Write>(writer: &mut W, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    writer.flush()
+}
+
+impl Client {
+    /// Hands a message to the writing thread, which has stopped if the
+    /// server can no longer be written to.
+    fn send(&mut self, message: Value) -> io::Result<()> {
+        self.writer.send(message).map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))
+    }
+
+    /// Sends now, or once the server is ready.
+    fn send_when_ready(&mut self, message: Value) -> io::Result<()> {
+        if self.ready {
+            self.send(message)
+        } else {
+            self.queued.push(message);
+            Ok(())
+        }
+    }
+
+    /// Sends a request, returning its id to match the answer by.
+    pub fn request(&mut self, method: &'static str, params: Value) -> io::Result<u64> {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.pending.insert(id, method);
+        let message = json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params});
+        if method == "initialize" {
+            self.send(message)?;
+        } else {
+            self.send_when_ready(message)?;
+        }
+        Ok(id)
+    }
+
+    pub fn notify(&mut self, method: &str, params: Value) -> io::Result<()> {
+        self.send_when_ready(json!({"jsonrpc": "2.0", "method": method, "params": params}))
+    }
+
+    /// Answers a request from the server.
+    fn reply(&mut self, id: Value, result: Value) -> io::Result<()> {
+        self.send(json!({"jsonrpc": "2.0", "id": id, "result": result}))
+    }
+
+    /// Whether a `method` request is still waiting for its answer.
+    fn waiting(&self, method: &str) -> bool {
+        self.pending.values().any(|&m| m == method)
+    }
+
+    /// The method of the request answered by `id`, which is no longer
+    /// pending.
+    pub fn answered(&mut self, id: &Value) -> Option<&'static str> {
+        self.pending.remove(&id.as_u64()?)
+    }
+
+    /// The server is set up: tell it so and send what was waiting.
+    fn initialized(&mut self, capabilities: Value) -> io::Result<()> {
+        self.capabilities = capabilities;
+        self.ready = true;
+        self.send(json!({"jsonrpc": "2.0", "method": "initialized", "params": {}}))?;
+        for message in self.queued.drain(..).collect::<Vec<_>>() {
+            self.send(message)?;
+        }
+        Ok(())
+    }
+
+    /// The count of buffer changes the document was last sent at, if it
+    /// is open.
+    pub fn synced(&self, uri: &str) -> Option<u64> {
+        self.documents.get(uri).map(|doc| doc.changes)
+    }
+
+    /// Opens the document on the server, or sends its text again.
+    pub fn sync(&mut self, uri: &str, language: &str, changes: u64, text: String) -> io::Result<()> {
+        let version = match self.documents.get(uri) {
+            Some(doc) => doc.version + 1,
+            None => {
+                self.notify("textDocument/didOpen", json!({"textDocument": {
+                    "uri": uri, "languageId": language, "version": 0, "text": text,
+                }}))?;
+                self.documents.insert(uri.to_string(), Document { version: 0, changes });
+                return Ok(());
+            }
+        };
+        self.notify("textDocument/didChange", json!({
+            "textDocument": {"uri": uri, "version": version},
+            "contentChanges": [{"text": text}],
+        }))?;
+        self.documents.insert(uri.to_string(), Document { version, changes });
+        Ok(())
+    }
+
+    pub fn saved(&mut self, uri: &str) -> io::Result<()> {
+        if !self.documents.contains_key(uri) {
+            return Ok(());
+        }
+        self.notify("textDocument/didSave", json!({"textDocument": {"uri": uri}}))
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        let _ = self.send(json!({"jsonrpc": "2.0", "method": "exit"}));
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+impl Servers {
+    /// For `poll` to wait on along with the keyboard, once a server runs.
+    pub fn fd(&self) -> RawFd {
+        self.wake.as_ref().map_or(-1, |(read, _)| read.as_raw_fd())
+    }
+
+    pub fn get(&mut self, command: &str) -> Option<&mut Client> {
+        self.clients.get_mut(command)
+    }
+
+    /// The server run by `command`, started for the file at `path` if it
+    /// isn't running. `None` when it can't be started.
+    fn start(&mut self, command: &str, path: &Path) -> Option<&mut Client> {
+        if !self.clients.contains_key(command) {
+            if self.failed.contains(command) {
+                return None;
+            }
+            match self.spawn(command, path) {
+                Ok(client) => {
+                    self.clients.insert(command.to_string(), client);
+                }
+                Err(_) => {
+                    self.failed.insert(command.to_string());
+                    return None;
+                }
+            }
+        }
+        self.clients.get_mut(command)
+    }
+
+    fn spawn(&mut self, command: &str, path: &Path) -> io::Result<Client> {
+        if self.wake.is_none() {
+            self.wake = Some(watch::pipe()?);
+        }
+        let mut signal = self.wake.as_ref().unwrap().1.try_clone()?;
+        let mut child = Command::new("sh").arg("-c").arg(command)
+            .stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::null())
+            .spawn()?;
+        let mut stdin = child.stdin.take().unwrap();
+        let mut stdout = BufReader::new(child.stdout.take().unwrap());
+        let sender = self.sender.clone();
+        let server = command.to_string();
+        thread::spawn(move || loop {
+            let message = read_message(&mut stdout).unwrap_or(None);
+            let done = message.is_none();
+            if sender.send(Incoming { server: server.clone(), message }).is_err() {
+                break;
+            }
+            let _ = signal.write(&[1]);
+            if done {
+                break;
+            }
+        });
+        let (writer, outgoing) = mpsc::channel::<Value>();
+        thread::spawn(move || {
+            for message in outgoing {
+                if write_message(&mut stdin, &message).is_err() {
+                    break;
+                }
+            }
+        });
+        let mut client = Client {
+            child,
+            writer,
+            next_id: 1,
+            pending: HashMap::new(),
+            documents: HashMap::new(),
+            ready: false,
+            queued: Vec::new(),
+            capabilities: Value::Null,
+        };
+        client.request("initialize", json!({
+            "processId": ::std::process::id(),
+            "rootUri": uri(&project_root(path)),
+            "clientInfo": {"name": "kilo", "version": env!("CARGO_PKG_VERSION")},
+            "capabilities": {
+                "textDocument": {
+                    "synchronization": {"didSave": true},
+                    "publishDiagnostics": {},
+                    "definition": {"linkSupport": true},
+                    "references": {},
+                    "completion": {"completionItem": {"snippetSupport": false}},
+                },
+            },
+        }))?;
+        Ok(client)
+    }
+
+    /// The messages that came in since the last call.
+    fn poll(&mut self) -> Vec<Incoming> {
+        if let Some((ref mut read, _)) = self.wake {
+            let mut buf = [0; 64];
+            while let Ok(n) = read.read(&mut buf) {
+                if n == 0 {
+                    break;
+                }
+            }
+        }
+        self.receiver.try_iter().collect()
+    }
+}
+
+impl Editor {
+    /// The command of the language server for this buffer and the file
+    /// it holds, when it has both.
+    fn lsp_document(&self) -> Option<(String, PathBuf)> {
+        let command = self.config.language_server.clone()?;
+        if self.scratch || self.listing || self.hex.is_some() || self.loading.is_some() {
+            return None;
+        }
+        Some((command, PathBuf::from(self.filename.as_ref()?)))
+    }
+
+    /// Starts the buffer's language server if needed and tells it about
+    /// any change to the text since last time.
+    pub(crate) fn lsp_sync(&mut self) {
+        let (command, path) = match self.lsp_document() {
+            Some(document) => document,
+            None => return,
+        };
+        let uri = uri(&path);
+        let changes = self.changes;
+        if self.servers.get(&command).is_some_and(|client| client.synced(&uri) == Some(changes)) {
+            return;
+        }
+        let language = self.highlighter.syntax.map_or("plaintext", |s| s.filetype()).to_lowercase();
+        let text = self.rows_to_string();
+        let started = self.servers.failed.contains(&command) || self.servers.clients.contains_key(&command);
+        let res = match self.servers.start(&command, &path) {
+            Some(client) => client.sync(&uri, &language, changes, text),
+            None if !started => {
+                self.set_status_msg(tr!(LspStartFailed, command));
+                return;
+            }
+            None => return,
+        };
+        if let Err(e) = res {
+            self.lsp_failed(&command, e);
+        }
+    }
+
+    /// Tells the buffer's language server the file was saved.
+    pub(crate) fn lsp_saved(&mut self) {
+        let (command, path) = match self.lsp_document() {
+            Some(document) => document,
+            None => return,
+        };
+        let res = match self.servers.get(&command) {
+            Some(client) => client.saved(&uri(&path)),
+            None => return,
+        };
+        if let Err(e) = res {
+            self.lsp_failed(&command, e);
+        }
+    }
+
+    /// Drops a server that can no longer be written to.
+    fn lsp_failed(&mut self, command: &str, e: io::Error) {
+        self.servers.clients.remove(command);
+        self.servers.failed.insert(command.to_string());
+        self.set_status_msg(tr!(LspFailed, command, e));
+    }
+
+    /// Handles what the language servers sent since last time.
+    pub(crate) fn handle_lsp(&mut self) {
+        for Incoming { server, message } in self.servers.poll() {
+            let message = match message {
+                Some(message) => message,
+                None => {
+                    if self.servers.clients.remove(&server).is_some() {
+                        self.servers.failed.insert(server.clone());
+                        self.set_status_msg(tr!(LspExited, server));
+                    }
+                    continue;
+                }
+            };
+            if let Err(e) = self.lsp_message(&server, message) {
+                self.lsp_failed(&server, e);
+            }
+        }
+    }
+
+    fn lsp_message(&mut self, server: &str, mut message: Value) -> io::Result<()> {
+        let client = match self.servers.clients.get_mut(server) {
+            Some(client) => client,
+            None => return Ok(()),
+        };
+        let method = message["method"].as_str().map(|m| m.to_string());
+        let params = message["params"].take();
+        match (method, message.get("id").cloned()) {
+            // A request from the server. Settings it asks for are left at
+            // its defaults; anything else is acknowledged and ignored.
+            (Some(method), Some(id)) => {
+                let result = match method.as_str() {
+                    "workspace/configuration" => {
+                        let items = params["items"].as_array().map_or(0, |items| items.len());
+                        Value::Array(vec![Value::Null; items])
+                    }
+                    _ => Value::Null,
+                };
+                client.reply(id, result)
+            }
+            (Some(method), None) => {
+                match method.as_str() {
+                    "textDocument/publishDiagnostics" => self.publish_diagnostics(&params),
+                    "window/showMessage" => {
+                        if let Some(text) = params["message"].as_str() {
+                            let text = text.lines().next().unwrap_or("").to_string();
+                            self.set_status_msg(text);
+                        }
+                    }
+                    _ => {}
+                }
+                Ok(())
+            }
+            (None, Some(id)) => {
+                let method = client.answered(&id);
+                if let Some(error) = message.get("error") {
+                    let text = error["message"].as_str().unwrap_or("").to_string();
+                    self.set_status_msg(tr!(LspError, server, text));
+                    return Ok(());
+                }
+                let result = message["result"].take();
+                match method {
+                    Some("initialize") => client.initialized(result["capabilities"].clone()),
+                    Some("textDocument/definition") => self.show_locations(&result, true),
+                    Some("textDocument/references") => self.show_locations(&result, false),
+                    Some("textDocument/completion") => {
+                        // Only wanted while the cursor is in the word it was
+                        // asked for.
+                        let asked = self.completion_asked.take();
+                        if asked.is_some() && asked == self.lsp_document().map(|(_, path)| (path, self.word_before())) {
+                            let items = completion_items(&self.rows, &result);
+                            self.show_completions(items);
+                        }
+                        Ok(())
+                    }
+                    _ => Ok(()),
+                }
+            }
+            (None, None) => Ok(()),
+        }
+    }
+
+    /// Puts what the server found wrong with a file in its buffer, if it
+    /// has one open.
+    fn publish_diagnostics(&mut self, params: &Value) {
+        let uri_of = |filename: &Option<String>| filename.as_ref().map(|f| uri(Path::new(f)));
+        let target = params["uri"].as_str().map(|u| u.to_string());
+        let found = params["diagnostics"].as_array().cloned().unwrap_or_default();
+        let convert = |rows: &Rows| -> Vec<Diagnostic> {
+            let mut diagnostics: Vec<Diagnostic> = found.iter().map(|d| Diagnostic {
+                start: position(rows, &d["range"]["start"]),
+                end: position(rows, &d["range"]["end"]),
+                severity: d["severity"].as_u64().unwrap_or(1),
+                message: d["message"].as_str().unwrap_or("").to_string(),
+            }).collect();
+            // The worst first, for the sign and message of a line.
+            diagnostics.sort_by_key(|d| (d.severity, d.start));
+            diagnostics
+        };
+        if uri_of(&self.filename) == target {
+            self.diagnostics = convert(&self.rows);
+            self.signs.clear_group(DIAGNOSTICS);
+            for d in self.diagnostics.iter().rev() {
+                self.signs.place(d.start.0, d.sign());
+            }
+        } else if let Some(buffer) = self.buffers.iter_mut().find(|b| uri_of(&b.filename) == target) {
+            buffer.diagnostics = convert(&buffer.rows);
+            buffer.signs.clear_group(DIAGNOSTICS);
+            for d in buffer.diagnostics.iter().rev() {
+                buffer.signs.place(d.start.0, d.sign());
+            }
+        }
+    }
+
+    /// Asks the buffer's language server `method` about the symbol under
+    /// the cursor. The answer is dealt with when it comes in.
+    pub(crate) fn lsp_ask(&mut self, method: &'static str) {
+        let (command, path) = match self.lsp_document() {
+            Some(document) => document,
+            None => {
+                self.set_status_msg(tr!(NoLanguageServer));
+                return;
+            }
+        };
+        // The server has to know the text the position is in.
+        self.lsp_sync();
+        let mut params = self.lsp_position(&path);
+        if method == "textDocument/references" {
+            params["context"] = json!({"includeDeclaration": true});
+        }
+        let res = match self.servers.get(&command) {
+            Some(client) => client.request(method, params),
+            None => return,
+        };
+        if let Err(e) = res {
+            self.lsp_failed(&command, e);
+        }
+    }
+
+    /// Jumps to the one place a definition or references answer names, or
+    /// lists them all as grep does.
+    fn show_locations(&mut self, result: &Value, definition: bool) -> io::Result<()> {
+        let found = locations(result);
+        match found.len() {
+            0 if definition => self.set_status_msg(tr!(NoDefinition)),
+            0 => self.set_status_msg(tr!(NoReferences)),
+            1 if definition => {
+                let (ref path, line, character) = found[0];
+                let here = (self.cy, self.cx);
+                self.jump_from(here);
+                self.open_in_buffer(path.to_string_lossy().into_owned())?;
+                // Still somewhere else when the file couldn't be opened.
+                if self.filename.as_ref().map(PathBuf::from).as_ref() == Some(path) {
+                    self.go_to(line + 1, 1);
+                    let row = self.rows.get(self.cy).map_or("", |row| row.as_str());
+                    self.cx = byte_column(row, character);
+                }
+            }
+            count => {
+                let cwd = env::current_dir()?;
+                let lines = found.iter().map(|&(ref path, line, _)| {
+                    let text = fs::read_to_string(path).ok()
+                        .and_then(|text| text.lines().nth(line).map(|l| l.to_string()))
+                        .unwrap_or_default();
+                    let name = path.strip_prefix(&cwd).unwrap_or(path);
+                    format!("{}:{}: {}", name.display(), line + 1, text)
+                }).collect();
+                self.show_in_scratch(lines);
+                self.locations = true;
+                self.set_status_msg(tr!(LocationsFound, count));
+            }
+        }
+        Ok(())
+    }
+
+    /// The cursor's place in the file at `path`, as servers want it.
+    fn lsp_position(&self, path: &Path) -> Value {
+        let line = self.rows.get(self.cy).map_or("", |row| row.as_str());
+        let character: usize = line[..self.cx.min(line.len())].chars().map(|c| c.len_utf16()).sum();
+        json!({
+            "textDocument": {"uri": uri(path)},
+            "position": {"line": self.cy, "character": character},
+        })
+    }
+
+    /// Asks the buffer's language server, if it can complete, what could
+    /// go at the cursor now `typed` has been: any word character, or one
+    /// of those the server said should set it off. Nothing is asked while
+    /// an answer is awaited.
+    pub(crate) fn lsp_complete(&mut self, typed: char) {
+        let (command, path) = match self.lsp_document() {
+            Some(document) => document,
+            None => return,
+        };
+        let context = {
+            let client = match self.servers.get(&command) {
+                Some(client) => client,
+                None => return,
+            };
+            let provider = &client.capabilities["completionProvider"];
+            let triggers = provider["triggerCharacters"].as_array().map_or(&[][..], |t| &t[..]);
+            if provider.is_null() || client.waiting("textDocument/completion") {
+                return;
+            }
+            if is_word_char(typed) {
+                json!({"triggerKind": 1})
+            } else if triggers.iter().any(|t| t.as_str() == Some(&typed.to_string())) {
+                json!({"triggerKind": 2, "triggerCharacter": typed.to_string()})
+            } else {
+                return;
+            }
+        };
+        self.lsp_sync();
+        let mut params = self.lsp_position(&path);
+        params["context"] = context;
+        self.completion_asked = Some((path, self.word_before()));
+        let res = match self.servers.get(&command) {
+            Some(client) => client.request("textDocument/completion", params),
+            None => return,
+        };
+        if let Err(e) = res {
+            self.lsp_failed(&command, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn messages_round_trip() {
+        let first = json!({"jsonrpc": "2.0", "id": 1, "result": "größe ✓"});
+        let second = json!({"jsonrpc": "2.0", "method": "exit"});
+        let mut out = Vec::new();
+        write_message(&mut out, &first).unwrap();
+        write_message(&mut out, &second).unwrap();
+        let body = first.to_string();
+        assert!(out.starts_with(format!("Content-Length: {}\r\n\r\n", body.len()).as_bytes()));
+        assert!(body.len() > body.chars().count());
+
+        let mut reader = Cursor::new(out);
+        assert_eq!(read_message(&mut reader).unwrap(), Some(first));
+        assert_eq!(read_message(&mut reader).unwrap(), Some(second));
+        assert_eq!(read_message(&mut reader).unwrap(), None);
+    }
+
+    #[test]
+    fn other_headers_are_skipped() {
+        let input = "Content-Type: application/vscode-jsonrpc\r\nContent-Length: 2\r\n\r\n{}";
+        assert_eq!(read_message(&mut Cursor::new(input)).unwrap(), Some(json!({})));
+    }
+
+    #[test]
+    fn bad_frames_are_errors() {
+        let err = read_message(&mut Cursor::new("Content-Type: x\r\n\r\n{}")).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let err = read_message(&mut Cursor::new("Content-Length: 10\r\n\r\n{}")).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        let err = read_message(&mut Cursor::new("Content-Length: 2\r\n\r\n{]")).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
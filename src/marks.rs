@@ -0,0 +1,68 @@
+//! Named marks: `:mark a` remembers where the cursor is as `a` and
+//! `:gomark a` goes back there; `:marks` lists them. A mark shows as its
+//! name in the sign column and moves with its line as lines are put in or
+//! taken out above it, going away when the line itself is deleted. In vi
+//! mode `ma` sets one and `'a` or `` `a `` goes to it.
+
+use editor::Editor;
+use render::Color;
+use signs::Sign;
+
+/// The sign group holding mark `name`.
+fn group(name: char) -> String {
+    format!("mark {}", name)
+}
+
+impl Editor {
+    /// Puts mark `name` at the cursor, moving it if it was set elsewhere.
+    pub(crate) fn set_mark(&mut self, name: char) {
+        if !name.is_alphanumeric() {
+            self.set_status_msg(tr!(BadMark, name));
+            return;
+        }
+        let sign = Sign { group: group(name), glyph: name, color: Color::Magenta, priority: -1 };
+        self.signs.clear_group(&sign.group);
+        self.signs.place(self.cy, sign);
+        self.mark_columns.insert(name, self.cx);
+        self.set_status_msg(tr!(NamedMarkSet, name, self.cy + 1));
+    }
+
+    /// Where mark `name` is now, as (row, byte).
+    fn mark(&self, name: char) -> Option<(usize, usize)> {
+        let row = self.signs.find(&group(name))?;
+        Some((row, self.mark_columns.get(&name).cloned().unwrap_or(0)))
+    }
+
+    /// Goes to mark `name`, remembering where the cursor was as a jump.
+    pub(crate) fn go_to_mark(&mut self, name: char) {
+        match self.mark(name) {
+            Some(at) => {
+                let here = (self.cy, self.cx);
+                self.jump_from(here);
+                self.go_to_position(at);
+            }
+            None => self.set_status_msg(tr!(NoMark, name)),
+        }
+    }
+
+    /// Shows the marks set in the buffer and their lines.
+    pub(crate) fn list_marks(&mut self) {
+        let mut marks: Vec<(char, usize)> = self.mark_columns.keys()
+            .filter_map(|&name| self.mark(name).map(|(y, _)| (name, y)))
+            .collect();
+        if marks.is_empty() {
+            self.set_status_msg(tr!(NoMarks));
+            return;
+        }
+        marks.sort();
+        let list: Vec<String> = marks.iter().map(|&(name, y)| format!("{} {}", name, y + 1)).collect();
+        self.set_status_msg(tr!(Marks, list.join(", ")));
+    }
+
+    /// Forgets the buffer's marks, as when another file is read into it.
+    pub(crate) fn clear_marks(&mut self) {
+        for (name, _) in self.mark_columns.drain() {
+            self.signs.clear_group(&group(name));
+        }
+    }
+}
@@ -0,0 +1,542 @@
+//! User-facing text. Every message has an English original and may have
+//! translations; the language is picked from the environment at startup and
+//! anything without a translation falls back to English.
+
+use std::env;
+use std::fmt::Display;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Formats a message, substituting the arguments for its `{}` markers in
+/// order.
+macro_rules! tr {
+    ($msg:ident) => {
+        $crate::messages::fill($crate::messages::Msg::$msg, &[])
+    };
+    ($msg:ident, $($arg:expr),+) => {
+        $crate::messages::fill($crate::messages::Msg::$msg, &[$(&$arg),+])
+    };
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lang {
+    English,
+    German,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Msg {
+    Help,
+    Welcome,
+    NoName,
+    Scratch,
+    OnlyBuffer,
+    FollowOn,
+    FollowPaused,
+    FollowNoFile,
+    FollowUnsaved,
+    LockedBy,
+    SwapFound,
+    SwapFailed,
+    Recovered,
+    NoSwap,
+    SwapDropped,
+    LockStolen,
+    ReadOnly,
+    NoPathHere,
+    NoCommentSyntax,
+    Indentation,
+    IndentSpaces,
+    IndentTabs,
+    Stripped,
+    LineEndings,
+    BinaryFile,
+    NotInHex,
+    NotText,
+    NoCompletions,
+    CompletePath,
+    Calculate,
+    CalcFailed,
+    Normalized,
+    ConfigError,
+    NoFormatter,
+    FormatFailed,
+    NoLocalHistory,
+    LocalHistory,
+    HistoryEntry,
+    HistoryRestore,
+    HistoryDiff,
+    HistoryView,
+    LineCount,
+    ByteCount,
+    ColumnNumber,
+    Modified,
+    PromptHint,
+    PromptCompletions,
+    PickHint,
+    QuitUnsaved,
+    BytesWritten,
+    WrittenInPlace,
+    SaveAborted,
+    SaveFailed,
+    SaveAs,
+    SavedOnBlur,
+    Autosaved,
+    AutosaveFailed,
+    ChangedOnDisk,
+    ChangedSinceRead,
+    ChangedOverwrite,
+    ChangedReload,
+    ChangedDiff,
+    Reloaded,
+    ReloadedChanged,
+    Open,
+    OpenUnsaved,
+    CantOpen,
+    TooLarge,
+    OpenAnyway,
+    MemoryHigh,
+    Loading,
+    LoadFailed,
+    Search,
+    RegexSearch,
+    Replace,
+    ReplaceWith,
+    ReplaceConfirm,
+    Replaced,
+    MouseOn,
+    MouseOff,
+    LineCopied,
+    SelectionCopied,
+    RegisterEmpty,
+    ClipboardEmpty,
+    ClipboardFailed,
+    MarkSet,
+    KillRingEmpty,
+    MarkCleared,
+    ClipboardReadFailed,
+    ClipboardTooLarge,
+    ClipboardHistory,
+    SurroundWith,
+    NothingToSurround,
+    NoSurrounding,
+    DeleteSurrounding,
+    ChangeSurrounding,
+    ChangeTo,
+    UnknownCommand,
+    NoFiles,
+    GrepPrompt,
+    NoMatches,
+    GrepFound,
+    GrepTruncated,
+    ShellFailed,
+    ShellNoOutput,
+    FilterPrompt,
+    FilterFailed,
+    NotCommitted,
+    NotInGit,
+    BlameHint,
+    BlameFailed,
+    GitFailed,
+    NoHunk,
+    HunkStaged,
+    WordCompletion,
+    WordCompletionOriginal,
+    LspStartFailed,
+    LspFailed,
+    LspExited,
+    LspError,
+    NoLanguageServer,
+    NoDefinition,
+    NoReferences,
+    LocationsFound,
+    SnippetError,
+    NoOlderJump,
+    NoNewerJump,
+    NamedMarkSet,
+    NoMark,
+    BadMark,
+    Marks,
+    NoMarks,
+    FinderHint,
+    UnknownSetting,
+    UnsavedQuit,
+    UnknownFiletype,
+    CommandFailed,
+    BadArgument,
+    NothingToUndo,
+    NothingToRedo,
+    CdNoFile,
+    CdFailed,
+    Cwd,
+    RecoveryWritten,
+    RecoveryFailed,
+    SpokenLine,
+    SpokenPosition,
+    ScreenReaderOn,
+    ScreenReaderOff,
+    Usage,
+    TermAttrsFailed,
+    RawModeFailed,
+    ScreenFailed,
+    SignalFailed,
+    FocusFailed,
+    MouseFailed,
+    PasteFailed,
+    WindowSizeFailed,
+    DrawFailed,
+    InputFailed,
+}
+
+static LANG: AtomicUsize = AtomicUsize::new(0);
+
+/// Picks the language from `LC_ALL`, `LC_MESSAGES` or `LANG`, in that order.
+pub fn init() {
+    let locale = ["LC_ALL", "LC_MESSAGES", "LANG"].iter()
+        .filter_map(|var| env::var(var).ok())
+        .find(|value| !value.is_empty())
+        .unwrap_or_default();
+    let lang = match locale.split(['_', '.', '@']).next() {
+        Some("de") => Lang::German,
+        _ => Lang::English,
+    };
+    LANG.store(lang as usize, Ordering::Relaxed);
+}
+
+pub fn lang() -> Lang {
+    match LANG.load(Ordering::Relaxed) {
+        1 => Lang::German,
+        _ => Lang::English,
+    }
+}
+
+pub fn text(msg: Msg) -> &'static str {
+    let translated = match lang() {
+        Lang::English => None,
+        Lang::German => german(msg),
+    };
+    translated.unwrap_or_else(|| english(msg))
+}
+
+pub fn fill(msg: Msg, args: &[&dyn Display]) -> String {
+    let mut parts = text(msg).split("{}");
+    let mut res = parts.next().unwrap_or("").to_string();
+    for (i, part) in parts.enumerate() {
+        if let Some(arg) = args.get(i) {
+            res += &arg.to_string();
+        }
+        res += part;
+    }
+    res
+}
+
+fn english(msg: Msg) -> &'static str {
+    match msg {
+        Msg::Help => "HELP: Ctrl-S = save | Ctrl-Q = quit | Ctrl-F = find | Ctrl-T = toggle mouse | Ctrl-G = register",
+        Msg::Welcome => "Kilo editor -- version {}",
+        Msg::NoName => "[No Name]",
+        Msg::Scratch => "[Scratch]",
+        Msg::OnlyBuffer => "No other buffers",
+        Msg::FollowOn => "Following file; scroll up or edit to stop",
+        Msg::FollowPaused => "Follow paused",
+        Msg::FollowNoFile => "follow: no file opened",
+        Msg::FollowUnsaved => "follow: file has unsaved changes, save it first",
+        Msg::LockedBy => "WARNING!!! File is being edited by process {}; opened read-only, use :steal to edit anyway",
+        Msg::SwapFound => "WARNING!!! Unsaved changes from {} were left behind; use :recover to restore them or :dropswap to delete them",
+        Msg::SwapFailed => "Can't write swap file {}: {}",
+        Msg::Recovered => "Recovered unsaved changes from {}",
+        Msg::NoSwap => "No swap file for this buffer",
+        Msg::SwapDropped => "Deleted {}",
+        Msg::LockStolen => "Lock taken over; the file is editable",
+        Msg::ReadOnly => "Buffer is read-only",
+        Msg::NoPathHere => "No path before the cursor",
+        Msg::NoCommentSyntax => "No comment syntax known for this file",
+        Msg::Indentation => "Indenting with {}, tab stops every {} columns",
+        Msg::IndentSpaces => "{} spaces",
+        Msg::IndentTabs => "tabs",
+        Msg::Stripped => "Trailing whitespace removed from {} lines",
+        Msg::LineEndings => "Lines end in {} when saved",
+        Msg::BinaryFile => "Binary file, shown in hex; Tab switches to the ASCII column",
+        Msg::NotInHex => "Not available in the hex view",
+        Msg::NotText => "Not UTF-8 text; staying in hex",
+        Msg::NoCompletions => "No completions",
+        Msg::CompletePath => "Complete path",
+        Msg::Calculate => "Calculate",
+        Msg::CalcFailed => "calc: {}",
+        Msg::Normalized => "{} lines normalized to NFC",
+        Msg::ConfigError => "{}: {}",
+        Msg::NoFormatter => "No formatter configured; set formatter in .kilo.toml",
+        Msg::FormatFailed => "Formatter failed ({}): {}",
+        Msg::NoLocalHistory => "No saved versions of this file",
+        Msg::LocalHistory => "Local history",
+        Msg::HistoryEntry => "{}, {} bytes",
+        Msg::HistoryRestore => "Restore this version",
+        Msg::HistoryDiff => "Diff against the buffer",
+        Msg::HistoryView => "View this version",
+        Msg::LineCount => "{} lines",
+        Msg::ByteCount => "{} bytes",
+        Msg::ColumnNumber => "col {}",
+        Msg::Modified => "(modified)",
+        Msg::PromptHint => "{}: {} (ESC to cancel)",
+        Msg::PromptCompletions => "{}: {}   {}",
+        Msg::PickHint => "{} ({}/{}) Enter to choose, ESC to cancel",
+        Msg::QuitUnsaved => "WARNING!!! File has unsaved changes. Press {} {} more times to quit",
+        Msg::BytesWritten => "{} bytes written to disk",
+        Msg::WrittenInPlace => "{} bytes written over the file in place, not atomically: {}",
+        Msg::SaveAborted => "Save aborted",
+        Msg::SaveFailed => "Can't save! I/O error: {}",
+        Msg::SaveAs => "Save as",
+        Msg::SavedOnBlur => "Saved on focus loss",
+        Msg::Autosaved => "Autosaved to {}",
+        Msg::AutosaveFailed => "Autosave failed: {}",
+        Msg::ChangedOnDisk => "WARNING!!! File changed on disk since it was opened",
+        Msg::ChangedSinceRead => "File changed on disk since it was read",
+        Msg::ChangedOverwrite => "Overwrite it with the buffer",
+        Msg::ChangedReload => "Reload it, dropping the changes here",
+        Msg::ChangedDiff => "Diff it against the buffer",
+        Msg::Reloaded => "Reloaded {} from disk",
+        Msg::ReloadedChanged => "{} changed on disk; reloaded (Ctrl-Z to undo)",
+        Msg::Open => "Open",
+        Msg::OpenUnsaved => "File has unsaved changes, save it first",
+        Msg::CantOpen => "Can't open {}: {}",
+        Msg::TooLarge => "needs about {} MiB, over the {} MiB limit; view it with a pager such as less, or raise memory_limit in config.toml",
+        Msg::OpenAnyway => "{} needs about {} MiB, over the {} MiB limit. Open anyway? (y/N)",
+        Msg::MemoryHigh => "WARNING!!! Buffer uses about {} MiB of the {} MiB memory limit",
+        Msg::Loading => "(loading {}%)",
+        Msg::LoadFailed => "Stopped loading: {}; buffer is read-only",
+        Msg::Search => "Search",
+        Msg::RegexSearch => "Regex search",
+        Msg::Replace => "Replace",
+        Msg::ReplaceWith => "Replace {} with",
+        Msg::ReplaceConfirm => "Replace? (y)es (n)o (a)ll (q)uit",
+        Msg::Replaced => "{} replaced",
+        Msg::MouseOn => "Mouse capture on",
+        Msg::MouseOff => "Mouse capture off: terminal selection available",
+        Msg::LineCopied => "Line copied",
+        Msg::SelectionCopied => "Selection copied",
+        Msg::RegisterEmpty => "Register is empty",
+        Msg::ClipboardEmpty => "Clipboard is empty",
+        Msg::ClipboardFailed => "Could not set the system clipboard: {}",
+        Msg::MarkSet => "Mark set",
+        Msg::KillRingEmpty => "Nothing killed yet",
+        Msg::MarkCleared => "Mark cleared",
+        Msg::ClipboardReadFailed => "Could not read the system clipboard: {}",
+        Msg::ClipboardTooLarge => "Too large for the system clipboard; kept in kilo only",
+        Msg::ClipboardHistory => "Paste from clipboard history",
+        Msg::SurroundWith => "Surround with",
+        Msg::NothingToSurround => "Nothing to surround",
+        Msg::NoSurrounding => "No surrounding {}{}",
+        Msg::DeleteSurrounding => "Delete surrounding",
+        Msg::ChangeSurrounding => "Change surrounding",
+        Msg::ChangeTo => "Change {} to",
+        Msg::UnknownCommand => "Unknown command: {}",
+        Msg::NoFiles => "No files here",
+        Msg::GrepPrompt => "Search files for (regex)",
+        Msg::NoMatches => "No matches for {}",
+        Msg::GrepFound => "{} matches in {} files; Enter opens one",
+        Msg::GrepTruncated => "Only the first {} matches are listed; Enter opens one",
+        Msg::ShellFailed => "{}: {}",
+        Msg::ShellNoOutput => "{}: no output",
+        Msg::FilterPrompt => "Filter through",
+        Msg::FilterFailed => "Filter failed ({}): {}",
+        Msg::NotCommitted => "Uncommitted",
+        Msg::NotInGit => "Only files are known to git",
+        Msg::BlameHint => "ESC or :blame hides who changed each line",
+        Msg::BlameFailed => "Can't blame: {}",
+        Msg::GitFailed => "git: {}",
+        Msg::NoHunk => "No change from {} here",
+        Msg::HunkStaged => "Staged a change of {} lines",
+        Msg::WordCompletion => "Completion {} of {}",
+        Msg::WordCompletionOriginal => "Back to what was typed",
+        Msg::LspStartFailed => "Couldn't start language server {}",
+        Msg::LspFailed => "Language server {} stopped answering: {}",
+        Msg::LspExited => "Language server {} exited",
+        Msg::LspError => "{}: {}",
+        Msg::NoLanguageServer => "No language server for this buffer",
+        Msg::NoDefinition => "No definition found",
+        Msg::NoReferences => "No references found",
+        Msg::LocationsFound => "{} places; Enter opens one",
+        Msg::SnippetError => "Snippets not read: {}",
+        Msg::NoOlderJump => "No earlier place to jump back to",
+        Msg::NoNewerJump => "No later place to jump forward to",
+        Msg::NamedMarkSet => "Mark {} set on line {}",
+        Msg::NoMark => "Mark {} not set",
+        Msg::BadMark => "Not a mark name: {}",
+        Msg::Marks => "Marks: {}",
+        Msg::NoMarks => "No marks set",
+        Msg::FinderHint => "Find file: {} ({} of {}, ESC to cancel)",
+        Msg::UnknownSetting => "Unknown setting: {}",
+        Msg::UnsavedQuit => "Unsaved changes; :q! quits without saving",
+        Msg::UnknownFiletype => "Unknown filetype: {}",
+        Msg::CommandFailed => "Command failed: {}",
+        Msg::BadArgument => "{}: invalid argument: {}",
+        Msg::NothingToUndo => "Nothing to undo",
+        Msg::NothingToRedo => "Nothing to redo",
+        Msg::CdNoFile => "cd: no file opened yet",
+        Msg::CdFailed => "cd: {}: {}",
+        Msg::Cwd => "cwd: {}",
+        Msg::RecoveryWritten => "unsaved changes written to {}",
+        Msg::RecoveryFailed => "failed to write {}: {}",
+        Msg::SpokenLine => "line {}: {}",
+        Msg::SpokenPosition => "line {}, column {}: {}",
+        Msg::ScreenReaderOn => "Screen reader mode on",
+        Msg::ScreenReaderOff => "Screen reader mode off",
+        Msg::Usage => "usage: kilo [--vim] [+line] [file[:line[:col]]...]",
+        Msg::TermAttrsFailed => "failed to get terminal attributes: {}",
+        Msg::RawModeFailed => "failed to enter raw mode: {}",
+        Msg::ScreenFailed => "failed to set up the screen: {}",
+        Msg::SignalFailed => "failed to install signal handler: {}",
+        Msg::FocusFailed => "failed to enable focus reporting: {}",
+        Msg::MouseFailed => "failed to enable mouse reporting: {}",
+        Msg::PasteFailed => "failed to enable bracketed paste: {}",
+        Msg::WindowSizeFailed => "failed to get window size: {}",
+        Msg::DrawFailed => "failed to draw screen: {}",
+        Msg::InputFailed => "failed to read input: {}",
+    }
+}
+
+fn german(msg: Msg) -> Option<&'static str> {
+    Some(match msg {
+        Msg::Help => "HILFE: Strg-S = Speichern | Strg-Q = Beenden | Strg-F = Suchen | Strg-T = Maus umschalten | Strg-G = Register",
+        Msg::Welcome => "Kilo-Editor -- Version {}",
+        Msg::NoName => "[Unbenannt]",
+        Msg::Scratch => "[Notizen]",
+        Msg::OnlyBuffer => "Keine weiteren Puffer",
+        Msg::FollowOn => "Datei wird verfolgt; hochscrollen oder bearbeiten zum Anhalten",
+        Msg::FollowPaused => "Verfolgen angehalten",
+        Msg::FollowNoFile => "follow: keine Datei geöffnet",
+        Msg::FollowUnsaved => "follow: ungespeicherte Änderungen, bitte zuerst speichern",
+        Msg::LockedBy => "WARNUNG!!! Datei wird von Prozess {} bearbeitet; schreibgeschützt geöffnet, mit :steal trotzdem bearbeiten",
+        Msg::SwapFound => "WARNUNG!!! Ungespeicherte Änderungen von {} gefunden; mit :recover wiederherstellen oder mit :dropswap löschen",
+        Msg::SwapFailed => "Kann Auslagerungsdatei {} nicht schreiben: {}",
+        Msg::Recovered => "Ungespeicherte Änderungen aus {} wiederhergestellt",
+        Msg::NoSwap => "Keine Auslagerungsdatei für diesen Puffer",
+        Msg::SwapDropped => "{} gelöscht",
+        Msg::LockStolen => "Sperre übernommen; die Datei ist bearbeitbar",
+        Msg::ReadOnly => "Puffer ist schreibgeschützt",
+        Msg::NoPathHere => "Kein Pfad vor dem Cursor",
+        Msg::NoCommentSyntax => "Keine Kommentarsyntax für diese Datei bekannt",
+        Msg::Indentation => "Einrückung mit {}, Tabstopps alle {} Spalten",
+        Msg::IndentSpaces => "{} Leerzeichen",
+        Msg::IndentTabs => "Tabs",
+        Msg::Stripped => "Leerraum am Zeilenende aus {} Zeilen entfernt",
+        Msg::LineEndings => "Zeilen enden beim Speichern mit {}",
+        Msg::BinaryFile => "Binärdatei, in Hex angezeigt; Tab wechselt zur ASCII-Spalte",
+        Msg::NotInHex => "In der Hex-Ansicht nicht verfügbar",
+        Msg::NotText => "Kein UTF-8-Text; bleibt in Hex",
+        Msg::NoCompletions => "Keine Vervollständigungen",
+        Msg::CompletePath => "Pfad vervollständigen",
+        Msg::Calculate => "Berechnen",
+        Msg::Normalized => "{} Zeilen nach NFC normalisiert",
+        Msg::NoFormatter => "Kein Formatierer eingestellt; formatter in .kilo.toml setzen",
+        Msg::FormatFailed => "Formatierer fehlgeschlagen ({}): {}",
+        Msg::NoLocalHistory => "Keine gespeicherten Versionen dieser Datei",
+        Msg::LocalHistory => "Lokaler Verlauf",
+        Msg::HistoryEntry => "{}, {} Bytes",
+        Msg::HistoryRestore => "Diese Version wiederherstellen",
+        Msg::HistoryDiff => "Mit dem Puffer vergleichen",
+        Msg::HistoryView => "Diese Version ansehen",
+        Msg::LineCount => "{} Zeilen",
+        Msg::ByteCount => "{} Bytes",
+        Msg::ColumnNumber => "Sp. {}",
+        Msg::Modified => "(geändert)",
+        Msg::PromptHint => "{}: {} (ESC zum Abbrechen)",
+        Msg::PromptCompletions => "{}: {}   {}",
+        Msg::PickHint => "{} ({}/{}) Enter zum Auswählen, ESC zum Abbrechen",
+        Msg::QuitUnsaved => "WARNUNG!!! Ungespeicherte Änderungen. {} noch {}-mal drücken zum Beenden",
+        Msg::BytesWritten => "{} Bytes gespeichert",
+        Msg::WrittenInPlace => "{} Bytes direkt in die Datei geschrieben, nicht atomar: {}",
+        Msg::SaveAborted => "Speichern abgebrochen",
+        Msg::SaveFailed => "Speichern fehlgeschlagen! E/A-Fehler: {}",
+        Msg::SaveAs => "Speichern unter",
+        Msg::SavedOnBlur => "Beim Fokusverlust gespeichert",
+        Msg::Autosaved => "Automatisch gespeichert in {}",
+        Msg::AutosaveFailed => "Automatisches Speichern fehlgeschlagen: {}",
+        Msg::ChangedOnDisk => "WARNUNG!!! Datei wurde seit dem Öffnen auf der Platte geändert",
+        Msg::ChangedSinceRead => "Datei wurde seit dem Lesen auf der Platte geändert",
+        Msg::ChangedOverwrite => "Mit dem Puffer überschreiben",
+        Msg::ChangedReload => "Neu laden, Änderungen hier verwerfen",
+        Msg::ChangedDiff => "Mit dem Puffer vergleichen",
+        Msg::Reloaded => "{} neu von der Platte geladen",
+        Msg::ReloadedChanged => "{} wurde auf der Platte geändert und neu geladen (Strg-Z macht es rückgängig)",
+        Msg::Open => "Öffnen",
+        Msg::OpenUnsaved => "Ungespeicherte Änderungen, bitte zuerst speichern",
+        Msg::CantOpen => "Kann {} nicht öffnen: {}",
+        Msg::TooLarge => "braucht etwa {} MiB, mehr als das Limit von {} MiB; mit einem Pager wie less ansehen oder memory_limit in config.toml erhöhen",
+        Msg::OpenAnyway => "{} braucht etwa {} MiB, mehr als das Limit von {} MiB. Trotzdem öffnen? (j/N)",
+        Msg::MemoryHigh => "WARNUNG!!! Puffer belegt etwa {} MiB des Speicherlimits von {} MiB",
+        Msg::Loading => "(lädt {}%)",
+        Msg::LoadFailed => "Laden abgebrochen: {}; Puffer ist schreibgeschützt",
+        Msg::Search => "Suchen",
+        Msg::RegexSearch => "Regex-Suche",
+        Msg::Replace => "Ersetzen",
+        Msg::ReplaceWith => "{} ersetzen durch",
+        Msg::Replaced => "{} ersetzt",
+        Msg::MouseOn => "Mauserfassung an",
+        Msg::MouseOff => "Mauserfassung aus: Terminal-Auswahl verfügbar",
+        Msg::LineCopied => "Zeile kopiert",
+        Msg::SelectionCopied => "Auswahl kopiert",
+        Msg::RegisterEmpty => "Register ist leer",
+        Msg::ClipboardEmpty => "Zwischenablage ist leer",
+        Msg::ClipboardTooLarge => "Zu groß für die System-Zwischenablage; nur in kilo behalten",
+        Msg::ClipboardHistory => "Aus dem Verlauf der Zwischenablage einfügen",
+        Msg::SurroundWith => "Umschließen mit",
+        Msg::NothingToSurround => "Nichts zum Umschließen",
+        Msg::NoSurrounding => "Kein umschließendes {}{}",
+        Msg::DeleteSurrounding => "Umschließendes löschen",
+        Msg::ChangeSurrounding => "Umschließendes ändern",
+        Msg::ChangeTo => "{} ändern in",
+        Msg::UnknownCommand => "Unbekannter Befehl: {}",
+        Msg::NoFiles => "Keine Dateien hier",
+        Msg::GrepPrompt => "In Dateien suchen (Regex)",
+        Msg::NoMatches => "Keine Treffer für {}",
+        Msg::GrepFound => "{} Treffer in {} Dateien; Enter öffnet einen",
+        Msg::GrepTruncated => "Nur die ersten {} Treffer sind aufgeführt; Enter öffnet einen",
+        Msg::ShellFailed => "{}: {}",
+        Msg::ShellNoOutput => "{}: keine Ausgabe",
+        Msg::FilterPrompt => "Filtern durch",
+        Msg::FilterFailed => "Filter fehlgeschlagen ({}): {}",
+        Msg::NotCommitted => "Ausstehend",
+        Msg::NotInGit => "Nur Dateien sind git bekannt",
+        Msg::BlameHint => "ESC oder :blame blendet die Autoren der Zeilen aus",
+        Msg::BlameFailed => "Blame nicht möglich: {}",
+        Msg::GitFailed => "git: {}",
+        Msg::NoHunk => "Hier keine Änderung gegenüber {}",
+        Msg::HunkStaged => "Änderung von {} Zeilen vorgemerkt",
+        Msg::WordCompletion => "Vervollständigung {} von {}",
+        Msg::WordCompletionOriginal => "Zurück zum Getippten",
+        Msg::LspStartFailed => "Sprachserver {} konnte nicht gestartet werden",
+        Msg::LspFailed => "Sprachserver {} antwortet nicht mehr: {}",
+        Msg::LspExited => "Sprachserver {} wurde beendet",
+        Msg::LspError => "{}: {}",
+        Msg::NoLanguageServer => "Kein Sprachserver für diesen Puffer",
+        Msg::NoDefinition => "Keine Definition gefunden",
+        Msg::NoReferences => "Keine Verweise gefunden",
+        Msg::LocationsFound => "{} Stellen; Enter öffnet eine",
+        Msg::SnippetError => "Snippets nicht gelesen: {}",
+        Msg::NoOlderJump => "Keine frühere Stelle zum Zurückspringen",
+        Msg::NoNewerJump => "Keine spätere Stelle zum Vorspringen",
+        Msg::NamedMarkSet => "Marke {} in Zeile {} gesetzt",
+        Msg::NoMark => "Marke {} nicht gesetzt",
+        Msg::BadMark => "Kein Markenname: {}",
+        Msg::Marks => "Marken: {}",
+        Msg::NoMarks => "Keine Marken gesetzt",
+        Msg::FinderHint => "Datei suchen: {} ({} von {}, ESC zum Abbrechen)",
+        Msg::UnknownSetting => "Unbekannte Einstellung: {}",
+        Msg::UnsavedQuit => "Ungespeicherte Änderungen; :q! beendet ohne zu speichern",
+        Msg::UnknownFiletype => "Unbekannter Dateityp: {}",
+        Msg::CommandFailed => "Befehl fehlgeschlagen: {}",
+        Msg::BadArgument => "{}: ungültiges Argument: {}",
+        Msg::NothingToUndo => "Nichts rückgängig zu machen",
+        Msg::NothingToRedo => "Nichts wiederherzustellen",
+        Msg::CdNoFile => "cd: noch keine Datei geöffnet",
+        Msg::Cwd => "Arbeitsverzeichnis: {}",
+        Msg::RecoveryWritten => "ungespeicherte Änderungen nach {} geschrieben",
+        Msg::RecoveryFailed => "konnte {} nicht schreiben: {}",
+        Msg::SpokenLine => "Zeile {}: {}",
+        Msg::SpokenPosition => "Zeile {}, Spalte {}: {}",
+        Msg::ScreenReaderOn => "Bildschirmleser-Modus an",
+        Msg::ScreenReaderOff => "Bildschirmleser-Modus aus",
+        Msg::Usage => "Aufruf: kilo [--vim] [+Zeile] [Datei[:Zeile[:Spalte]]...]",
+        _ => return None,
+    })
+}
@@ -0,0 +1,989 @@
+//! Drawing the screen: the text with its gutter and colors, the tabs, the
+//! status and message bars, and scrolling the view to the cursor.
+
+use std::env;
+use std::io::Result;
+use std::iter;
+use std::ops::Range;
+use std::time::{Duration, Instant};
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use syntax::Hl;
+use bidi;
+use buffer::display_name;
+use signs::SignColumn;
+use status::{self, Segment};
+use editor::Editor;
+
+/// Completions shown at once.
+const MENU_HEIGHT: usize = 10;
+
+pub(crate) trait Render {
+    fn render(&self, tab_stop: usize) -> Self;
+}
+
+impl Render for String {
+    fn render(&self, tab_stop: usize) -> String {
+        let mut res = "".to_string();
+        let mut col = 0;
+
+        for ch in self.chars() {
+            if ch == '\t' {
+                res.push(' ');
+                col += 1;
+                while col % tab_stop != 0 { res.push(' '); col += 1; };
+            } else {
+                res.push(ch);
+                col += ch.width().unwrap_or(0);
+            }
+        }
+        res
+    }
+}
+
+/// For each character of `row` rendered, what to show in its place when
+/// whitespace is made visible: `→` where a tab starts, `·` for a space.
+/// The rest of a tab stays blank.
+fn whitespace_marks(row: &str, tab_stop: usize) -> Vec<Option<char>> {
+    let mut marks = Vec::new();
+    let mut col = 0;
+    for ch in row.chars() {
+        match ch {
+            '\t' => {
+                marks.push(Some('→'));
+                col += 1;
+                while col % tab_stop != 0 {
+                    marks.push(None);
+                    col += 1;
+                }
+            }
+            ' ' => {
+                marks.push(Some('·'));
+                col += 1;
+            }
+            _ => {
+                marks.push(None);
+                col += ch.width().unwrap_or(0);
+            }
+        }
+    }
+    marks
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ColorSupport {
+    None,
+    Basic,
+    Ansi256,
+    TrueColor,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+    Indexed(u8),
+    Rgb(u8, u8, u8),
+}
+
+impl Color {
+    fn to_rgb(self) -> (u8, u8, u8) {
+        const BASIC: [(u8, u8, u8); 8] = [
+            (0, 0, 0), (205, 0, 0), (0, 205, 0), (205, 205, 0),
+            (0, 0, 238), (205, 0, 205), (0, 205, 205), (229, 229, 229),
+        ];
+        match self {
+            Color::Rgb(r, g, b) => (r, g, b),
+            Color::Indexed(i) if i < 16 => BASIC[i as usize % 8],
+            Color::Indexed(i) if i < 232 => {
+                let level = |v: u8| if v == 0 { 0 } else { 55 + v * 40 };
+                let i = i - 16;
+                (level(i / 36), level(i / 6 % 6), level(i % 6))
+            }
+            Color::Indexed(i) => {
+                let v = 8 + (i - 232) * 10;
+                (v, v, v)
+            }
+            basic => BASIC[basic.basic_index().unwrap() as usize],
+        }
+    }
+
+    fn basic_index(self) -> Option<u8> {
+        match self {
+            Color::Black => Some(0),
+            Color::Red => Some(1),
+            Color::Green => Some(2),
+            Color::Yellow => Some(3),
+            Color::Blue => Some(4),
+            Color::Magenta => Some(5),
+            Color::Cyan => Some(6),
+            Color::White => Some(7),
+            Color::Indexed(i) if i < 8 => Some(i),
+            _ => None,
+        }
+    }
+
+    /// Nearest of the eight basic colors.
+    fn to_basic(self) -> u8 {
+        if let Some(i) = self.basic_index() {
+            return i;
+        }
+        let (r, g, b) = self.to_rgb();
+        let (r, g, b) = (r as i32, g as i32, b as i32);
+        (0..8u8)
+            .min_by_key(|&i| {
+                let (cr, cg, cb) = Color::Indexed(i).to_rgb();
+                let (dr, dg, db) = (r - cr as i32, g - cg as i32, b - cb as i32);
+                dr * dr + dg * dg + db * db
+            })
+            .unwrap()
+    }
+
+    /// Nearest entry of the xterm 256 color palette.
+    fn to_indexed(self) -> u8 {
+        match self {
+            Color::Indexed(i) => i,
+            Color::Rgb(r, g, b) => {
+                let cube = |v: u8| if v < 48 { 0 } else if v < 115 { 1 } else { (v - 35) / 40 };
+                16 + 36 * cube(r) + 6 * cube(g) + cube(b)
+            }
+            basic => basic.basic_index().unwrap(),
+        }
+    }
+}
+
+/// What the terminal can be asked to render. Every color and attribute
+/// escape goes through here so that styling degrades instead of leaking
+/// escape sequences onto terminals that can't show them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    pub color: ColorSupport,
+    pub attributes: bool,
+}
+
+impl Capabilities {
+    /// Detects support from `TERM`, `COLORTERM` and `NO_COLOR`.
+    pub fn detect() -> Capabilities {
+        let term = env::var("TERM").unwrap_or_default();
+        if term.is_empty() || term == "dumb" {
+            return Capabilities { color: ColorSupport::None, attributes: false };
+        }
+
+        let colorterm = env::var("COLORTERM").unwrap_or_default();
+        let color = if env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+            ColorSupport::None
+        } else if colorterm == "truecolor" || colorterm == "24bit" {
+            ColorSupport::TrueColor
+        } else if term.contains("256color") {
+            ColorSupport::Ansi256
+        } else {
+            ColorSupport::Basic
+        };
+        Capabilities { color, attributes: true }
+    }
+
+    fn color(&self, color: Color, base: u8) -> String {
+        match self.color {
+            ColorSupport::None => "".to_string(),
+            ColorSupport::Basic => format!("\x1b[{}m", base + color.to_basic()),
+            ColorSupport::Ansi256 => format!("\x1b[{};5;{}m", base + 8, color.to_indexed()),
+            ColorSupport::TrueColor => match color {
+                Color::Rgb(r, g, b) => format!("\x1b[{};2;{};{};{}m", base + 8, r, g, b),
+                _ => format!("\x1b[{};5;{}m", base + 8, color.to_indexed()),
+            },
+        }
+    }
+
+    pub fn fg(&self, color: Color) -> String {
+        self.color(color, 30)
+    }
+
+    pub fn bg(&self, color: Color) -> String {
+        self.color(color, 40)
+    }
+
+    pub fn reverse(&self) -> &'static str {
+        if self.attributes { "\x1b[7m" } else { "" }
+    }
+
+    pub fn underline(&self) -> &'static str {
+        if self.attributes { "\x1b[4m" } else { "" }
+    }
+
+    pub fn reset(&self) -> &'static str {
+        if self.attributes { "\x1b[m" } else { "" }
+    }
+}
+
+fn highlight_color(hl: Hl) -> Option<Color> {
+    match hl {
+        Hl::Normal => None,
+        Hl::Comment | Hl::MlComment => Some(Color::Cyan),
+        Hl::Keyword => Some(Color::Yellow),
+        Hl::Type => Some(Color::Green),
+        Hl::String => Some(Color::Magenta),
+        Hl::Number => Some(Color::Red),
+        #[cfg(feature = "syntect")]
+        Hl::Rgb(r, g, b) => Some(Color::Rgb(r, g, b)),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineNumbers {
+    Off,
+    Absolute,
+    /// Distance from the cursor line, which shows its own number.
+    Relative,
+}
+
+/// Where the time of one frame went, for the profiling HUD.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct FrameStats {
+    pub(crate) decode: Duration,
+    pub(crate) update: Duration,
+    render: Duration,
+    flush: Duration,
+    pub(crate) bytes: usize,
+}
+
+/// Screen columns taken by `s`, which holds no tabs. East Asian wide
+/// characters and emoji take two.
+pub(crate) fn width(s: &str) -> usize {
+    UnicodeWidthStr::width(s)
+}
+
+impl Editor {
+    /// Sizes the text area to what the bars leave of the screen: two rows
+    /// go to the status and message bars, and one to the tabs when shown.
+    pub(crate) fn layout(&mut self) {
+        let bars = if self.tab_bar_shown() { 3 } else { 2 };
+        self.numrows = self.screenrows.saturating_sub(bars).max(1);
+    }
+
+    pub(crate) fn tab_bar_shown(&self) -> bool {
+        self.tab_bar && !self.buffers.is_empty()
+    }
+
+    /// The screen row the text starts on, counting from 0.
+    pub(crate) fn text_top(&self) -> usize {
+        if self.tab_bar_shown() { 1 } else { 0 }
+    }
+
+    pub fn refresh_screen(&mut self) -> Result<()> {
+        self.layout();
+        if self.accessible {
+            self.scroll();
+            return self.announce();
+        }
+        let start = Instant::now();
+        self.frame_flush = Duration::from_secs(0);
+        self.frame_bytes = 0;
+
+        self.scroll();
+        // The whole frame goes out in one write, so a slow link never shows
+        // half of one.
+        let mut frame = "\x1b[?25l".to_string();
+        let mut lines = Vec::with_capacity(self.screenrows);
+        if self.tab_bar_shown() {
+            lines.push(self.draw_tab_bar());
+        }
+        let rowoff = self.rowoff;
+        if let Some(shown) = self.shown_rowoff {
+            self.rowoff = shown;
+        }
+        lines.extend(self.draw_rows());
+        self.rowoff = rowoff;
+        lines.push(self.draw_status_bar());
+        lines.push(self.draw_message_bar());
+        frame += &self.draw_changed(lines);
+        if self.completion_menu.is_some() && self.hex.is_none() {
+            frame += &self.draw_completion_menu();
+        }
+        if self.show_hud {
+            frame += &self.draw_hud();
+        }
+        let (y, x) = match self.hex {
+            Some(ref hex) => (hex.cursor_line() - self.rowoff, hex.cursor_column().min(self.numcols - 1)),
+            None => self.cursor_position(),
+        };
+        frame += &format!("\x1b[{};{}H", y + self.text_top() + 1, x + 1);
+        // Mid-animation the cursor may be off screen; show it once the view
+        // has caught up.
+        if self.shown_rowoff.is_none() {
+            frame += "\x1b[?25h";
+        }
+        self.write(frame)?;
+
+        self.stats.flush = self.frame_flush;
+        self.stats.render = start.elapsed() - self.frame_flush;
+        self.stats.bytes = self.frame_bytes;
+        Ok(())
+    }
+
+    /// The screen lines that differ from the last frame, each after moving
+    /// to the start of its row.
+    fn draw_changed(&mut self, lines: Vec<String>) -> String {
+        let mut s = String::new();
+        for (y, line) in lines.iter().enumerate() {
+            if self.drawn.get(y) != Some(line) {
+                s += &format!("\x1b[{};1H{}", y + 1, line);
+            }
+        }
+        self.drawn = lines;
+        s
+    }
+
+    /// Notes that screen lines `lines` were drawn over outside a frame, so
+    /// the next one draws them again.
+    pub(crate) fn overdrawn(&mut self, lines: Range<usize>) {
+        for y in lines {
+            if let Some(line) = self.drawn.get_mut(y) {
+                line.clear();
+            }
+        }
+    }
+
+    /// Clears the screen and has the next frame draw all of it.
+    pub(crate) fn redraw(&mut self) -> Result<()> {
+        self.drawn.clear();
+        self.write("\x1b[2J")
+    }
+
+    /// Screen reader output: rather than repainting, print the current line
+    /// when the cursor moves to another one or its text changes, and new
+    /// status messages, each on a line of its own.
+    fn announce(&mut self) -> Result<()> {
+        let mut s = String::new();
+        let line = (self.cy, self.rows.get(self.cy).cloned().unwrap_or_default());
+        if self.spoken_line.as_ref() != Some(&line) {
+            s += &tr!(SpokenLine, line.0 + 1, line.1);
+            s += "\r\n";
+            self.spoken_line = Some(line);
+        }
+        if self.status_msg != self.spoken_status {
+            if !self.status_msg.is_empty() {
+                s += &self.status_msg;
+                s += "\r\n";
+            }
+            self.spoken_status = self.status_msg.clone();
+        }
+        self.write(s)
+    }
+
+    pub(crate) fn speak_position(&mut self) -> Result<()> {
+        let line = self.rows.get(self.cy).cloned().unwrap_or_default();
+        let s = tr!(SpokenPosition, self.cy + 1, self.cx + 1, line) + "\r\n";
+        self.write(s)
+    }
+
+    pub(crate) fn toggle_accessible(&mut self) -> Result<()> {
+        self.accessible = !self.accessible;
+        self.spoken_line = None;
+        self.spoken_status.clear();
+        self.drawn.clear();
+        if self.accessible {
+            self.write("\x1b[2J\x1b[H")?;
+            self.set_status_msg(tr!(ScreenReaderOn));
+        } else {
+            self.set_status_msg(tr!(ScreenReaderOff));
+        }
+        Ok(())
+    }
+
+    /// Where the cursor goes on the screen, below the tab bar: on
+    /// right-to-left lines that's where its character is drawn, not where
+    /// it is in the text.
+    fn cursor_position(&self) -> (usize, usize) {
+        let row = self.rows.get(self.cy).map(|row| row.render(self.tab_stop)).unwrap_or_default();
+        let rx = bidi::visual(&row).map_or(self.rx, |order| bidi::display_column(&row, &order, self.rx));
+        let lines = self.screen_lines();
+        let (y, x) = lines.iter()
+            .rposition(|&(row, start, _)| row == self.cy && start <= rx)
+            .map_or((self.cy.saturating_sub(self.rowoff), 0), |y| (y, rx - lines[y].1));
+        (y, x.min(self.text_cols().saturating_sub(1)) + self.gutter_width())
+    }
+
+    /// The completion menu, drawn over the text under the word being
+    /// completed, or above it when there's more room there.
+    fn draw_completion_menu(&mut self) -> String {
+        let (y, x) = self.cursor_position();
+        let menu = match self.completion_menu {
+            Some(ref menu) => menu,
+            None => return String::new(),
+        };
+        let typed = self.rows[self.cy][menu.start.1..self.cx].chars().count();
+        let x = x.saturating_sub(typed);
+        let (below, above) = (self.numrows - y - 1, y);
+        let height = menu.shown.len().min(MENU_HEIGHT).min(below.max(above));
+        if height == 0 {
+            return String::new();
+        }
+        let top = if below >= height { y + 1 } else { y - height };
+        let first = (menu.selected + 1).saturating_sub(height);
+        let items: Vec<_> = menu.shown().skip(first).take(height).collect();
+        let label_width = items.iter().map(|item| item.label.chars().count()).max().unwrap_or(0);
+        let kind_width = items.iter().map(|item| item.kind.len()).max().unwrap_or(0);
+        let lines: Vec<String> = items.iter().map(|item| {
+            format!(" {:3$}  {:4$}  {} ", item.label, item.kind, item.detail, label_width, kind_width)
+        }).collect();
+        let width = lines.iter().map(|line| line.chars().count()).max().unwrap_or(0).min(self.numcols);
+        let x = x.min(self.numcols - width);
+        let mut s = String::new();
+        for (i, line) in lines.iter().enumerate() {
+            let line: String = line.chars().chain(iter::repeat(' ')).take(width).collect();
+            let style = if first + i == menu.selected { self.caps.reverse().to_string() } else { self.caps.bg(Color::Blue) };
+            s += &format!("\x1b[{};{}H{}{}{}", top + i + self.text_top() + 1, x + 1, style, line, self.caps.reset());
+        }
+        let top = top + self.text_top();
+        self.overdrawn(top..top + height);
+        s
+    }
+
+    /// Timings of the previous frame, drawn over the top right corner.
+    fn draw_hud(&mut self) -> String {
+        let ms = |d: Duration| d.as_secs() as f64 * 1e3 + d.subsec_nanos() as f64 / 1e6;
+        let hud = format!(
+            " key {:.3}ms | update {:.3}ms | render {:.3}ms | write {:.3}ms | {} bytes | mem {}K ",
+            ms(self.stats.decode), ms(self.stats.update),
+            ms(self.stats.render), ms(self.stats.flush), self.stats.bytes,
+            self.memory_usage() / 1024);
+        let hud: String = hud.chars().take(self.numcols).collect();
+        let col = self.numcols - hud.len() + 1;
+        self.overdrawn(0..1);
+        format!("\x1b[1;{}H{}{}{}", col, self.caps.reverse(), hud, self.caps.reset())
+    }
+
+    /// The text area, one string per screen row.
+    fn draw_rows(&mut self) -> Vec<String> {
+        if let Some(ref hex) = self.hex {
+            let lines = self.rowoff..(self.rowoff + self.numrows).min(hex.lines());
+            let numcols = self.numcols;
+            let mut lines: Vec<String> = lines
+                .map(|line| hex.line(line).chars().take(numcols).collect::<String>() + "\x1b[K")
+                .collect();
+            lines.resize(self.numrows, "~\x1b[K".to_string());
+            return lines;
+        }
+        let rows = &self.rows;
+        let tab_stop = self.tab_stop;
+        self.highlighter.update(rows.len(), self.rowoff + self.numrows, |i| rows[i].render(tab_stop));
+        let mut lines = Vec::with_capacity(self.numrows);
+        for (y, &(fileoff, start, end)) in self.screen_lines().iter().enumerate() {
+            let mut s = String::new();
+            if self.wrap && start > 0 {
+                s += &" ".repeat(self.gutter_width());
+            } else {
+                s += &self.draw_gutter(fileoff);
+            }
+            if fileoff >= self.rows.len() {
+                if self.rows.is_empty() && y == self.numrows / 3 {
+                    let welcome = tr!(Welcome, env!("CARGO_PKG_VERSION"));
+                    let mut padding = self.text_cols().saturating_sub(welcome.len()) / 2;
+                    if padding > 0 {
+                        s += "~";
+                        padding -= 1;
+                    }
+                    for _ in 0..padding {
+                        s += " ";
+                    }
+                    s += welcome.as_str();
+                } else {
+                    s += "~";
+                }
+            } else {
+                let row = self.rows[fileoff].render(self.tab_stop);
+                s += &self.draw_line(fileoff, &row, start, end);
+            }
+            s += "\x1b[K";
+            lines.push(s);
+        }
+        lines
+    }
+
+    /// What each screen row shows: a buffer row, and the columns of it from
+    /// where the screen row starts to where the next one does. Without
+    /// wrapping that's one screen row per buffer row, scrolled by `coloff`.
+    pub(crate) fn screen_lines(&self) -> Vec<(usize, usize, usize)> {
+        let mut lines = Vec::with_capacity(self.numrows);
+        let mut row = self.rowoff;
+        let mut skip = self.wrapoff;
+        while lines.len() < self.numrows {
+            if !self.wrap || row >= self.rows.len() {
+                lines.push((row, self.coloff, self.coloff + self.text_cols()));
+            } else {
+                let starts = self.wrap_points(row);
+                for (i, &start) in starts.iter().enumerate().skip(skip) {
+                    lines.push((row, start, starts.get(i + 1).cloned().unwrap_or(usize::MAX)));
+                }
+            }
+            row += 1;
+            skip = 0;
+        }
+        lines.truncate(self.numrows);
+        lines
+    }
+
+    /// The columns where each screen row of `row` starts when wrapped. A
+    /// wide character that doesn't fit at the end goes to the next one.
+    pub(crate) fn wrap_points(&self, row: usize) -> Vec<usize> {
+        let cols = self.text_cols().max(2);
+        let mut starts = vec![0];
+        let render = match self.rows.get(row) {
+            Some(row) => row.render(self.tab_stop),
+            None => return starts,
+        };
+        let (mut col, mut start) = (0, 0);
+        for g in render.graphemes(true) {
+            let w = width(g);
+            if col + w - start > cols {
+                starts.push(col);
+                start = col;
+            }
+            col += w;
+        }
+        starts
+    }
+
+    /// Which of the screen rows of `row` column `rx` is on, and where that
+    /// screen row starts.
+    pub(crate) fn wrap_segment(&self, row: usize, rx: usize) -> (usize, usize, Vec<usize>) {
+        let starts = self.wrap_points(row);
+        let seg = starts.iter().rposition(|&start| start <= rx).unwrap_or(0);
+        (seg, starts[seg], starts)
+    }
+
+    /// The visible part of a row from column `start` to `end`, with its
+    /// colors: the selection in reverse video, trailing whitespace on red,
+    /// and the rest highlighted. A line with right-to-left text is cut by
+    /// the columns it's drawn at, but colored by where things are in the
+    /// text.
+    fn draw_line(&self, fileoff: usize, row: &str, start: usize, end: usize) -> String {
+        let hl = self.highlighter.line(fileoff);
+        let selected = self.selected_columns(fileoff);
+        // The line being typed on is left alone, or every space would flash
+        // red until the next word starts.
+        let trailing = if self.show_trailing && fileoff != self.cy {
+            let text = self.rows[fileoff].trim_end_matches([' ', '\t']).to_string();
+            Some(width(&text.render(self.tab_stop)))
+        } else {
+            None
+        };
+        let marks = if self.show_whitespace { whitespace_marks(&self.rows[fileoff], self.tab_stop) } else { vec![] };
+        let flagged = self.diagnostic_columns(fileoff);
+        let mut s = String::new();
+        // Escape sequence in effect, so runs of the same color share one.
+        let mut current = String::new();
+        // Highlighting is per char, while columns go by screen cells: each
+        // grapheme with its first char and the column it starts at.
+        let mut cells = Vec::new();
+        let (mut ci, mut col) = (0, 0);
+        for g in row.graphemes(true) {
+            cells.push((g, ci, col));
+            ci += g.chars().count();
+            col += width(g);
+        }
+        if let Some(order) = bidi::visual(row) {
+            cells = order.into_iter().map(|k| cells[k]).collect();
+        }
+        // Where the grapheme is drawn, which is `i` unless it was reordered.
+        let mut x = 0;
+        for (g, first_char, i) in cells {
+            let (left, w) = (x, width(g));
+            x += w;
+            if left < start && (w == 0 || x <= start) {
+                continue;
+            }
+            if left >= end {
+                break;
+            }
+            let mark = marks.get(first_char).cloned().unwrap_or(None);
+            let style = match selected {
+                Some((from, to)) if i >= from && i < to => self.caps.reverse().to_string(),
+                _ if trailing.is_some_and(|t| i >= t) => self.caps.bg(Color::Red),
+                _ if mark.is_some() => self.caps.fg(Color::Blue),
+                _ => match hl.and_then(|hl| hl.get(first_char)).and_then(|&h| highlight_color(h)) {
+                    Some(color) => self.caps.fg(color),
+                    None => String::new(),
+                },
+            };
+            let style = if flagged.iter().any(|&(from, to)| i >= from && i < to) {
+                style + self.caps.underline()
+            } else {
+                style
+            };
+            if style != current {
+                if !current.is_empty() {
+                    s += self.caps.reset();
+                }
+                s += &style;
+                current = style;
+            }
+            if left < start || x > end {
+                // A wide character cut by the edge of the screen.
+                for _ in left.max(start)..x.min(end) {
+                    s.push(' ');
+                }
+            } else if let Some(mark) = mark {
+                s.push(mark);
+            } else {
+                s += g;
+            }
+        }
+        if !current.is_empty() {
+            s += self.caps.reset();
+        }
+        s
+    }
+
+    /// The tabs that fit on the screen, as their position, the column they
+    /// start at and their label. Tabs are dropped from the left until the
+    /// current one fits.
+    pub(crate) fn tab_labels(&self) -> Vec<(usize, usize, String)> {
+        let n = self.buffers.len();
+        let split = n - self.tab;
+        let label = |filename: &Option<String>, scratch: bool, dirty: bool| {
+            let name: String = display_name(filename, scratch).chars().take(20).collect();
+            format!(" {}{} ", name, if dirty && !scratch { "+" } else { "" })
+        };
+        let mut labels: Vec<String> = self.buffers[split..].iter()
+            .map(|b| label(&b.filename, b.scratch, b.dirty))
+            .collect();
+        labels.push(label(&self.filename, self.scratch, self.dirty));
+        labels.extend(self.buffers[..split].iter().map(|b| label(&b.filename, b.scratch, b.dirty)));
+
+        let mut first = 0;
+        while first < self.tab
+            && labels[first..self.tab + 1].iter().map(|l| l.chars().count()).sum::<usize>() > self.numcols {
+            first += 1;
+        }
+        let mut col = 0;
+        let mut shown = Vec::new();
+        for (i, label) in labels.into_iter().enumerate().skip(first) {
+            if col >= self.numcols {
+                break;
+            }
+            let width = label.chars().count();
+            shown.push((i, col, label));
+            col += width;
+        }
+        shown
+    }
+
+    /// One line of tabs, the current one standing out from the rest.
+    fn draw_tab_bar(&self) -> String {
+        let mut s = String::new();
+        let mut col = 0;
+        for (i, _, label) in self.tab_labels() {
+            let label: String = label.chars().take(self.numcols - col).collect();
+            col += label.chars().count();
+            if i == self.tab {
+                s += self.caps.reset();
+                s += &label;
+            } else {
+                s += self.caps.reverse();
+                s += &label;
+            }
+        }
+        s += self.caps.reverse();
+        s += &" ".repeat(self.numcols - col);
+        s += self.caps.reset();
+        s
+    }
+
+    fn draw_status_bar(&self) -> String {
+        let left = self.config.status_left.as_ref().map_or(status::LEFT, |s| s.as_slice());
+        let right = self.config.status_right.as_ref().map_or(status::RIGHT, |s| s.as_slice());
+        let join = |segments: &[Segment], separator| {
+            let texts: Vec<String> = segments.iter()
+                .map(|&segment| self.segment_text(segment))
+                .filter(|text| !text.is_empty())
+                .collect();
+            texts.join(separator)
+        };
+        let left: String = join(left, status::LEFT_SEPARATOR).chars().take(self.numcols).collect();
+        let right = join(right, status::RIGHT_SEPARATOR);
+        let mut s = self.caps.reverse().to_string();
+        s += &left;
+        // The right side only goes in whole.
+        let gap = self.numcols.saturating_sub(width(&left));
+        if width(&right) <= gap {
+            s += &" ".repeat(gap - width(&right));
+            s += &right;
+        } else {
+            s += &" ".repeat(gap);
+        }
+        s += self.caps.reset();
+        s
+    }
+
+    /// What `segment` shows now; empty when there's nothing to say.
+    fn segment_text(&self, segment: Segment) -> String {
+        match segment {
+            Segment::Mode => self.vim.as_ref().map_or(String::new(), |vim| vim.mode.name().to_string()),
+            Segment::Name => self.display_name().chars().take(20).collect(),
+            Segment::Lines => match self.hex {
+                Some(ref hex) => tr!(ByteCount, hex.bytes.len()),
+                None => tr!(LineCount, self.rows.len()),
+            },
+            Segment::Modified => match self.loading {
+                Some(ref loader) => tr!(Loading, loader.percent()),
+                None if self.unsaved() => tr!(Modified),
+                None => String::new(),
+            },
+            Segment::Endings if self.crlf && self.hex.is_none() => "CRLF".to_string(),
+            Segment::Endings => String::new(),
+            Segment::Encoding if self.hex.is_some() => "binary".to_string(),
+            Segment::Encoding => "UTF-8".to_string(),
+            Segment::Filetype => self.highlighter.syntax.map_or(String::new(), |s| s.filetype().to_string()),
+            Segment::Position => match self.hex {
+                Some(ref hex) => format!("{:08x}/{:08x}", hex.pos, hex.bytes.len()),
+                None => format!("{}/{}", self.cy + 1, self.rows.len()),
+            },
+            Segment::Column if self.hex.is_some() => String::new(),
+            Segment::Column => tr!(ColumnNumber, self.rx + 1),
+            Segment::Percent => {
+                let (at, len) = match self.hex {
+                    Some(ref hex) => (hex.pos, hex.bytes.len()),
+                    None => (self.cy, self.rows.len().saturating_sub(1)),
+                };
+                format!("{}%", (at.min(len) * 100).checked_div(len).unwrap_or(100))
+            }
+        }
+    }
+
+    fn draw_message_bar(&self) -> String {
+        let mut res = "".to_string();
+        res += "\x1b[K";
+        if Instant::now().duration_since(self.status_msg_time).as_secs() < self.status_secs {
+            let msg: String = self.status_msg.chars().take(self.numcols).collect();
+            res += &msg;
+        } else if let Some(diagnostic) = self.diagnostics.iter().find(|d| d.start.0 == self.cy) {
+            // What's wrong with the cursor line shows when nothing else does.
+            let msg: String = diagnostic.message.lines().next().unwrap_or("").chars().take(self.numcols).collect();
+            res += &self.caps.fg(diagnostic.color());
+            res += &msg;
+            res += self.caps.reset();
+        }
+        res
+    }
+
+    pub(crate) fn set_status_msg<S: AsRef<str>>(&mut self, message: S) {
+        self.status_msg = message.as_ref().to_owned();
+        self.status_msg_time = Instant::now();
+    }
+
+    fn scroll(&mut self) {
+        if let Some(line) = self.hex.as_ref().map(|hex| hex.cursor_line()) {
+            if line < self.rowoff {
+                self.rowoff = line;
+            }
+            if line >= self.rowoff + self.numrows {
+                self.rowoff = line + 1 - self.numrows;
+            }
+            return;
+        }
+        self.rx = 0;
+        if self.cy < self.rows.len() {
+            let (cx, line) = (self.cx, &self.rows[self.cy]);
+            self.rx = self.cx_to_rx(line, cx);
+        }
+        if self.wrap {
+            self.scroll_wrapped();
+            return;
+        }
+
+        let before = self.rowoff;
+        if self.cy < self.rowoff {
+            self.rowoff = self.cy;
+        }
+
+        if self.cy >= self.rowoff + self.numrows {
+            self.rowoff = self.cy - self.numrows + 1;
+        }
+        let jump = self.rowoff.abs_diff(before);
+        if self.smooth_scroll && self.shown_rowoff.is_none() && jump > (self.numrows / 2).max(2) {
+            self.shown_rowoff = Some(before);
+        }
+
+        if self.rx < self.coloff {
+            self.coloff = self.rx;
+        }
+
+        let cols = self.text_cols();
+        if self.rx >= self.coloff + cols {
+            self.coloff = self.rx - cols + 1;
+        }
+    }
+
+    /// Scrolls by screen rows so the one with the cursor is visible.
+    fn scroll_wrapped(&mut self) {
+        self.coloff = 0;
+        let (seg, _, _) = self.wrap_segment(self.cy, self.rx);
+        if (self.cy, seg) < (self.rowoff, self.wrapoff) {
+            self.rowoff = self.cy;
+            self.wrapoff = seg;
+            return;
+        }
+        if self.cy >= self.rowoff + self.numrows {
+            self.rowoff = self.cy + 1 - self.numrows;
+            self.wrapoff = 0;
+        }
+        // Screen rows from the top of the screen to the cursor's.
+        let mut lines = seg + 1;
+        for row in self.rowoff..self.cy {
+            lines += self.wrap_points(row).len();
+        }
+        lines = lines.saturating_sub(self.wrapoff);
+        while lines > self.numrows {
+            if self.wrapoff + 1 < self.wrap_points(self.rowoff).len() {
+                self.wrapoff += 1;
+            } else {
+                self.rowoff += 1;
+                self.wrapoff = 0;
+            }
+            lines -= 1;
+        }
+    }
+
+    /// Moves the animated view one frame closer to `rowoff`, covering a
+    /// third of the remaining distance each time.
+    pub(crate) fn animate_scroll(&mut self) {
+        let shown = match self.shown_rowoff {
+            Some(shown) => shown,
+            None => return,
+        };
+        let step = |d: usize| d.div_ceil(3);
+        self.shown_rowoff = if shown < self.rowoff {
+            Some(shown + step(self.rowoff - shown))
+        } else if shown > self.rowoff {
+            Some(shown - step(shown - self.rowoff))
+        } else {
+            None
+        };
+        if self.shown_rowoff == Some(self.rowoff) {
+            self.shown_rowoff = None;
+        }
+    }
+
+    pub(crate) fn gutter_width(&self) -> usize {
+        let signs = match self.sign_column {
+            SignColumn::Always => true,
+            SignColumn::Auto => !self.signs.is_empty(),
+            SignColumn::Never => false,
+        };
+        (if signs { 2 } else { 0 }) + self.number_width() + self.blame_width()
+    }
+
+    /// Columns for the blame annotations and the space after them.
+    fn blame_width(&self) -> usize {
+        self.blame.as_ref().and_then(|lines| lines.first()).map_or(0, |line| width(line) + 1)
+    }
+
+    /// Columns for line numbers and the space after them; room for three
+    /// digits is kept so the text doesn't shift on the 10th and 100th line.
+    fn number_width(&self) -> usize {
+        if self.line_numbers == LineNumbers::Off {
+            return 0;
+        }
+        self.rows.len().to_string().len().max(3) + 1
+    }
+
+    /// Screen columns left for text after the gutter.
+    fn text_cols(&self) -> usize {
+        self.numcols.saturating_sub(self.gutter_width())
+    }
+
+    fn draw_gutter(&self, row: usize) -> String {
+        let numbers = self.number_width();
+        let blame = self.blame_width();
+        let mut s = match self.blame.as_ref().and_then(|lines| lines.get(row)) {
+            Some(line) => format!("{}{}{} ", self.caps.fg(Color::Blue), line, self.caps.reset()),
+            None => " ".repeat(blame),
+        };
+        s += &match self.signs.top(row) {
+            _ if self.gutter_width() == numbers + blame => String::new(),
+            Some(sign) if row < self.rows.len() => {
+                format!("{}{}{} ", self.caps.fg(sign.color), sign.glyph, self.caps.reset())
+            }
+            _ => "  ".to_string(),
+        };
+        if numbers > 0 {
+            let number = match self.line_numbers {
+                _ if row >= self.rows.len() => String::new(),
+                LineNumbers::Relative if row != self.cy => {
+                    row.abs_diff(self.cy).to_string()
+                }
+                _ => (row + 1).to_string(),
+            };
+            let number = format!("{:>1$} ", number, numbers - 1);
+            if row == self.cy {
+                s += &self.caps.fg(Color::Yellow);
+                s += &number;
+                s += self.caps.reset();
+            } else {
+                s += &number;
+            }
+        }
+        s
+    }
+
+    pub(crate) fn cx_to_rx<S: AsRef<str>>(&self, s: S, cx: usize) -> usize {
+        let mut rx = 0;
+        for g in s.as_ref()[..cx].graphemes(true) {
+            if g == "\t" {
+                rx += self.tab_stop - (rx % self.tab_stop);
+            } else {
+                rx += width(g);
+            }
+        }
+        rx
+    }
+
+    pub(crate) fn rx_to_cx<S: AsRef<str>>(&self, s: S, rx: usize) -> usize {
+        let mut cur_rx = 0;
+
+        for (cx, g) in s.as_ref().grapheme_indices(true) {
+            if g == "\t" {
+                cur_rx += self.tab_stop - (cur_rx % self.tab_stop);
+            } else {
+                cur_rx += width(g);
+            }
+            if cur_rx > rx { return cx; }
+        }
+        s.as_ref().len()
+    }
+
+    /// The screen columns of `row` that diagnostics point at. One that
+    /// points between two characters takes in the one after.
+    fn diagnostic_columns(&self, row: usize) -> Vec<(usize, usize)> {
+        let line = &self.rows[row];
+        self.diagnostics.iter()
+            .filter(|d| d.start.0 <= row && row <= d.end.0)
+            .map(|d| {
+                let from = if d.start.0 == row { self.cx_to_rx(line, d.start.1.min(line.len())) } else { 0 };
+                let to = if d.end.0 == row { self.cx_to_rx(line, d.end.1.min(line.len())) } else { usize::MAX };
+                (from, to.max(from + 1))
+            })
+            .collect()
+    }
+
+    /// Rendered columns of `row` covered by the selection.
+    fn selected_columns(&self, row: usize) -> Option<(usize, usize)> {
+        let ((sy, sx), (ey, ex)) = self.selection()?;
+        if row < sy || row > ey {
+            return None;
+        }
+        let line = &self.rows[row];
+        let from = if row == sy { self.cx_to_rx(line, sx) } else { 0 };
+        let to = if row == ey { self.cx_to_rx(line, ex) } else { width(&line.render(self.tab_stop)) };
+        Some((from, to))
+    }
+}
@@ -0,0 +1,256 @@
+//! Searching the buffer, plainly or with regular expressions, and
+//! replacing.
+
+use std::io::Result;
+
+use regex::RegexBuilder;
+
+use editor::Editor;
+use input::{Completion, Key};
+use render::{Render, width};
+
+/// Matches of `pattern` in `text`, the lines of the buffer joined by
+/// newlines so that matches may span lines, as (row, column) ranges.
+fn regex_matches(text: &str, pattern: &str) -> Vec<((usize, usize), (usize, usize))> {
+    let re = match RegexBuilder::new(pattern).multi_line(true).build() {
+        Ok(re) => re,
+        Err(_) => return vec![],
+    };
+    let mut starts = vec![0];
+    starts.extend(text.match_indices('\n').map(|(i, _)| i + 1));
+    let position = |offset: usize| {
+        let row = match starts.binary_search(&offset) {
+            Ok(row) => row,
+            Err(row) => row - 1,
+        };
+        (row, offset - starts[row])
+    };
+    re.find_iter(text)
+        .filter(|m| m.start() != m.end())
+        .map(|m| (position(m.start()), position(m.end())))
+        .collect()
+}
+
+impl Editor {
+    pub fn find_regex(&mut self) -> Result<()> {
+        let saved_cx = self.cx;
+        let saved_cy = self.cy;
+        let saved_coloff = self.coloff;
+        let saved_rowoff = self.rowoff;
+
+        let mut current: Option<usize> = None;
+
+        let query = self.prompt(tr!(RegexSearch), |editor: &mut Editor, query: &str, key| {
+            let matches = regex_matches(&editor.rows.join("\n"), query);
+            if matches.is_empty() {
+                editor.anchor = None;
+                current = None;
+                return;
+            }
+            let count = matches.len();
+            let next = match (key, current) {
+                (Key::Right, Some(i)) | (Key::Down, Some(i)) => (i + 1) % count,
+                (Key::Left, Some(i)) | (Key::Up, Some(i)) => (i + count - 1) % count,
+                _ => matches.iter()
+                    .position(|&(start, _)| start >= (saved_cy, saved_cx))
+                    .unwrap_or(0),
+            };
+            current = Some(next);
+
+            // Show the match as a selection with the cursor at its start.
+            let (start, end) = matches[next];
+            editor.cy = start.0;
+            editor.cx = start.1;
+            editor.anchor = Some(end);
+            editor.rowoff = editor.rows.len();
+        })?;
+        self.anchor = None;
+        if query.is_none() {
+            self.cx = saved_cx;
+            self.cy = saved_cy;
+            self.rowoff = saved_rowoff;
+            self.coloff = saved_coloff;
+        } else if (self.cy, self.cx) != (saved_cy, saved_cx) {
+            self.jump_from((saved_cy, saved_cx));
+        }
+        Ok(())
+    }
+
+    /// Replaces occurrences of `pattern` from the cursor on, wrapping
+    /// around to it, asking about each one unless `all` is set. Whatever
+    /// isn't given is prompted for.
+    pub fn replace(&mut self, pattern: Option<String>, replacement: Option<String>, all: bool) -> Result<()> {
+        if self.read_only {
+            self.set_status_msg(tr!(ReadOnly));
+            return Ok(());
+        }
+        let pattern = match pattern {
+            Some(p) => p,
+            None => match self.prompt(tr!(Replace), |_, _, _| {})? {
+                Some(p) => p,
+                None => return Ok(()),
+            },
+        };
+        let replacement = match replacement {
+            Some(r) => r,
+            None => match self.prompt_with(&tr!(ReplaceWith, pattern), Completion::Off, |_, _, _| {})? {
+                Some(r) => r,
+                None => return Ok(()),
+            },
+        };
+        let (saved_cy, saved_cx) = (self.cy, self.cx);
+        let mut limit = (self.cy, self.cx);
+        let (mut y, mut x) = limit;
+        let mut wrapped = false;
+        let mut all = all;
+        let mut count = 0;
+        loop {
+            if y >= self.rows.len() {
+                if wrapped {
+                    break;
+                }
+                wrapped = true;
+                y = 0;
+                x = 0;
+                continue;
+            }
+            let found = self.rows[y][x.min(self.rows[y].len())..].find(&pattern).map(|i| x + i);
+            let at = match found {
+                Some(at) if !wrapped || (y, at) < limit => at,
+                _ if wrapped && y >= limit.0 => break,
+                _ => {
+                    y += 1;
+                    x = 0;
+                    continue;
+                }
+            };
+            let end = at + pattern.len();
+            let answer = if all {
+                b'a'
+            } else {
+                self.cy = y;
+                self.cx = end;
+                self.anchor = Some((y, at));
+                self.set_status_msg(tr!(ReplaceConfirm));
+                self.refresh_screen()?;
+                match self.read_key()? {
+                    Key::Char(c) => c.to_ascii_lowercase(),
+                    _ => b'q',
+                }
+            };
+            match answer {
+                b'y' | b'a' => {
+                    self.record((y, at), pattern.clone(), replacement.clone());
+                    self.rows[y].replace_range(at..end, &replacement);
+                    self.dirty = true;
+                    if y == limit.0 && at < limit.1 {
+                        limit.1 = (limit.1 + replacement.len()).saturating_sub(pattern.len());
+                    }
+                    x = at + replacement.len();
+                    self.cy = y;
+                    self.cx = x;
+                    count += 1;
+                    all = all || answer == b'a';
+                }
+                b'n' => x = end,
+                _ => break,
+            }
+        }
+        self.anchor = None;
+        if all {
+            self.cy = saved_cy;
+            self.cx = saved_cx.min(self.rows.get(saved_cy).map_or(0, |r| r.len()));
+        }
+        self.set_status_msg(tr!(Replaced, count));
+        Ok(())
+    }
+
+    pub fn find(&mut self) -> Result<()> {
+        let saved_cx = self.cx;
+        let saved_cy = self.cy;
+        let saved_coloff = self.coloff;
+        let saved_rowoff = self.rowoff;
+
+        let mut last_match: isize = -1;
+        let mut direction: isize = 1;
+
+        let query = self.prompt(tr!(Search), |editor: &mut Editor, query: &str, key| {
+            match key {
+                Key::Return | Key::Char(b'\x1b') => {
+                    last_match = -1;
+                    direction = 1;
+                    return;
+                }
+                Key::Right | Key::Down => {
+                    direction = 1;
+                }
+                Key::Left | Key::Up => {
+                    direction = -1;
+                }
+                _ => {
+                    last_match = -1;
+                    direction = 1;
+                }
+            }
+            if last_match == -1 {
+                direction = 1;
+            }
+            let mut current = last_match;
+
+            for _ in 0..editor.rows.len() {
+                current += direction;
+                if current == -1 { current = editor.rows.len() as isize - 1 }
+                else if current == editor.rows.len() as isize { current = 0; };
+
+                let row = &editor.rows[current as usize];
+                let render = row.render(editor.tab_stop);
+
+                if let Some(pos) = render.find(query) {
+                    last_match = current;
+                    editor.cy = current as usize;
+                    // The match is found in the rendered row; map its
+                    // column back through any tabs before it.
+                    let rx = width(&render[..pos]);
+                    editor.cx = editor.rx_to_cx(row, rx);
+                    editor.rowoff = editor.rows.len();
+                    break;
+                }
+            }
+        })?;
+        if query.is_none() {
+            self.cx = saved_cx;
+            self.cy = saved_cy;
+            self.rowoff = saved_rowoff;
+            self.coloff = saved_coloff;
+        } else if (self.cy, self.cx) != (saved_cy, saved_cx) {
+            self.jump_from((saved_cy, saved_cx));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_are_rows_and_columns() {
+        let text = "let x = 1;\nlet y = 22;";
+        assert_eq!(regex_matches(text, r"\d+"), [((0, 8), (0, 9)), ((1, 8), (1, 10))]);
+        assert_eq!(regex_matches(text, "^let"), [((0, 0), (0, 3)), ((1, 0), (1, 3))]);
+        assert_eq!(regex_matches(text, ";$"), [((0, 9), (0, 10)), ((1, 10), (1, 11))]);
+    }
+
+    #[test]
+    fn matches_may_span_lines() {
+        assert_eq!(regex_matches("one\nthe\nten", r"e\nt"), [((0, 2), (1, 1)), ((1, 2), (2, 1))]);
+        assert_eq!(regex_matches("a\n\nb", r"\n\n"), [((0, 1), (2, 0))]);
+    }
+
+    #[test]
+    fn empty_and_bad_patterns_find_nothing() {
+        assert_eq!(regex_matches("abc", "x*"), []);
+        assert_eq!(regex_matches("abc", "(unclosed"), []);
+        assert_eq!(regex_matches("", "a"), []);
+    }
+}
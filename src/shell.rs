@@ -0,0 +1,117 @@
+//! Running shell commands from the command prompt. `:!cmd` runs `cmd`
+//! with the terminal handed back to it and shows what it printed in a
+//! scratch buffer; `:r !cmd` puts that at the cursor instead. `:filter cmd`
+//! pipes the selected lines, or the whole buffer, through `cmd` and puts
+//! what comes out in their place.
+
+use std::io::{Result, Write};
+use std::process::{Command, Stdio};
+use std::thread;
+
+use editor::Editor;
+
+impl Editor {
+    /// Runs `command` in the shell on our terminal and returns its
+    /// standard output. What it writes to standard error shows as usual.
+    fn run_in_terminal(&mut self, command: &str) -> Result<Option<String>> {
+        self.suspend_terminal();
+        let output = Command::new("sh").arg("-c").arg(command)
+            .stdin(Stdio::inherit()).stdout(Stdio::piped()).stderr(Stdio::inherit())
+            .output();
+        self.resume_terminal()?;
+        let output = output?;
+        if !output.status.success() {
+            self.set_status_msg(tr!(ShellFailed, command, output.status));
+        }
+        let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+        Ok(if stdout.is_empty() { None } else { Some(stdout) })
+    }
+
+    /// Runs `command` and shows its output in a scratch buffer.
+    pub(crate) fn shell_command(&mut self, command: &str) -> Result<()> {
+        if command.is_empty() {
+            self.set_status_msg(tr!(BadArgument, "!", command));
+            return Ok(());
+        }
+        match self.run_in_terminal(command)? {
+            Some(output) => {
+                self.show_in_scratch(output.lines().map(|line| line.to_string()).collect());
+            }
+            None if self.status_msg.is_empty() => self.set_status_msg(tr!(ShellNoOutput, command)),
+            None => {}
+        }
+        Ok(())
+    }
+
+    /// Runs `command` and inserts its output at the cursor.
+    pub(crate) fn read_command(&mut self, command: &str) -> Result<()> {
+        if self.read_only {
+            self.set_status_msg(tr!(ReadOnly));
+            return Ok(());
+        }
+        if self.hex.is_some() {
+            self.set_status_msg(tr!(NotInHex));
+            return Ok(());
+        }
+        if let Some(output) = self.run_in_terminal(command)? {
+            self.insert_text(&output);
+        }
+        Ok(())
+    }
+
+    /// Replaces the lines the selection touches, or the whole buffer, with
+    /// what `command` prints when given them, as one undoable edit. Nothing
+    /// changes when it fails.
+    pub(crate) fn filter(&mut self, command: &str) -> Result<()> {
+        let command = match command {
+            "" => match self.prompt(tr!(FilterPrompt), |_, _, _| {})? {
+                Some(command) => command,
+                None => return Ok(()),
+            },
+            command => command.to_string(),
+        };
+        if self.read_only {
+            self.set_status_msg(tr!(ReadOnly));
+            return Ok(());
+        }
+        if self.hex.is_some() {
+            self.set_status_msg(tr!(NotInHex));
+            return Ok(());
+        }
+        self.finish_loading();
+        let lines = if self.selection().is_some() { self.touched_rows() } else { None };
+        let input = match lines {
+            Some((sy, ey)) => self.region_text((sy, 0), (ey + 1, 0)),
+            None => self.rows_to_string(),
+        };
+        let mut child = Command::new("sh").arg("-c").arg(&command)
+            .stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped())
+            .spawn()?;
+        // Written from another thread so a command that prints as it reads
+        // can't fill its output pipe while we're still writing.
+        let mut stdin = child.stdin.take().unwrap();
+        let writer = thread::spawn(move || stdin.write_all(input.as_bytes()));
+        let output = child.wait_with_output()?;
+        // A command that stops reading early breaks the pipe; that's fine.
+        let _ = writer.join();
+        if !output.status.success() {
+            let err = String::from_utf8_lossy(&output.stderr);
+            let err = err.lines().next().unwrap_or("").to_string();
+            self.set_status_msg(tr!(FilterFailed, output.status, err));
+            return Ok(());
+        }
+        let mut new = String::from_utf8_lossy(&output.stdout).into_owned();
+        if !new.is_empty() && !new.ends_with('\n') {
+            new.push('\n');
+        }
+        match lines {
+            Some((sy, ey)) => {
+                self.replace_region((sy, 0), (ey + 1, 0), &new);
+                self.cy = sy;
+                self.cx = 0;
+            }
+            None => self.replace_all(new),
+        }
+        Ok(())
+    }
+}
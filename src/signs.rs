@@ -0,0 +1,82 @@
+//! Signs shown in the gutter next to lines.
+
+use std::collections::HashMap;
+
+use render::Color;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignColumn {
+    /// Shown while any sign is placed.
+    Auto,
+    Always,
+    Never,
+}
+
+/// A mark shown in the sign column next to a line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sign {
+    /// The subsystem that placed the sign, e.g. "diagnostics" or "git".
+    pub group: String,
+    pub glyph: char,
+    pub color: Color,
+    /// When several signs share a line the highest priority one is shown.
+    pub priority: i32,
+}
+
+/// Signs placed on lines, keyed by row. Rows are kept in step with lines
+/// being inserted and removed.
+#[derive(Debug, Default)]
+pub struct SignRegistry {
+    pub(crate) signs: HashMap<usize, Vec<Sign>>,
+}
+
+impl SignRegistry {
+    /// Places `sign` on `row`, replacing any sign of the same group there.
+    pub fn place(&mut self, row: usize, sign: Sign) {
+        let signs = self.signs.entry(row).or_default();
+        signs.retain(|s| s.group != sign.group);
+        signs.push(sign);
+    }
+
+    pub fn remove(&mut self, row: usize, group: &str) {
+        if let Some(signs) = self.signs.get_mut(&row) {
+            signs.retain(|s| s.group != group);
+        }
+        self.signs.retain(|_, signs| !signs.is_empty());
+    }
+
+    pub fn clear_group(&mut self, group: &str) {
+        for signs in self.signs.values_mut() {
+            signs.retain(|s| s.group != group);
+        }
+        self.signs.retain(|_, signs| !signs.is_empty());
+    }
+
+    /// The row of the sign of `group`, when there's one.
+    pub fn find(&self, group: &str) -> Option<usize> {
+        self.signs.iter().find(|&(_, signs)| signs.iter().any(|s| s.group == group)).map(|(&row, _)| row)
+    }
+
+    /// The sign to show for `row`.
+    pub fn top(&self, row: usize) -> Option<&Sign> {
+        self.signs.get(&row)?.iter().max_by_key(|s| s.priority)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.signs.is_empty()
+    }
+
+    pub(crate) fn lines_inserted(&mut self, at: usize, count: usize) {
+        self.signs = self.signs.drain()
+            .map(|(row, signs)| (if row >= at { row + count } else { row }, signs))
+            .collect();
+    }
+
+    /// Signs on the removed lines go away with them.
+    pub(crate) fn lines_removed(&mut self, at: usize, count: usize) {
+        self.signs = self.signs.drain()
+            .filter(|&(row, _)| row < at || row >= at + count)
+            .map(|(row, signs)| (if row >= at { row - count } else { row }, signs))
+            .collect();
+    }
+}
@@ -0,0 +1,356 @@
+//! Snippets: a word that Tab expands into a piece of text with places in
+//! it to fill in. They're read from `~/.config/kilo/snippets/`, from the
+//! file named after the filetype, as for `[filetype.NAME]` settings, and
+//! from `all.toml` for every file. Each key is the word typed and its
+//! value what it becomes.
+//!
+//! ```toml
+//! # snippets/c.toml
+//! for = "for (${1:int i = 0}; ${2:i < n}; ${3:i++}) {\n\t$0\n}"
+//! main = "int main(int argc, char **argv)\n{\n\t${1:return 0;}\n}"
+//! ```
+//!
+//! `$1`, `${2}` and `${3:default}` are the places, visited in order with
+//! Tab and back with Shift-Tab; the cursor ends up at `$0`, or after the
+//! snippet without one. A default left in place is selected and goes away
+//! when something is typed over it. A number used twice is only visited
+//! the first time. `\$` puts in a dollar sign, and `\}` a brace inside a
+//! default. Tabs become the file's indentation and later lines keep that
+//! of the line the snippet starts on.
+
+use std::fs;
+use std::path::Path;
+
+use toml::Value;
+
+use config;
+use edit::is_word_char;
+use editor::Editor;
+use input::Key;
+use keymap::Action;
+use syntax;
+
+/// A snippet being filled in.
+pub(crate) struct SnippetSession {
+    /// The places Tab goes to, in order, as (row, byte) start and end.
+    stops: Vec<((usize, usize), (usize, usize))>,
+    current: usize,
+    /// The rows in the buffer and the bytes after the current place on
+    /// its line when the cursor got there, to tell how much typing in it
+    /// moved the places after it.
+    rows: usize,
+    after: usize,
+    /// Set while the current place still holds its default untouched.
+    fresh: bool,
+}
+
+/// The text of `body` with the places taken out, tabs made `tab` and
+/// `indent` put after each line break, and where the places are in it as
+/// (number, start, end) byte offsets.
+fn parse(body: &str, indent: &str, tab: &str) -> (String, Vec<(usize, usize, usize)>) {
+    let mut text = String::new();
+    let mut places = Vec::new();
+    // The number and start of a `${N:default}` whose default is being read.
+    let mut open: Option<(usize, usize)> = None;
+    let mut chars = body.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek().is_some_and(|&c| c == '$' || c == '}' || c == '\\') => {
+                text.push(chars.next().unwrap());
+            }
+            '$' if chars.peek().is_some_and(|c| c.is_ascii_digit()) => {
+                let mut n = 0;
+                while let Some(d) = chars.peek().and_then(|c| c.to_digit(10)) {
+                    n = n * 10 + d as usize;
+                    chars.next();
+                }
+                places.push((n, text.len(), text.len()));
+            }
+            '$' if chars.peek() == Some(&'{') && open.is_none() => {
+                let mut lookahead = chars.clone();
+                lookahead.next();
+                let mut n = None;
+                while let Some(d) = lookahead.peek().and_then(|c| c.to_digit(10)) {
+                    n = Some(n.unwrap_or(0) * 10 + d as usize);
+                    lookahead.next();
+                }
+                match (n, lookahead.peek()) {
+                    (Some(n), Some(&'}')) => {
+                        lookahead.next();
+                        places.push((n, text.len(), text.len()));
+                    }
+                    (Some(n), Some(&':')) => {
+                        lookahead.next();
+                        open = Some((n, text.len()));
+                    }
+                    _ => {
+                        text.push('$');
+                        continue;
+                    }
+                }
+                chars = lookahead;
+            }
+            '}' if open.is_some() => {
+                let (n, start) = open.take().unwrap();
+                places.push((n, start, text.len()));
+            }
+            '\n' => {
+                text.push('\n');
+                text += indent;
+            }
+            '\t' => text += tab,
+            c => text.push(c),
+        }
+    }
+    (text, places)
+}
+
+/// The (row, byte) that byte `offset` of `text`, put in at `at`, ends up at.
+fn offset_position(text: &str, at: (usize, usize), offset: usize) -> (usize, usize) {
+    let before = &text[..offset];
+    match before.rfind('\n') {
+        Some(i) => (at.0 + before.matches('\n').count(), offset - i - 1),
+        None => (at.0, at.1 + offset),
+    }
+}
+
+/// The snippets for a file named `filename`, from the file for its
+/// filetype, extension or name and then `all.toml`, the first definition
+/// of a word winning. Files that don't parse are reported in `errors`.
+fn load(filename: &str, errors: &mut Vec<String>) -> Vec<(String, String)> {
+    let dir = match config::global_path() {
+        Some(path) => path.with_file_name("snippets"),
+        None => return Vec::new(),
+    };
+    let path = Path::new(filename);
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    let filetype = syntax::for_file(name).map_or("", |syntax| syntax.filetype);
+    let mut snippets: Vec<(String, String)> = Vec::new();
+    let mut read = Vec::new();
+    for name in &[filetype, ext, name, "all"] {
+        if name.is_empty() || read.contains(name) {
+            continue;
+        }
+        read.push(*name);
+        let file = dir.join(format!("{}.toml", name));
+        let text = match fs::read_to_string(&file) {
+            Ok(text) => text,
+            Err(_) => continue,
+        };
+        let table = match text.parse::<Value>() {
+            Ok(Value::Table(table)) => table,
+            Ok(_) => continue,
+            Err(e) => {
+                errors.push(format!("{}: {}", file.display(), e));
+                continue;
+            }
+        };
+        for (word, body) in table {
+            if let Some(body) = body.as_str() {
+                if !snippets.iter().any(|(w, _)| *w == word) {
+                    snippets.push((word, body.to_string()));
+                }
+            }
+        }
+    }
+    snippets
+}
+
+impl Editor {
+    /// Handles Tab and Shift-Tab while a snippet is filled in, Tab after a
+    /// snippet's word, and typing over a default. Returns whether the key
+    /// was dealt with.
+    pub(crate) fn snippet_key(&mut self, c: Key, action: Option<Action>) -> bool {
+        if self.snippet.as_ref().is_some_and(|s| s.fresh) {
+            let (start, end) = {
+                let session = self.snippet.as_mut().unwrap();
+                session.fresh = false;
+                session.stops[session.current]
+            };
+            let typing = match (c, action) {
+                (Key::Char(c), None) => c >= 0x20 && c != 0x7f,
+                (Key::Paste, None) => true,
+                _ => false,
+            };
+            let deleting = action == Some(Action::Backspace) || action == Some(Action::Delete);
+            if (typing || deleting) && (self.cy, self.cx) == end {
+                self.replace_region(start, end, "");
+                self.anchor = None;
+                if deleting {
+                    return true;
+                }
+            }
+        }
+        match action {
+            Some(Action::Indent) if self.snippet.is_some() => {
+                if self.snippet_step(true) {
+                    return true;
+                }
+                self.snippet = None;
+            }
+            Some(Action::Outdent) if self.snippet.is_some() => {
+                if self.snippet_step(false) {
+                    return true;
+                }
+                self.snippet = None;
+            }
+            _ if c == Key::Char(b'\x1b') && self.snippet.is_some() => {
+                self.snippet = None;
+                self.anchor = None;
+                return true;
+            }
+            _ => {}
+        }
+        action == Some(Action::Indent) && self.selection().is_none() && self.expand_snippet()
+    }
+
+    /// Expands the word before the cursor if it names a snippet.
+    fn expand_snippet(&mut self) -> bool {
+        let filename = match self.filename {
+            Some(ref name) if self.hex.is_none() && !self.scratch => name.clone(),
+            _ => return false,
+        };
+        let (start, word, indent) = {
+            let row = match self.rows.get(self.cy) {
+                Some(row) => row.as_str(),
+                None => return false,
+            };
+            if row[self.cx..].chars().next().is_some_and(is_word_char) {
+                return false;
+            }
+            let start = self.word_before().1;
+            let indent: String = row.chars().take_while(|&c| c == ' ' || c == '\t').collect();
+            (start, row[start..self.cx].to_string(), indent)
+        };
+        if word.is_empty() {
+            return false;
+        }
+        let mut errors = Vec::new();
+        let snippets = load(&filename, &mut errors);
+        if let Some(e) = errors.first() {
+            self.set_status_msg(tr!(SnippetError, e));
+        }
+        let body = match snippets.into_iter().find(|(w, _)| *w == word) {
+            Some((_, body)) => body,
+            None => return false,
+        };
+        let (text, mut places) = parse(&body, &indent, &self.indent_unit());
+        let at = (self.cy, start);
+        self.replace_region(at, (self.cy, self.cx), &text);
+
+        // Numbered places in order, then `$0` or the end; only the first
+        // of each number.
+        places.sort_by_key(|&(n, start, _)| (n == 0, n, start));
+        places.dedup_by_key(|&mut (n, _, _)| n);
+        if places.last().is_none_or(|&(n, _, _)| n != 0) {
+            places.push((0, text.len(), text.len()));
+        }
+        let stops = places.iter()
+            .map(|&(_, s, e)| (offset_position(&text, at, s), offset_position(&text, at, e)))
+            .collect();
+        self.snippet = Some(SnippetSession { stops, current: 0, rows: 0, after: 0, fresh: false });
+        self.snippet_go(0);
+        true
+    }
+
+    /// Moves on to the next place, or back to the one before, after moving
+    /// those after the current one by what was typed in it. False when the
+    /// cursor has left the current place, which ends the snippet.
+    fn snippet_step(&mut self, forward: bool) -> bool {
+        let rows = self.rows.len();
+        let (cy, cx) = (self.cy, self.cx);
+        let next = {
+            let lines = &self.rows;
+            let row_len = |y: usize| lines.get(y).map_or(0, |row| row.len());
+            let session = self.snippet.as_mut().unwrap();
+            let (start, (ey, ex)) = session.stops[session.current];
+            let new_ey = (ey + rows).saturating_sub(session.rows);
+            let new_ex = match row_len(new_ey).checked_sub(session.after) {
+                Some(x) => x,
+                None => return false,
+            };
+            if (cy, cx) < start || (cy, cx) > (new_ey, new_ex) {
+                return false;
+            }
+            for stop in session.stops.iter_mut().skip(session.current + 1) {
+                for point in &mut [&mut stop.0, &mut stop.1] {
+                    if **point >= (ey, ex) {
+                        **point = if point.0 == ey {
+                            (new_ey, point.1 - ex + new_ex)
+                        } else {
+                            (point.0 + new_ey - ey, point.1)
+                        };
+                    }
+                }
+            }
+            session.stops[session.current] = (start, (new_ey, new_ex));
+            match forward {
+                true => session.current + 1,
+                false => session.current.saturating_sub(1),
+            }
+        };
+        self.snippet_go(next);
+        true
+    }
+
+    /// Puts the cursor at place `index`, selecting its default; the last
+    /// place ends the snippet.
+    fn snippet_go(&mut self, index: usize) {
+        let (start, end, last) = {
+            let session = self.snippet.as_mut().unwrap();
+            let index = index.min(session.stops.len() - 1);
+            session.current = index;
+            let (start, end) = session.stops[index];
+            (start, end, index + 1 == session.stops.len())
+        };
+        self.cy = end.0;
+        self.cx = end.1;
+        self.anchor = if start != end { Some(start) } else { None };
+        if last {
+            self.snippet = None;
+            return;
+        }
+        let after = self.rows.get(end.0).map_or(0, |row| row.len()) - end.1;
+        let rows = self.rows.len();
+        let session = self.snippet.as_mut().unwrap();
+        session.rows = rows;
+        session.after = after;
+        session.fresh = start != end;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn places_are_taken_out() {
+        let (text, places) = parse("for (${1:int i = 0}; ${2:i < n}; $3) {$0}", "", "\t");
+        assert_eq!(text, "for (int i = 0; i < n; ) {}");
+        assert_eq!(places, [(1, 5, 14), (2, 16, 21), (3, 23, 23), (0, 26, 26)]);
+        assert_eq!(parse("${12}x", "", "\t"), (String::from("x"), vec![(12, 0, 0)]));
+    }
+
+    #[test]
+    fn escapes_and_stray_dollars_stay_as_text() {
+        assert_eq!(parse(r"\$1 costs $$ and ${x}", "", "\t").0, "$1 costs $$ and ${x}");
+        assert_eq!(parse(r"${1:a\}b}", "", "\t"), (String::from("a}b"), vec![(1, 0, 3)]));
+        assert_eq!(parse(r"back\\slash", "", "\t").0, r"back\slash");
+    }
+
+    #[test]
+    fn later_lines_are_indented() {
+        let (text, places) = parse("if x {\n\t$0\n}", "  ", "    ");
+        assert_eq!(text, "if x {\n      \n  }");
+        assert_eq!(places, [(0, 13, 13)]);
+    }
+
+    #[test]
+    fn offsets_become_rows_and_bytes() {
+        let text = "ab\ncd\nef";
+        assert_eq!(offset_position(text, (4, 10), 1), (4, 11));
+        assert_eq!(offset_position(text, (4, 10), 3), (5, 0));
+        assert_eq!(offset_position(text, (4, 10), 7), (6, 1));
+    }
+}
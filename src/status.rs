@@ -0,0 +1,59 @@
+//! What the status bar shows. Each side is a list of segments, drawn in
+//! order with the empty ones left out:
+//!
+//! ```toml
+//! status_left = ["name", "lines", "modified"]
+//! status_right = ["filetype", "endings", "encoding", "percent", "position"]
+//! ```
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Segment {
+    /// The vi mode, when keys work as in vi.
+    Mode,
+    /// The file name, shortened.
+    Name,
+    /// How long the buffer is, in lines or, for binary files, bytes.
+    Lines,
+    /// Unsaved changes, or how much of the file is still loading.
+    Modified,
+    /// `CRLF` for files with Windows line endings.
+    Endings,
+    /// `UTF-8`, or `binary` in the hex view.
+    Encoding,
+    Filetype,
+    /// The cursor line out of the lines there are, or its offset in hex.
+    Position,
+    Column,
+    /// How far down the buffer the cursor is.
+    Percent,
+}
+
+const NAMES: &[(&str, Segment)] = &[
+    ("mode", Segment::Mode),
+    ("name", Segment::Name),
+    ("lines", Segment::Lines),
+    ("modified", Segment::Modified),
+    ("endings", Segment::Endings),
+    ("encoding", Segment::Encoding),
+    ("filetype", Segment::Filetype),
+    ("position", Segment::Position),
+    ("column", Segment::Column),
+    ("percent", Segment::Percent),
+];
+
+pub const LEFT: &[Segment] = &[Segment::Mode, Segment::Name, Segment::Lines, Segment::Modified];
+pub const RIGHT: &[Segment] = &[Segment::Filetype, Segment::Endings, Segment::Position];
+
+/// Put between the segments on the left and on the right.
+pub const LEFT_SEPARATOR: &str = " - ";
+pub const RIGHT_SEPARATOR: &str = " | ";
+
+impl Segment {
+    pub fn from_name(name: &str) -> Option<Segment> {
+        NAMES.iter().find(|&&(n, _)| n == name).map(|&(_, segment)| segment)
+    }
+
+    pub fn names() -> Vec<&'static str> {
+        NAMES.iter().map(|&(name, _)| name).collect()
+    }
+}
@@ -0,0 +1,84 @@
+//! Highlighting with syntect, which reads Sublime Text syntax definitions
+//! and color themes, for the languages the builtin database doesn't know.
+//! Only compiled with the `syntect` feature.
+//!
+//! The definitions and themes bundled with syntect are loaded the first
+//! time a file is opened. `KILO_THEME` picks the theme by name.
+
+use std::env;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use syntect::highlighting::{HighlightIterator, HighlightState, Highlighter, Theme, ThemeSet};
+use syntect::parsing::{ParseState, ScopeStack, SyntaxSet};
+
+pub use syntect::parsing::SyntaxReference;
+
+use syntax::Hl;
+
+const DEFAULT_THEME: &str = "base16-ocean.dark";
+
+/// The parser and theme state at the end of a row.
+#[derive(Clone, PartialEq)]
+pub struct State {
+    parse: ParseState,
+    highlight: HighlightState,
+}
+
+fn syntaxes() -> &'static SyntaxSet {
+    static SYNTAXES: OnceLock<SyntaxSet> = OnceLock::new();
+    SYNTAXES.get_or_init(SyntaxSet::load_defaults_newlines)
+}
+
+fn theme() -> &'static Theme {
+    static THEME: OnceLock<Theme> = OnceLock::new();
+    THEME.get_or_init(|| {
+        let mut themes = ThemeSet::load_defaults().themes;
+        let name = env::var("KILO_THEME").ok().filter(|name| themes.contains_key(name));
+        themes.remove(name.as_ref().map_or(DEFAULT_THEME, |n| n.as_str())).unwrap_or_default()
+    })
+}
+
+fn highlighter() -> &'static Highlighter<'static> {
+    static HIGHLIGHTER: OnceLock<Highlighter<'static>> = OnceLock::new();
+    HIGHLIGHTER.get_or_init(|| Highlighter::new(theme()))
+}
+
+pub fn for_file(filename: &str) -> Option<&'static SyntaxReference> {
+    let syntaxes = syntaxes();
+    let path = Path::new(filename);
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or(filename);
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    syntaxes.find_syntax_by_extension(name).or_else(|| syntaxes.find_syntax_by_extension(ext))
+}
+
+pub fn by_name(name: &str) -> Option<&'static SyntaxReference> {
+    let syntaxes = syntaxes();
+    syntaxes.find_syntax_by_name(name).or_else(|| syntaxes.find_syntax_by_token(name))
+}
+
+pub fn initial(syntax: &SyntaxReference) -> State {
+    State {
+        parse: ParseState::new(syntax),
+        highlight: HighlightState::new(highlighter(), ScopeStack::new()),
+    }
+}
+
+/// Colors for each character of `row`, carrying on from `state`. A row the
+/// definition can't parse is left uncolored.
+pub fn highlight_row(row: &str, state: &State) -> (Vec<Hl>, State) {
+    let mut state = state.clone();
+    // The bundled definitions expect each line to end in a newline.
+    let line = format!("{}\n", row);
+    let ops = match state.parse.parse_line(&line, syntaxes()) {
+        Ok(ops) => ops,
+        Err(_) => return (vec![Hl::Normal; row.chars().count()], state),
+    };
+    let mut hl = Vec::with_capacity(line.len());
+    for (style, text) in HighlightIterator::new(&mut state.highlight, &ops, &line, highlighter()) {
+        let c = style.foreground;
+        hl.extend(text.chars().map(|_| Hl::Rgb(c.r, c.g, c.b)));
+    }
+    hl.truncate(row.chars().count());
+    (hl, state)
+}
@@ -0,0 +1,393 @@
+//! Syntax highlighting in the manner of the kilo tutorial: a small database
+//! of filetypes, each with keywords and which kinds of literals and comments
+//! to pick out, and a highlighter that colors one rendered row at a time.
+//!
+//! A row can only be highlighted once it's known whether it starts inside a
+//! multi-line comment, which depends on every row above it. The highlighter
+//! keeps each row's colors along with the state it started and ended in, so
+//! after an edit only the touched rows are redone, and the rows below them
+//! only while the state they start in keeps changing.
+
+use std::path::Path;
+
+#[cfg(feature = "syntect")]
+use sublime;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hl {
+    Normal,
+    Comment,
+    MlComment,
+    Keyword,
+    Type,
+    String,
+    Number,
+    /// A color picked by a theme.
+    #[cfg(feature = "syntect")]
+    Rgb(u8, u8, u8),
+}
+
+pub struct Syntax {
+    pub filetype: &'static str,
+    /// Extensions with their dot, or whole file names.
+    pub filematch: &'static [&'static str],
+    pub keywords: &'static [&'static str],
+    pub types: &'static [&'static str],
+    pub comment: &'static str,
+    pub ml_comment: Option<(&'static str, &'static str)>,
+    pub numbers: bool,
+    pub strings: bool,
+    /// Single quotes are character literals, so `'` alone (a Rust lifetime)
+    /// doesn't start a string.
+    pub char_literals: bool,
+}
+
+pub static HLDB: &[Syntax] = &[
+    Syntax {
+        filetype: "c",
+        filematch: &[".c", ".h", ".cc", ".cpp", ".hpp"],
+        keywords: &[
+            "switch", "if", "while", "for", "break", "continue", "return", "else", "struct", "union",
+            "typedef", "static", "enum", "class", "case", "default", "do", "goto", "sizeof", "const",
+            "extern", "volatile", "register", "namespace", "template", "public", "private", "protected",
+            "new", "delete", "#include", "#define", "#ifdef", "#ifndef", "#endif", "#if", "#else",
+        ],
+        types: &[
+            "int", "long", "double", "float", "char", "unsigned", "signed", "void", "short", "bool",
+            "size_t", "auto",
+        ],
+        comment: "//",
+        ml_comment: Some(("/*", "*/")),
+        numbers: true,
+        strings: true,
+        char_literals: true,
+    },
+    Syntax {
+        filetype: "rust",
+        filematch: &[".rs"],
+        keywords: &[
+            "as", "break", "const", "continue", "crate", "else", "enum", "extern", "fn", "for", "if",
+            "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return", "self",
+            "Self", "static", "struct", "super", "trait", "type", "unsafe", "use", "where", "while",
+            "dyn", "true", "false",
+        ],
+        types: &[
+            "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32", "u64", "u128", "usize",
+            "f32", "f64", "bool", "char", "str", "String", "Vec", "Option", "Result", "Box",
+        ],
+        comment: "//",
+        ml_comment: Some(("/*", "*/")),
+        numbers: true,
+        strings: true,
+        char_literals: true,
+    },
+    Syntax {
+        filetype: "python",
+        filematch: &[".py"],
+        keywords: &[
+            "and", "as", "assert", "break", "class", "continue", "def", "del", "elif", "else", "except",
+            "finally", "for", "from", "global", "if", "import", "in", "is", "lambda", "nonlocal", "not",
+            "or", "pass", "raise", "return", "try", "while", "with", "yield", "None", "True", "False",
+        ],
+        types: &["int", "float", "str", "bytes", "list", "dict", "set", "tuple", "bool", "object"],
+        comment: "#",
+        ml_comment: None,
+        numbers: true,
+        strings: true,
+        char_literals: false,
+    },
+    Syntax {
+        filetype: "sh",
+        filematch: &[".sh", ".bash", ".bashrc", ".profile"],
+        keywords: &[
+            "if", "then", "else", "elif", "fi", "case", "esac", "for", "while", "until", "do", "done",
+            "in", "function", "return", "local", "export", "readonly", "shift", "exit",
+        ],
+        types: &[],
+        comment: "#",
+        ml_comment: None,
+        numbers: false,
+        strings: true,
+        char_literals: false,
+    },
+    Syntax {
+        filetype: "toml",
+        filematch: &[".toml"],
+        keywords: &["true", "false"],
+        types: &[],
+        comment: "#",
+        ml_comment: None,
+        numbers: true,
+        strings: true,
+        char_literals: false,
+    },
+];
+
+/// The syntax for a file, going by its extension or name.
+pub fn for_file(filename: &str) -> Option<&'static Syntax> {
+    let path = Path::new(filename);
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or(filename);
+    let ext = path.extension().and_then(|e| e.to_str()).map(|e| format!(".{}", e));
+    HLDB.iter().find(|syntax| {
+        syntax.filematch.iter().any(|m| *m == name || ext.as_ref().is_some_and(|e| e == m))
+    })
+}
+
+pub fn by_name(filetype: &str) -> Option<&'static Syntax> {
+    HLDB.iter().find(|syntax| syntax.filetype == filetype)
+}
+
+fn is_separator(c: char) -> bool {
+    c.is_whitespace() || ",.()+-/*=~%<>[];{}:&|!^?".contains(c)
+}
+
+fn starts_with(chars: &[char], i: usize, s: &str) -> bool {
+    s.chars().enumerate().all(|(n, c)| chars.get(i + n) == Some(&c))
+}
+
+/// Colors for each character of `row`, given whether it starts inside a
+/// multi-line comment, and whether it ends inside one.
+pub fn highlight_row(syntax: &Syntax, row: &str, open_comment: bool) -> (Vec<Hl>, bool) {
+    let chars: Vec<char> = row.chars().collect();
+    let mut hl = vec![Hl::Normal; chars.len()];
+    let mut in_comment = open_comment;
+    let mut in_string: Option<char> = None;
+    let mut prev_sep = true;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let prev_hl = if i > 0 { hl[i - 1] } else { Hl::Normal };
+
+        if in_string.is_none() && !in_comment && !syntax.comment.is_empty()
+            && starts_with(&chars, i, syntax.comment) {
+            for h in &mut hl[i..] {
+                *h = Hl::Comment;
+            }
+            break;
+        }
+
+        if let (Some((start, end)), None) = (syntax.ml_comment, in_string) {
+            if in_comment {
+                hl[i] = Hl::MlComment;
+                if starts_with(&chars, i, end) {
+                    let n = end.chars().count();
+                    for h in &mut hl[i..i + n] {
+                        *h = Hl::MlComment;
+                    }
+                    i += n;
+                    in_comment = false;
+                    prev_sep = true;
+                } else {
+                    i += 1;
+                }
+                continue;
+            } else if starts_with(&chars, i, start) {
+                let n = start.chars().count();
+                for h in &mut hl[i..i + n] {
+                    *h = Hl::MlComment;
+                }
+                i += n;
+                in_comment = true;
+                continue;
+            }
+        }
+
+        if syntax.strings {
+            if let Some(quote) = in_string {
+                hl[i] = Hl::String;
+                if c == '\\' && i + 1 < chars.len() {
+                    hl[i + 1] = Hl::String;
+                    i += 2;
+                    continue;
+                }
+                if c == quote {
+                    in_string = None;
+                }
+                i += 1;
+                prev_sep = true;
+                continue;
+            }
+            let char_literal = chars.get(i + 1) == Some(&'\\') || chars.get(i + 2) == Some(&'\'');
+            if c == '"' || (c == '\'' && (!syntax.char_literals || char_literal)) {
+                in_string = Some(c);
+                hl[i] = Hl::String;
+                i += 1;
+                continue;
+            }
+        }
+
+        if syntax.numbers && ((c.is_ascii_digit() && (prev_sep || prev_hl == Hl::Number))
+                              || (c == '.' && prev_hl == Hl::Number)) {
+            hl[i] = Hl::Number;
+            i += 1;
+            prev_sep = false;
+            continue;
+        }
+
+        if prev_sep {
+            let word_end = |len: usize| chars.get(i + len).is_none_or(|&c| is_separator(c));
+            let found = syntax.keywords.iter().map(|k| (k, Hl::Keyword))
+                .chain(syntax.types.iter().map(|k| (k, Hl::Type)))
+                .find(|&(k, _)| starts_with(&chars, i, k) && word_end(k.chars().count()));
+            if let Some((keyword, kind)) = found {
+                let n = keyword.chars().count();
+                for h in &mut hl[i..i + n] {
+                    *h = kind;
+                }
+                i += n;
+                prev_sep = false;
+                continue;
+            }
+        }
+
+        prev_sep = is_separator(c);
+        i += 1;
+    }
+    (hl, in_comment)
+}
+
+/// Where a row leaves off, which the next row starts from.
+#[derive(Clone, PartialEq)]
+pub enum State {
+    /// Whether a multi-line comment is still open.
+    Builtin(bool),
+    #[cfg(feature = "syntect")]
+    Sublime(Box<sublime::State>),
+}
+
+/// What does the highlighting: the filetype database above, or with the
+/// `syntect` feature, a Sublime Text syntax definition.
+#[derive(Clone, Copy)]
+pub enum Backend {
+    Builtin(&'static Syntax),
+    #[cfg(feature = "syntect")]
+    Sublime(&'static sublime::SyntaxReference),
+}
+
+impl Backend {
+    /// The backend for a file, preferring syntect's definitions when they
+    /// are compiled in.
+    pub fn for_file(filename: &str) -> Option<Backend> {
+        #[cfg(feature = "syntect")]
+        {
+            if let Some(syntax) = sublime::for_file(filename) {
+                return Some(Backend::Sublime(syntax));
+            }
+        }
+        for_file(filename).map(Backend::Builtin)
+    }
+
+    pub fn by_name(name: &str) -> Option<Backend> {
+        #[cfg(feature = "syntect")]
+        {
+            if let Some(syntax) = sublime::by_name(name) {
+                return Some(Backend::Sublime(syntax));
+            }
+        }
+        by_name(name).map(Backend::Builtin)
+    }
+
+    pub fn filetype(&self) -> &'static str {
+        match *self {
+            Backend::Builtin(syntax) => syntax.filetype,
+            #[cfg(feature = "syntect")]
+            Backend::Sublime(syntax) => &syntax.name,
+        }
+    }
+
+    fn initial(&self) -> State {
+        match *self {
+            Backend::Builtin(_) => State::Builtin(false),
+            #[cfg(feature = "syntect")]
+            Backend::Sublime(syntax) => State::Sublime(Box::new(sublime::initial(syntax))),
+        }
+    }
+
+    fn highlight(&self, row: &str, state: &State) -> (Vec<Hl>, State) {
+        match (*self, state) {
+            (Backend::Builtin(syntax), &State::Builtin(open)) => {
+                let (hl, open) = highlight_row(syntax, row, open);
+                (hl, State::Builtin(open))
+            }
+            #[cfg(feature = "syntect")]
+            (Backend::Sublime(_), State::Sublime(state)) => {
+                let (hl, state) = sublime::highlight_row(row, state);
+                (hl, State::Sublime(Box::new(state)))
+            }
+            #[cfg(feature = "syntect")]
+            _ => self.highlight(row, &self.initial()),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Line {
+    hl: Vec<Hl>,
+    start: State,
+    end: State,
+    /// The row changed since it was highlighted.
+    stale: bool,
+}
+
+const STALE: Line = Line { hl: Vec::new(), start: State::Builtin(false), end: State::Builtin(false), stale: true };
+
+/// Highlighting for every row of a buffer, worked out as rows are shown.
+pub struct Highlighter {
+    pub syntax: Option<Backend>,
+    lines: Vec<Line>,
+    /// Rows before this one are up to date.
+    checked: usize,
+}
+
+impl Highlighter {
+    pub fn new(syntax: Option<Backend>) -> Highlighter {
+        Highlighter { syntax, lines: Vec::new(), checked: 0 }
+    }
+
+    /// Notes that `removed` was replaced by `inserted` starting on `row`,
+    /// so rows below shift along with their colors.
+    pub fn edited(&mut self, row: usize, removed: &str, inserted: &str) {
+        if row < self.lines.len() {
+            let end = (row + removed.matches('\n').count() + 1).min(self.lines.len());
+            let count = inserted.matches('\n').count() + 1;
+            self.lines.splice(row..end, (0..count).map(|_| STALE));
+        }
+        self.checked = self.checked.min(row);
+    }
+
+    /// Brings the rows before `upto` up to date, out of `len` rows; `render`
+    /// gives the rendered text of a row.
+    pub fn update<F: Fn(usize) -> String>(&mut self, len: usize, upto: usize, render: F) {
+        let backend = match self.syntax {
+            Some(backend) => backend,
+            None => return,
+        };
+        self.lines.resize(len, STALE);
+        self.checked = self.checked.min(len);
+        let upto = upto.min(len);
+        let mut state = match self.checked {
+            0 => backend.initial(),
+            n => self.lines[n - 1].end.clone(),
+        };
+        for i in self.checked..upto {
+            let line = &mut self.lines[i];
+            if line.stale || line.start != state {
+                let (hl, end) = backend.highlight(&render(i), &state);
+                *line = Line { hl, start: state, end, stale: false };
+            }
+            state = line.end.clone();
+        }
+        self.checked = self.checked.max(upto);
+    }
+
+    /// Colors for each rendered character of `row`, if it has been
+    /// highlighted.
+    pub fn line(&self, row: usize) -> Option<&[Hl]> {
+        self.syntax?;
+        match self.lines.get(row) {
+            Some(line) if !line.stale => Some(&line.hl),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,221 @@
+//! Raw mode for the terminal on stdin. The guard puts the terminal back the
+//! way it found it when dropped, so every way out of the editor that runs
+//! destructors leaves a usable shell behind.
+//!
+//! Also the signals the terminal sends, and writing to and sizing the
+//! screen.
+
+use std::{io, str};
+use std::fs::File;
+use std::io::{Read, Result, Write};
+use std::os::unix::io::IntoRawFd;
+use std::time::Instant;
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicUsize, Ordering};
+
+use termios::*;
+use libc;
+use termsize;
+
+use editor::Editor;
+use watch;
+
+pub struct RawMode {
+    original: Termios,
+}
+
+impl RawMode {
+    /// Switches the terminal from `original` to raw mode: no echo, no line
+    /// buffering, no signals from keys, and no output processing. Reads
+    /// time out after a tenth of a second.
+    pub fn enable(original: Termios) -> io::Result<RawMode> {
+        let mode = RawMode { original };
+        mode.resume()?;
+        Ok(mode)
+    }
+
+    /// Switches to raw mode again after `restore`.
+    pub fn resume(&self) -> io::Result<()> {
+        let mut raw = self.original;
+        raw.c_iflag &= !(BRKINT | ICRNL | INPCK | ISTRIP | IXON);
+        raw.c_oflag &= !(OPOST);
+        raw.c_cflag |= CS8;
+        raw.c_lflag &= !(ECHO | IEXTEN | ICANON | ISIG);
+
+        raw.c_cc[VMIN] = 0;
+        raw.c_cc[VTIME] = 1;
+
+        tcsetattr(0, TCSAFLUSH, &raw)
+    }
+
+    /// The settings raw mode replaced.
+    pub fn original(&self) -> Termios {
+        self.original
+    }
+
+    /// Puts the original settings back now, for paths that exit the
+    /// process without unwinding and for running commands.
+    pub fn restore(&self) {
+        let _ = tcsetattr(0, TCSAFLUSH, &self.original);
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        self.restore();
+    }
+}
+
+/// Last terminating signal received, or 0. Set from the signal handler and
+/// acted upon by the event loop.
+pub(crate) static PENDING_SIGNAL: AtomicUsize = AtomicUsize::new(0);
+
+pub(crate) extern "C" fn on_terminate(signal: libc::c_int) {
+    PENDING_SIGNAL.store(signal as usize, Ordering::SeqCst);
+    wake();
+}
+
+/// Set on SIGWINCH until the event loop has picked up the new size.
+static RESIZED: AtomicBool = AtomicBool::new(false);
+
+pub(crate) extern "C" fn on_resize(_: libc::c_int) {
+    RESIZED.store(true, Ordering::SeqCst);
+    wake();
+}
+
+/// Write end of the pipe the handlers write to. A signal only interrupts
+/// `poll()` when the event loop is already blocked in it; one that arrives
+/// just before leaves a byte here that wakes it all the same.
+static SIGNAL_PIPE: AtomicI32 = AtomicI32::new(-1);
+
+fn wake() {
+    let fd = SIGNAL_PIPE.load(Ordering::SeqCst);
+    if fd >= 0 {
+        // A full pipe already has a wakeup waiting.
+        unsafe { libc::write(fd, b"\x01".as_ptr() as *const libc::c_void, 1) };
+    }
+}
+
+/// Sets up the pipe the signal handlers wake the event loop through,
+/// returning its read end for `poll` to wait on.
+pub(crate) fn signal_pipe() -> io::Result<File> {
+    let (read, write) = watch::pipe()?;
+    // Kept open for as long as the handlers may run, which is until exit.
+    SIGNAL_PIPE.store(write.into_raw_fd(), Ordering::SeqCst);
+    Ok(read)
+}
+
+impl Editor {
+    pub(crate) fn set_window_size(&mut self, s: termsize::Size) {
+        self.numcols = (s.cols as usize).max(1);
+        self.screenrows = s.rows as usize;
+        self.layout();
+    }
+
+    /// Picks up a new terminal size after SIGWINCH. The next refresh
+    /// scrolls the cursor back into view; the screen is cleared so nothing
+    /// drawn at the old size is left behind.
+    pub(crate) fn handle_resize(&mut self) -> Result<()> {
+        if !RESIZED.swap(false, Ordering::SeqCst) {
+            return Ok(());
+        }
+        if let Some(s) = termsize::get() {
+            self.set_window_size(s);
+        }
+        self.rowoff = self.rowoff.min(self.cy);
+        self.wrapoff = 0;
+        self.coloff = self.coloff.min(self.rx);
+        self.shown_rowoff = None;
+        self.redraw()
+    }
+
+    pub(crate) fn set_mouse_capture(&mut self, enabled: bool) -> Result<()> {
+        self.mouse = enabled;
+        if enabled {
+            self.write("\x1b[?1000h\x1b[?1006h")
+        } else {
+            self.write("\x1b[?1006l\x1b[?1000l")
+        }
+    }
+
+    fn get_cursor_position(&mut self) -> Result<(u16, u16)> {
+        self.write("\x1b[6n")?;
+        self.write("\r\n")?;
+        
+        let mut charbuf = [0; 1];
+        let mut buf = [0; 32];
+        let mut i = 0;
+        while i < buf.len() {
+            if self.stdin.read(&mut charbuf)? == 0 {
+                break;
+            }
+            if charbuf[0] == b'R' { break }
+            buf[i] = charbuf[0];
+            i += 1;
+        }
+        if buf[0] != b'\x1b' || buf[1] != b'[' {
+            return Err(io::Error::other("Terminal error"));
+        }
+        let dims : Vec<_> = str::from_utf8(&buf[2..i]).unwrap().split(";").collect();
+        if dims.len() != 2 {
+            return Err(io::Error::other("Terminal error"));
+        }
+
+        Ok((dims[0].parse().unwrap(), dims[1].parse().unwrap()))
+    }
+
+    pub(crate) fn get_window_size(&mut self) -> Result<termsize::Size> {
+        match termsize::get() {
+            Some(s) => Ok(s),
+            _ => {
+                self.write("\x1b[999C\x1b[999B")?; 
+                let (rows, cols) = self.get_cursor_position()?;
+                Ok(termsize::Size { rows, cols })
+            }
+        }
+    }
+
+    pub(crate) fn write<S: AsRef<str>>(&mut self, text: S) -> Result<()> {
+        let start = Instant::now();
+        let mut stdout = self.stdout.lock();
+        stdout.write_all(text.as_ref().as_bytes())?;
+        stdout.flush()?;
+        self.frame_flush += start.elapsed();
+        self.frame_bytes += text.as_ref().len();
+        Ok(())
+    }
+
+    /// Gives the terminal back the way we found it, for a command to use,
+    /// until `resume_terminal`.
+    pub(crate) fn suspend_terminal(&mut self) {
+        let mouse = self.mouse;
+        self.restore_terminal();
+        self.mouse = mouse;
+        let _ = self.write("\x1b[?25h");
+        self.raw.restore();
+    }
+
+    pub(crate) fn resume_terminal(&mut self) -> Result<()> {
+        self.raw.resume()?;
+        if self.alt_screen {
+            self.write("\x1b[?1049h")?;
+        }
+        let mouse = self.mouse;
+        self.set_mouse_capture(mouse)?;
+        self.write("\x1b[?1004h\x1b[?2004h")?;
+        self.redraw()
+    }
+
+    pub(crate) fn restore_terminal(&mut self) {
+        // Errors are ignored here: we are on our way out and have nowhere
+        // better to report them.
+        if self.mouse {
+            let _ = self.set_mouse_capture(false);
+        }
+        let _ = self.write("\x1b[?2004l\x1b[?1004l");
+        let _ = if self.alt_screen {
+            self.write("\x1b[?1049l")
+        } else {
+            self.write("\x1b[2J\x1b[H")
+        };
+    }
+}
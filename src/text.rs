@@ -0,0 +1,385 @@
+//! Where the text of a buffer lives. Rows are kept in a gap buffer: a
+//! vector with a run of unused slots at the last place rows were added or
+//! removed. Typing a line at a time, or deleting a few, only moves the rows
+//! between the gap and the edit, rather than shifting every row after it as
+//! a plain vector would in a file of millions of lines.
+//!
+//! Each row is its own `String`, edited in place, so the cost of an edit
+//! within a row grows with the length of that row and not of the file.
+
+use std::iter::{Chain, FromIterator};
+use std::ops::{Index, IndexMut, Range};
+use std::{mem, slice};
+
+use undo;
+
+/// Text as rows, each followed by an implicit newline. Positions are
+/// `(row, byte)` pairs, as in the undo history.
+pub trait TextBuffer {
+    /// The text between `start` and `end`.
+    fn text_between(&self, start: (usize, usize), end: (usize, usize)) -> String;
+
+    /// Inserts `text` at `at`, returning the position just past it.
+    fn insert_text(&mut self, at: (usize, usize), text: &str) -> (usize, usize);
+
+    /// Removes the text between `start` and `end`, returning it.
+    fn remove_text(&mut self, start: (usize, usize), end: (usize, usize)) -> String;
+}
+
+/// Slots added when the gap fills up, at least.
+const MIN_GAP: usize = 64;
+
+pub type Iter<'a> = Chain<slice::Iter<'a, String>, slice::Iter<'a, String>>;
+
+#[derive(Clone, Default)]
+pub struct Rows {
+    buf: Vec<String>,
+    /// Where the unused slots start.
+    gap: usize,
+    /// How many there are, all empty strings.
+    gap_len: usize,
+}
+
+impl Rows {
+    pub fn new() -> Rows {
+        Rows::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.buf.len() - self.gap_len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Slots allocated, used or not.
+    pub fn capacity(&self) -> usize {
+        self.buf.capacity()
+    }
+
+    fn slot(&self, y: usize) -> usize {
+        if y < self.gap { y } else { y + self.gap_len }
+    }
+
+    pub fn get(&self, y: usize) -> Option<&String> {
+        if y < self.len() { Some(&self.buf[self.slot(y)]) } else { None }
+    }
+
+    pub fn get_mut(&mut self, y: usize) -> Option<&mut String> {
+        if y < self.len() {
+            let slot = self.slot(y);
+            Some(&mut self.buf[slot])
+        } else {
+            None
+        }
+    }
+
+    pub fn iter<'a>(&'a self) -> Iter<'a> {
+        self.buf[..self.gap].iter().chain(self.buf[self.gap + self.gap_len..].iter())
+    }
+
+    /// Moves the gap to just before row `y`.
+    fn move_gap(&mut self, y: usize) {
+        while y < self.gap {
+            self.gap -= 1;
+            self.buf.swap(self.gap, self.gap + self.gap_len);
+        }
+        while y > self.gap {
+            self.buf.swap(self.gap, self.gap + self.gap_len);
+            self.gap += 1;
+        }
+    }
+
+    /// Makes room for at least `n` more rows, doubling the space so pushing
+    /// row after row stays cheap.
+    fn reserve(&mut self, n: usize) {
+        if self.gap_len >= n {
+            return;
+        }
+        let len = self.len();
+        self.move_gap(len);
+        let extra = n.max(len).max(MIN_GAP);
+        self.buf.resize(self.buf.len() + extra, String::new());
+        self.gap_len += extra;
+    }
+
+    pub fn insert(&mut self, y: usize, row: String) {
+        assert!(y <= self.len(), "row {} out of range", y);
+        self.reserve(1);
+        self.move_gap(y);
+        self.buf[self.gap] = row;
+        self.gap += 1;
+        self.gap_len -= 1;
+    }
+
+    pub fn push(&mut self, row: String) {
+        let len = self.len();
+        self.insert(len, row);
+    }
+
+    pub fn remove(&mut self, y: usize) -> String {
+        assert!(y < self.len(), "row {} out of range", y);
+        self.move_gap(y);
+        let row = mem::take(&mut self.buf[self.gap + self.gap_len]);
+        self.gap_len += 1;
+        row
+    }
+
+    pub fn pop(&mut self) -> Option<String> {
+        match self.len() {
+            0 => None,
+            len => Some(self.remove(len - 1)),
+        }
+    }
+
+    /// Removes the rows in `range`, returning them.
+    pub fn drain(&mut self, range: Range<usize>) -> Vec<String> {
+        assert!(range.start <= range.end && range.end <= self.len(), "rows {:?} out of range", range);
+        self.move_gap(range.start);
+        let start = self.gap + self.gap_len;
+        let rows = self.buf[start..start + range.len()].iter_mut()
+            .map(mem::take)
+            .collect();
+        self.gap_len += range.len();
+        rows
+    }
+
+    pub fn join(&self, sep: &str) -> String {
+        let mut text = String::with_capacity(self.iter().map(|row| row.len() + sep.len()).sum());
+        for (y, row) in self.iter().enumerate() {
+            if y > 0 {
+                text += sep;
+            }
+            text += row;
+        }
+        text
+    }
+}
+
+impl Index<usize> for Rows {
+    type Output = String;
+
+    fn index(&self, y: usize) -> &String {
+        match self.get(y) {
+            Some(row) => row,
+            None => panic!("row {} out of range for {} rows", y, self.len()),
+        }
+    }
+}
+
+impl IndexMut<usize> for Rows {
+    fn index_mut(&mut self, y: usize) -> &mut String {
+        let len = self.len();
+        match self.get_mut(y) {
+            Some(row) => row,
+            None => panic!("row {} out of range for {} rows", y, len),
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a Rows {
+    type Item = &'a String;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
+
+impl Extend<String> for Rows {
+    fn extend<I: IntoIterator<Item = String>>(&mut self, rows: I) {
+        for row in rows {
+            self.push(row);
+        }
+    }
+}
+
+impl FromIterator<String> for Rows {
+    fn from_iter<I: IntoIterator<Item = String>>(rows: I) -> Rows {
+        Rows::from(rows.into_iter().collect::<Vec<_>>())
+    }
+}
+
+impl From<Vec<String>> for Rows {
+    fn from(buf: Vec<String>) -> Rows {
+        Rows { gap: buf.len(), gap_len: 0, buf }
+    }
+}
+
+impl TextBuffer for Rows {
+    fn text_between(&self, start: (usize, usize), end: (usize, usize)) -> String {
+        let ((sy, sx), (ey, ex)) = (start, end);
+        if sy == ey {
+            return self[sy][sx..ex].to_string();
+        }
+        let mut text = self[sy][sx..].to_string();
+        for row in self.iter().skip(sy + 1).take(ey - sy - 1) {
+            text.push('\n');
+            text += row;
+        }
+        text.push('\n');
+        if ey < self.len() {
+            text += &self[ey][..ex];
+        }
+        text
+    }
+
+    fn insert_text(&mut self, at: (usize, usize), text: &str) -> (usize, usize) {
+        if text.is_empty() {
+            return at;
+        }
+        let (y, x) = at;
+        let at_end = y == self.len();
+        if at_end {
+            self.push(String::new());
+        }
+        let tail = self[y].split_off(x);
+        let mut lines = text.split('\n');
+        self[y] += lines.next().unwrap_or("");
+        let mut row = y;
+        for line in lines {
+            row += 1;
+            self.insert(row, line.to_string());
+        }
+        self[row] += &tail;
+        // Text ending in a newline at the end of the buffer is complete rows;
+        // don't leave an extra empty one behind.
+        if at_end && text.ends_with('\n') {
+            self.pop();
+        }
+        undo::end_of(at, text)
+    }
+
+    fn remove_text(&mut self, start: (usize, usize), end: (usize, usize)) -> String {
+        if start == end {
+            return String::new();
+        }
+        let ((sy, sx), (ey, ex)) = (start, end);
+        if sy == ey {
+            return self[sy].drain(sx..ex).collect();
+        }
+        let mut text = self[sy].split_off(sx);
+        let last = ey.min(self.len());
+        for row in self.drain(sy + 1..last) {
+            text.push('\n');
+            text += &row;
+        }
+        text.push('\n');
+        if sy + 1 < self.len() {
+            let tail = self.remove(sy + 1);
+            text += &tail[..ex];
+            self[sy] += &tail[ex..];
+        } else if sx == 0 {
+            self.remove(sy);
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(lines: &[&str]) -> Rows {
+        lines.iter().map(|line| line.to_string()).collect()
+    }
+
+    fn lines(rows: &Rows) -> Vec<&str> {
+        rows.iter().map(|row| row.as_str()).collect()
+    }
+
+    #[test]
+    fn gap_follows_edits() {
+        let mut r = Rows::new();
+        assert!(r.is_empty());
+        assert_eq!(r.pop(), None);
+        for i in 0..100 {
+            r.push(i.to_string());
+        }
+        r.insert(0, "first".to_string());
+        r.insert(50, "middle".to_string());
+        assert_eq!(r.len(), 102);
+        assert_eq!(r[0], "first");
+        assert_eq!(r[50], "middle");
+        assert_eq!(r[51], "49");
+        assert_eq!(r.remove(50), "middle");
+        assert_eq!(r.drain(1..98), (0..97).map(|i| i.to_string()).collect::<Vec<_>>());
+        assert_eq!(lines(&r), ["first", "97", "98", "99"]);
+        assert_eq!(r.pop(), Some("99".to_string()));
+        r[1].push('!');
+        assert_eq!(r.get(1).map(|row| row.as_str()), Some("97!"));
+        assert_eq!(r.get(3), None);
+        assert_eq!(r.join("\n"), "first\n97!\n98");
+    }
+
+    #[test]
+    fn text_between_spans_rows() {
+        let r = rows(&["hello", "big", "world"]);
+        assert_eq!(r.text_between((0, 1), (0, 4)), "ell");
+        assert_eq!(r.text_between((0, 3), (2, 2)), "lo\nbig\nwo");
+        assert_eq!(r.text_between((1, 0), (3, 0)), "big\nworld\n");
+    }
+
+    #[test]
+    fn insert_within_and_across_rows() {
+        let mut r = rows(&["hello", "world"]);
+        assert_eq!(r.insert_text((0, 5), ","), (0, 6));
+        assert_eq!(r.insert_text((0, 3), "p\nhel"), (1, 3));
+        assert_eq!(lines(&r), ["help", "hello,", "world"]);
+        assert_eq!(r.insert_text((2, 0), "\n"), (3, 0));
+        assert_eq!(lines(&r), ["help", "hello,", "", "world"]);
+        assert_eq!(r.insert_text((1, 1), ""), (1, 1));
+    }
+
+    #[test]
+    fn insert_at_buffer_end() {
+        let mut r = rows(&["a"]);
+        assert_eq!(r.insert_text((1, 0), "b\nc\n"), (3, 0));
+        assert_eq!(lines(&r), ["a", "b", "c"]);
+        assert_eq!(r.insert_text((3, 0), "d"), (3, 1));
+        assert_eq!(lines(&r), ["a", "b", "c", "d"]);
+
+        let mut r = Rows::new();
+        assert_eq!(r.insert_text((0, 0), "\n"), (1, 0));
+        assert_eq!(lines(&r), [""]);
+    }
+
+    #[test]
+    fn remove_within_and_across_rows() {
+        let mut r = rows(&["hello", "big", "world"]);
+        assert_eq!(r.remove_text((0, 1), (0, 3)), "el");
+        assert_eq!(r.remove_text((0, 1), (2, 1)), "lo\nbig\nw");
+        assert_eq!(lines(&r), ["horld"]);
+        assert_eq!(r.remove_text((0, 2), (0, 2)), "");
+    }
+
+    #[test]
+    fn remove_joins_at_a_newline() {
+        let mut r = rows(&["one", "two"]);
+        assert_eq!(r.remove_text((0, 3), (1, 0)), "\n");
+        assert_eq!(lines(&r), ["onetwo"]);
+    }
+
+    #[test]
+    fn remove_to_buffer_end() {
+        let mut r = rows(&["a", "b", "c"]);
+        assert_eq!(r.remove_text((1, 0), (3, 0)), "b\nc\n");
+        assert_eq!(lines(&r), ["a"]);
+        let mut r = rows(&["a", "b"]);
+        assert_eq!(r.remove_text((0, 1), (2, 0)), "\nb\n");
+        assert_eq!(lines(&r), ["a"]);
+    }
+
+    #[test]
+    fn insert_undoes_remove() {
+        let original = ["fn main() {", "    go();", "}"];
+        let spans = [((0, 3), (2, 0)), ((0, 11), (1, 4)), ((1, 0), (3, 0)), ((0, 0), (3, 0))];
+        for &(start, end) in &spans {
+            let mut r = rows(&original);
+            let text = r.remove_text(start, end);
+            assert_eq!(r.insert_text(start, &text), end);
+            assert_eq!(lines(&r), original);
+        }
+    }
+}
@@ -0,0 +1,315 @@
+//! Undo history. Edits are kept as deltas -- where the change happened and
+//! the text it removed and inserted -- never as copies of rows, so the cost
+//! of the history grows with the amount typed rather than the file size.
+//!
+//! Positions are `(row, byte)` pairs into the editor's rows, where every row
+//! is followed by an implicit newline.
+
+use std::collections::VecDeque;
+use std::mem;
+
+/// Undo steps kept before the oldest ones are dropped.
+pub const DEFAULT_MAX_EDITS: usize = 10_000;
+/// Bytes of removed and inserted text kept before the oldest steps are
+/// dropped.
+pub const DEFAULT_MAX_BYTES: usize = 16 << 20;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edit {
+    pub at: (usize, usize),
+    pub removed: String,
+    pub inserted: String,
+}
+
+impl Edit {
+    fn size(&self) -> usize {
+        mem::size_of::<Edit>() + self.removed.len() + self.inserted.len()
+    }
+
+    /// Folds `next` into this edit when it continues typing or deleting on
+    /// the same line.
+    fn merge(&mut self, next: &Edit) -> bool {
+        if next.inserted.contains('\n') || next.removed.contains('\n') {
+            return false;
+        }
+        if self.inserted.contains('\n') || self.removed.contains('\n') {
+            return false;
+        }
+        if self.removed.is_empty() && next.removed.is_empty()
+            && next.at == end_of(self.at, &self.inserted) {
+            self.inserted += &next.inserted;
+            return true;
+        }
+        if self.inserted.is_empty() && next.inserted.is_empty() {
+            if end_of(next.at, &next.removed) == self.at {
+                self.removed.insert_str(0, &next.removed);
+                self.at = next.at;
+                return true;
+            }
+            if next.at == self.at {
+                self.removed += &next.removed;
+                return true;
+            }
+        }
+        false
+    }
+}
+
+/// Edits undone and redone together, in the order they were made.
+pub type Group = Vec<Edit>;
+
+fn group_size(group: &Group) -> usize {
+    group.iter().map(Edit::size).sum()
+}
+
+pub struct History {
+    undo: VecDeque<Group>,
+    redo: Vec<Group>,
+    bytes: usize,
+    /// How many `begin_group` calls are waiting for their `end_group`.
+    depth: usize,
+    /// Nothing has been recorded yet in the group being built.
+    fresh: bool,
+    pub max_edits: usize,
+    pub max_bytes: usize,
+}
+
+impl History {
+    pub fn new(max_edits: usize, max_bytes: usize) -> History {
+        History {
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            bytes: 0,
+            depth: 0,
+            fresh: false,
+            max_edits,
+            max_bytes,
+        }
+    }
+
+    /// Approximate memory held by the history.
+    pub fn bytes(&self) -> usize {
+        self.bytes + self.redo.iter().map(group_size).sum::<usize>()
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.bytes = 0;
+    }
+
+    /// Starts a group: edits recorded until the matching `end_group` are
+    /// one undo step. Groups may nest; the outermost one counts.
+    pub fn begin_group(&mut self) {
+        if self.depth == 0 {
+            self.fresh = true;
+        }
+        self.depth += 1;
+    }
+
+    pub fn end_group(&mut self) {
+        self.depth = self.depth.saturating_sub(1);
+    }
+
+    pub fn record(&mut self, edit: Edit) {
+        if edit.removed.is_empty() && edit.inserted.is_empty() {
+            return;
+        }
+        self.redo.clear();
+        // Later edits of a group always join it. The first one only joins
+        // the step before when that was a single edit it continues, so
+        // typing a word is one step even though each key is a group.
+        let join = self.depth > 0 && !self.fresh;
+        self.fresh = false;
+        let before = self.bytes;
+        match self.undo.back_mut() {
+            Some(last) if join || last.len() == 1 => {
+                let size = group_size(last);
+                let merged = last.last_mut().is_some_and(|prev| prev.merge(&edit));
+                if merged || join {
+                    if !merged {
+                        last.push(edit);
+                    }
+                    self.bytes = before - size + group_size(last);
+                    self.trim();
+                    return;
+                }
+            }
+            _ => {}
+        }
+        self.bytes += edit.size();
+        self.undo.push_back(vec![edit]);
+        self.trim();
+    }
+
+    fn trim(&mut self) {
+        while self.undo.len() > self.max_edits || (self.bytes > self.max_bytes && self.undo.len() > 1) {
+            match self.undo.pop_front() {
+                Some(group) => self.bytes -= group_size(&group),
+                None => break,
+            }
+        }
+    }
+
+    /// Takes the latest step to be reverted by the caller, last edit first.
+    pub fn undo(&mut self) -> Option<Group> {
+        let group = self.undo.pop_back()?;
+        self.bytes -= group_size(&group);
+        self.redo.push(group.clone());
+        Some(group)
+    }
+
+    /// Takes the latest undone step to be applied again by the caller, in
+    /// order.
+    pub fn redo(&mut self) -> Option<Group> {
+        let group = self.redo.pop()?;
+        self.bytes += group_size(&group);
+        self.undo.push_back(group.clone());
+        self.trim();
+        Some(group)
+    }
+}
+
+/// The position just past `text` when inserted at `at`.
+pub fn end_of(at: (usize, usize), text: &str) -> (usize, usize) {
+    match text.rfind('\n') {
+        Some(i) => (at.0 + text.matches('\n').count(), text.len() - i - 1),
+        None => (at.0, at.1 + text.len()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use text::{Rows, TextBuffer};
+
+    fn edit(at: (usize, usize), removed: &str, inserted: &str) -> Edit {
+        Edit { at, removed: removed.to_string(), inserted: inserted.to_string() }
+    }
+
+    /// Makes `edit` on `rows` and records it, as the editor does.
+    fn apply(rows: &mut Rows, history: &mut History, edit: Edit) {
+        rows.remove_text(edit.at, end_of(edit.at, &edit.removed));
+        rows.insert_text(edit.at, &edit.inserted);
+        history.record(edit);
+    }
+
+    fn undo(rows: &mut Rows, history: &mut History) -> bool {
+        match history.undo() {
+            Some(group) => {
+                for edit in group.iter().rev() {
+                    rows.remove_text(edit.at, end_of(edit.at, &edit.inserted));
+                    rows.insert_text(edit.at, &edit.removed);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn redo(rows: &mut Rows, history: &mut History) -> bool {
+        match history.redo() {
+            Some(group) => {
+                for edit in &group {
+                    rows.remove_text(edit.at, end_of(edit.at, &edit.removed));
+                    rows.insert_text(edit.at, &edit.inserted);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn text(rows: &Rows) -> String {
+        rows.join("\n")
+    }
+
+    #[test]
+    fn end_of_counts_rows_and_bytes() {
+        assert_eq!(end_of((2, 3), ""), (2, 3));
+        assert_eq!(end_of((2, 3), "ab"), (2, 5));
+        assert_eq!(end_of((2, 3), "ab\ncd\n"), (4, 0));
+        assert_eq!(end_of((2, 3), "ab\nxyz"), (3, 3));
+    }
+
+    #[test]
+    fn typing_on_one_line_is_one_step() {
+        let mut history = History::new(DEFAULT_MAX_EDITS, DEFAULT_MAX_BYTES);
+        let mut rows = Rows::from(vec!["x".to_string()]);
+        for (i, c) in "abc".chars().enumerate() {
+            history.begin_group();
+            apply(&mut rows, &mut history, edit((0, i), "", &c.to_string()));
+            history.end_group();
+        }
+        assert_eq!(text(&rows), "abcx");
+        assert!(undo(&mut rows, &mut history));
+        assert_eq!(text(&rows), "x");
+        assert!(!undo(&mut rows, &mut history));
+        assert!(redo(&mut rows, &mut history));
+        assert_eq!(text(&rows), "abcx");
+        assert!(!redo(&mut rows, &mut history));
+    }
+
+    #[test]
+    fn backspacing_merges_backwards() {
+        let mut history = History::new(DEFAULT_MAX_EDITS, DEFAULT_MAX_BYTES);
+        let mut rows = Rows::from(vec!["hello".to_string()]);
+        apply(&mut rows, &mut history, edit((0, 4), "o", ""));
+        apply(&mut rows, &mut history, edit((0, 3), "l", ""));
+        apply(&mut rows, &mut history, edit((0, 0), "h", ""));
+        assert_eq!(text(&rows), "el");
+        assert!(undo(&mut rows, &mut history));
+        assert_eq!(text(&rows), "hel");
+        assert!(undo(&mut rows, &mut history));
+        assert_eq!(text(&rows), "hello");
+    }
+
+    #[test]
+    fn groups_round_trip_across_lines() {
+        let mut history = History::new(DEFAULT_MAX_EDITS, DEFAULT_MAX_BYTES);
+        let mut rows = Rows::from(vec!["one".to_string(), "two".to_string()]);
+        history.begin_group();
+        apply(&mut rows, &mut history, edit((1, 3), "", "\nthree"));
+        history.begin_group();
+        apply(&mut rows, &mut history, edit((0, 0), "one\n", ""));
+        history.end_group();
+        history.end_group();
+        apply(&mut rows, &mut history, edit((0, 0), "", "zero\n"));
+        assert_eq!(text(&rows), "zero\ntwo\nthree");
+
+        assert!(undo(&mut rows, &mut history));
+        assert_eq!(text(&rows), "two\nthree");
+        assert!(undo(&mut rows, &mut history));
+        assert_eq!(text(&rows), "one\ntwo");
+        assert!(redo(&mut rows, &mut history));
+        assert_eq!(text(&rows), "two\nthree");
+
+        // A new edit drops what could have been redone.
+        apply(&mut rows, &mut history, edit((0, 0), "t", "T"));
+        assert!(!redo(&mut rows, &mut history));
+        assert!(undo(&mut rows, &mut history));
+        assert!(undo(&mut rows, &mut history));
+        assert_eq!(text(&rows), "one\ntwo");
+    }
+
+    #[test]
+    fn limits_drop_the_oldest_steps() {
+        let mut history = History::new(2, DEFAULT_MAX_BYTES);
+        let mut rows = Rows::from(vec![String::new()]);
+        for line in 0..3 {
+            apply(&mut rows, &mut history, edit((line, 0), "", "x\n"));
+        }
+        assert!(undo(&mut rows, &mut history));
+        assert!(undo(&mut rows, &mut history));
+        assert!(!undo(&mut rows, &mut history));
+        assert_eq!(text(&rows), "x\n");
+
+        let mut history = History::new(DEFAULT_MAX_EDITS, 1);
+        history.record(edit((0, 0), "", "a\n"));
+        history.record(edit((1, 0), "", "b\n"));
+        assert_eq!(history.bytes(), mem::size_of::<Edit>() + 2);
+        history.clear();
+        assert_eq!(history.bytes(), 0);
+        assert!(history.undo().is_none());
+    }
+}
@@ -0,0 +1,791 @@
+//! Keys that work as in vi, for fingers that expect them. Turned on with
+//! `vim = true` in the settings, `:set vim`, or `--vim` on the command line.
+//!
+//! Normal mode moves with `h` `j` `k` `l`, `w` `b` `e`, `0` `^` `$`, `gg`
+//! and `G`, each taking a count. The operators `d`, `c` and `y` take a
+//! motion after them, or are doubled for whole lines, and `x`, `X`, `D`,
+//! `C`, `Y` and `s` are the usual short forms. `i` `a` `I` `A` `o` `O` go
+//! into insert mode, where keys work as they do without vi mode until Esc.
+//! `v` and `V` select characters or lines for an operator, `p` and `P` put
+//! back what was deleted or copied, `"x` picks a register, `u` and Ctrl-R
+//! undo and redo, Ctrl-O and Ctrl-I go back and forward through the jump
+//! list, `mx` sets mark `x` and `'x` or `` `x `` goes to it, and `:` and
+//! `/` open the command and search prompts.
+//! Keys that aren't printable, like Ctrl-S and the arrows, keep their
+//! bindings in every mode.
+
+use std::io::Result;
+
+use edit::{is_word_char, next_boundary, prev_boundary};
+use editor::Editor;
+use input::Key;
+use keymap::Action;
+use text::Rows;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Normal,
+    Insert,
+    Visual,
+    VisualLine,
+}
+
+impl Mode {
+    pub fn name(self) -> &'static str {
+        match self {
+            Mode::Normal => "NORMAL",
+            Mode::Insert => "INSERT",
+            Mode::Visual => "VISUAL",
+            Mode::VisualLine => "V-LINE",
+        }
+    }
+}
+
+pub(crate) struct Vim {
+    pub mode: Mode,
+    /// The count typed so far.
+    count: Option<usize>,
+    /// An operator waiting for its motion, with the count typed before it.
+    operator: Option<(char, usize)>,
+    /// The first key of a two-key command, as `g` of `gg`, `"` of `"a` or
+    /// `m` of `ma`.
+    pending: Option<char>,
+    register: Option<char>,
+    /// Where the selection started in visual mode.
+    visual: (usize, usize),
+}
+
+/// Where a motion goes and how an operator takes the text it passes over.
+struct Motion {
+    to: (usize, usize),
+    /// Whole lines, as `j` and `G` move.
+    linewise: bool,
+    /// Takes in the character it ends on, as `e` and `$` do.
+    inclusive: bool,
+}
+
+/// Blank, word, punctuation or empty line: what `w`, `b` and `e` go by.
+/// The end of a line counts as blank, except an empty line is a word of
+/// its own.
+fn class(rows: &Rows, at: (usize, usize)) -> u8 {
+    match rows[at.0][at.1..].chars().next() {
+        None if rows[at.0].is_empty() => 3,
+        None => 0,
+        Some(c) if c.is_whitespace() => 0,
+        Some(c) if is_word_char(c) => 1,
+        Some(_) => 2,
+    }
+}
+
+/// The position after `at`, going on to the next line from the end of one.
+fn next(rows: &Rows, at: (usize, usize)) -> Option<(usize, usize)> {
+    if at.1 < rows[at.0].len() {
+        Some((at.0, next_boundary(&rows[at.0], at.1)))
+    } else if at.0 + 1 < rows.len() {
+        Some((at.0 + 1, 0))
+    } else {
+        None
+    }
+}
+
+fn prev(rows: &Rows, at: (usize, usize)) -> Option<(usize, usize)> {
+    if at.1 > 0 {
+        Some((at.0, prev_boundary(&rows[at.0], at.1)))
+    } else if at.0 > 0 {
+        Some((at.0 - 1, rows[at.0 - 1].len()))
+    } else {
+        None
+    }
+}
+
+/// The start of the next word.
+fn word_forward(rows: &Rows, mut at: (usize, usize)) -> (usize, usize) {
+    let start = class(rows, at);
+    if start != 0 {
+        loop {
+            at = match next(rows, at) {
+                Some(n) => n,
+                None => return at,
+            };
+            if start == 3 || class(rows, at) != start {
+                break;
+            }
+        }
+    }
+    while class(rows, at) == 0 {
+        at = match next(rows, at) {
+            Some(n) => n,
+            None => break,
+        };
+    }
+    at
+}
+
+/// The start of this word, or of the one before when already there.
+fn word_backward(rows: &Rows, at: (usize, usize)) -> (usize, usize) {
+    let mut at = match prev(rows, at) {
+        Some(p) => p,
+        None => return at,
+    };
+    while class(rows, at) == 0 {
+        at = match prev(rows, at) {
+            Some(p) => p,
+            None => return at,
+        };
+    }
+    let class_of = class(rows, at);
+    if class_of == 3 {
+        return at;
+    }
+    while let Some(p) = prev(rows, at) {
+        if class(rows, p) != class_of {
+            break;
+        }
+        at = p;
+    }
+    at
+}
+
+/// The last character of this word, or of the next when already there.
+fn word_end(rows: &Rows, at: (usize, usize)) -> (usize, usize) {
+    let mut at = match next(rows, at) {
+        Some(n) => n,
+        None => return at,
+    };
+    while class(rows, at) == 0 || class(rows, at) == 3 {
+        at = match next(rows, at) {
+            Some(n) => n,
+            None => return at,
+        };
+    }
+    let class_of = class(rows, at);
+    while let Some(n) = next(rows, at) {
+        if n.0 != at.0 || class(rows, n) != class_of {
+            break;
+        }
+        at = n;
+    }
+    at
+}
+
+/// Where the text of `row` starts after its indentation.
+fn first_nonblank(row: &str) -> usize {
+    row.len() - row.trim_start_matches([' ', '\t']).len()
+}
+
+impl Vim {
+    fn new() -> Vim {
+        Vim { mode: Mode::Normal, count: None, operator: None, pending: None, register: None, visual: (0, 0) }
+    }
+
+    /// Forgets a command half typed.
+    fn reset(&mut self) {
+        self.count = None;
+        self.operator = None;
+        self.pending = None;
+        self.register = None;
+    }
+}
+
+impl Editor {
+    /// Turns vi keys on or off.
+    pub(crate) fn set_vim(&mut self, on: bool) {
+        if on && self.vim.is_none() {
+            self.vim = Some(Vim::new());
+            self.vim_clamp();
+        } else if !on {
+            self.vim = None;
+        }
+    }
+
+    /// Handles a key the way vi would in the current mode. False for keys
+    /// left to their usual bindings.
+    pub(crate) fn vim_key(&mut self, c: Key, action: Option<Action>) -> Result<bool> {
+        let mode = match self.vim {
+            // The hex view has keys of its own.
+            Some(ref vim) if self.hex.is_none() => vim.mode,
+            _ => return Ok(false),
+        };
+        if mode == Mode::Insert {
+            if c != Key::Char(b'\x1b') || self.completion_menu.is_some() {
+                return Ok(false);
+            }
+            self.snippet = None;
+            self.vim_mode(Mode::Normal);
+            if self.cx > 0 {
+                self.cx = prev_boundary(&self.rows[self.cy], self.cx);
+            }
+            return Ok(true);
+        }
+        let key = match c {
+            Key::Char(b) if (0x20..0x7f).contains(&b) => b as char,
+            Key::Char(b'\x1b') => {
+                self.vim.as_mut().unwrap().reset();
+                if mode != Mode::Normal {
+                    self.vim_mode(Mode::Normal);
+                }
+                return Ok(true);
+            }
+            Key::Ctrl(b'r') => {
+                self.redo();
+                self.vim_clamp();
+                return Ok(true);
+            }
+            Key::Ctrl(b'o') => {
+                self.jump_back()?;
+                self.vim_clamp();
+                return Ok(true);
+            }
+            Key::Ctrl(b'i') => {
+                self.jump_forward()?;
+                self.vim_clamp();
+                return Ok(true);
+            }
+            Key::Return | Key::Down => 'j',
+            Key::Up => 'k',
+            Key::Left | Key::Backspace => 'h',
+            Key::Right => 'l',
+            // The rest keep their bindings; other control characters are
+            // nothing to vi.
+            Key::Char(_) if action.is_none() => return Ok(true),
+            _ => return Ok(false),
+        };
+        self.vim_command(key, mode)?;
+        if let Some(ref vim) = self.vim {
+            match vim.mode {
+                Mode::Visual => self.anchor = Some(vim.visual),
+                Mode::VisualLine => self.anchor = Some((vim.visual.0, 0)),
+                _ => {}
+            }
+        }
+        Ok(true)
+    }
+
+    fn vim_mode(&mut self, mode: Mode) {
+        let at = (self.cy, self.cx);
+        {
+            let vim = self.vim.as_mut().unwrap();
+            vim.mode = mode;
+            vim.visual = at;
+            vim.reset();
+        }
+        if mode == Mode::Normal {
+            self.anchor = None;
+            self.vim_clamp();
+        }
+    }
+
+    /// Keeps the cursor on a character, as vi does outside insert mode.
+    fn vim_clamp(&mut self) {
+        if self.rows.is_empty() {
+            return;
+        }
+        self.cy = self.cy.min(self.rows.len() - 1);
+        let row = &self.rows[self.cy];
+        if self.cx >= row.len() && !row.is_empty() {
+            self.cx = prev_boundary(row, row.len());
+        }
+    }
+
+    /// Runs the printable `key` typed in normal or visual mode.
+    fn vim_command(&mut self, key: char, mode: Mode) -> Result<()> {
+        // Two-key commands.
+        let pending = self.vim.as_mut().unwrap().pending.take();
+        match pending {
+            Some('"') => {
+                self.vim.as_mut().unwrap().register = Some(key);
+                return Ok(());
+            }
+            Some('m') => {
+                self.set_mark(key);
+                self.vim.as_mut().unwrap().reset();
+                return Ok(());
+            }
+            Some(mark) if mark == '\'' || mark == '`' => {
+                self.go_to_mark(key);
+                // `'` goes to the start of the line's text, `` ` `` to the
+                // very place.
+                if mark == '\'' {
+                    self.cx = first_nonblank(self.rows.get(self.cy).map_or("", |row| row.as_str()));
+                }
+                self.vim_clamp();
+                self.vim.as_mut().unwrap().reset();
+                return Ok(());
+            }
+            Some('g') if key == 'g' => {}
+            Some(_) => {
+                self.vim.as_mut().unwrap().reset();
+                return Ok(());
+            }
+            None => {}
+        }
+        let (count, operator) = {
+            let vim = self.vim.as_mut().unwrap();
+            if key.is_ascii_digit() && (key != '0' || vim.count.is_some()) {
+                let digit = key.to_digit(10).unwrap() as usize;
+                vim.count = Some(vim.count.unwrap_or(0).saturating_mul(10).saturating_add(digit));
+                return Ok(());
+            }
+            let mark = (key == 'm' || key == '\'' || key == '`') && vim.operator.is_none();
+            if (key == 'g' && pending.is_none()) || key == '"' || mark {
+                vim.pending = Some(key);
+                return Ok(());
+            }
+            (vim.count.take(), vim.operator)
+        };
+        let times = count.unwrap_or(1);
+        if self.rows.is_empty() && !"iaIAoOpP:/".contains(key) {
+            return Ok(());
+        }
+
+        // A motion, for the operator waiting or to move the cursor.
+        let motion_key = if pending == Some('g') { 'g' } else { key };
+        if self.vim_motion(motion_key, times, count, operator.map(|(op, _)| op)).is_some() {
+            match operator {
+                Some((op, before)) => {
+                    // Counts before and after the operator multiply.
+                    let count = if count.is_some() || before > 1 { Some(times * before) } else { None };
+                    if let Some(motion) = self.vim_motion(motion_key, times * before, count, Some(op)) {
+                        self.vim_operate(op, motion);
+                    }
+                }
+                None if motion_key == 'j' || motion_key == 'k' => {
+                    for _ in 0..times {
+                        let last = self.cy + 1 >= self.rows.len();
+                        if (motion_key == 'j' && last) || (motion_key == 'k' && self.cy == 0) {
+                            break;
+                        }
+                        self.move_cursor(if motion_key == 'j' { Key::Down } else { Key::Up });
+                    }
+                }
+                None => {
+                    let motion = self.vim_motion(motion_key, times, count, None).unwrap();
+                    if motion_key == 'g' || motion_key == 'G' {
+                        let here = (self.cy, self.cx);
+                        self.jump_from(here);
+                    }
+                    self.cy = motion.to.0;
+                    self.cx = motion.to.1;
+                }
+            }
+            if mode == Mode::Normal || self.vim.as_ref().is_some_and(|v| v.mode == Mode::Normal) {
+                self.vim_clamp();
+            }
+            if let Some(vim) = self.vim.as_mut() {
+                vim.reset();
+            }
+            return Ok(());
+        }
+
+        let register = self.vim.as_mut().unwrap().register.take();
+        if mode != Mode::Normal {
+            return self.vim_visual(key, mode, register);
+        }
+        match key {
+            'd' | 'c' | 'y' => match operator {
+                // Doubled, for whole lines.
+                Some((op, before)) if op == key => {
+                    let last = (self.cy + times * before - 1).min(self.rows.len() - 1);
+                    let to = (last, 0);
+                    self.vim.as_mut().unwrap().register = register;
+                    self.vim_operate(op, Motion { to, linewise: true, inclusive: false });
+                }
+                Some(_) => {}
+                None => {
+                    let vim = self.vim.as_mut().unwrap();
+                    vim.operator = Some((key, times));
+                    vim.register = register;
+                    return Ok(());
+                }
+            },
+            'x' | 'X' | 'D' | 'C' | 's' | 'Y' => {
+                let (op, motion) = match key {
+                    'x' => ('d', 'l'),
+                    'X' => ('d', 'h'),
+                    'D' => ('d', '$'),
+                    'C' => ('c', '$'),
+                    's' => ('c', 'l'),
+                    _ => ('y', 'Y'),
+                };
+                let motion = match motion {
+                    'Y' => Some(Motion {
+                        to: ((self.cy + times - 1).min(self.rows.len() - 1), 0),
+                        linewise: true,
+                        inclusive: false,
+                    }),
+                    // `x` and `s` take the last character of a line too.
+                    'l' => {
+                        let row = &self.rows[self.cy];
+                        let mut x = self.cx;
+                        for _ in 0..times {
+                            x = next_boundary(row, x);
+                        }
+                        Some(Motion { to: (self.cy, x), linewise: false, inclusive: false })
+                    }
+                    m => self.vim_motion(m, times, count, Some(op)),
+                };
+                if let Some(motion) = motion {
+                    self.vim.as_mut().unwrap().register = register;
+                    self.vim_operate(op, motion);
+                }
+            }
+            'o' | 'O' if self.read_only => self.set_status_msg(tr!(ReadOnly)),
+            'i' | 'a' | 'I' | 'A' | 'o' | 'O' => {
+                let row = self.rows.get(self.cy).map_or("", |row| row.as_str());
+                match key {
+                    'a' if self.cx < row.len() => self.cx = next_boundary(row, self.cx),
+                    'I' => self.cx = first_nonblank(row),
+                    'A' => self.cx = row.len(),
+                    'o' => {
+                        self.cx = row.len();
+                        if self.rows.is_empty() {
+                            self.insert_text("\n");
+                        } else {
+                            self.newline_and_indent();
+                        }
+                    }
+                    'O' => {
+                        self.cx = 0;
+                        self.insert_text("\n");
+                        self.cy -= 1;
+                    }
+                    _ => {}
+                }
+                self.vim_mode(Mode::Insert);
+                return Ok(());
+            }
+            'v' => {
+                self.vim_mode(Mode::Visual);
+                return Ok(());
+            }
+            'V' => {
+                self.vim_mode(Mode::VisualLine);
+                return Ok(());
+            }
+            'p' | 'P' => self.vim_put(key == 'p', register, times),
+            'u' => {
+                for _ in 0..times {
+                    self.undo();
+                }
+            }
+            ':' => self.run_action(Action::Command, None)?,
+            '/' => self.run_action(Action::Find, None)?,
+            _ => {}
+        }
+        if let Some(vim) = self.vim.as_mut() {
+            vim.reset();
+        }
+        if self.vim.as_ref().is_some_and(|v| v.mode == Mode::Normal) {
+            self.vim_clamp();
+        }
+        Ok(())
+    }
+
+    /// Where the motion `key` goes from the cursor, `times` over. `None`
+    /// when `key` isn't a motion. `count` is the count as typed, which `G`
+    /// and `gg` take as a line number. A change over words stops at their
+    /// end, as in vi.
+    fn vim_motion(&self, key: char, times: usize, count: Option<usize>, operator: Option<char>) -> Option<Motion> {
+        let rows = &self.rows;
+        let at = (self.cy.min(rows.len().saturating_sub(1)), self.cx);
+        let row = rows.get(at.0).map_or("", |row| row.as_str());
+        let charwise = |to, inclusive| Some(Motion { to, linewise: false, inclusive });
+        match key {
+            'h' => {
+                let mut x = at.1;
+                for _ in 0..times {
+                    x = prev_boundary(row, x);
+                }
+                charwise((at.0, x), false)
+            }
+            'l' => {
+                let mut x = at.1;
+                for _ in 0..times {
+                    let n = next_boundary(row, x);
+                    // Off the last character only for an operator.
+                    if n >= row.len() && operator.is_none() {
+                        break;
+                    }
+                    x = n;
+                }
+                charwise((at.0, x), false)
+            }
+            'j' | 'k' => {
+                let y = if key == 'j' { (at.0 + times).min(rows.len() - 1) } else { at.0.saturating_sub(times) };
+                Some(Motion { to: (y, at.1.min(rows[y].len())), linewise: true, inclusive: false })
+            }
+            'w' if operator == Some('c') && class(rows, at) != 0 => {
+                // The end of this word, even when the cursor is on it.
+                let mut to = at;
+                while let Some(n) = next(rows, to) {
+                    if n.0 != to.0 || class(rows, n) != class(rows, at) {
+                        break;
+                    }
+                    to = n;
+                }
+                for _ in 1..times {
+                    to = word_end(rows, to);
+                }
+                charwise(to, true)
+            }
+            'w' => {
+                let mut to = at;
+                for i in 0..times {
+                    let n = word_forward(rows, to);
+                    // An operator stops at the end of the line the last
+                    // word is on rather than going on to the next.
+                    if operator.is_some() && i + 1 == times && n.0 > to.0 {
+                        to = (to.0, rows[to.0].len());
+                        break;
+                    }
+                    to = n;
+                }
+                charwise(to, false)
+            }
+            'b' => {
+                let mut to = at;
+                for _ in 0..times {
+                    to = word_backward(rows, to);
+                }
+                charwise(to, false)
+            }
+            'e' => {
+                let mut to = at;
+                for _ in 0..times {
+                    to = word_end(rows, to);
+                }
+                charwise(to, true)
+            }
+            '0' => charwise((at.0, 0), false),
+            '^' => charwise((at.0, first_nonblank(row)), false),
+            '$' => {
+                let y = (at.0 + times - 1).min(rows.len() - 1);
+                let len = rows[y].len();
+                charwise((y, if len > 0 { prev_boundary(&rows[y], len) } else { 0 }), true)
+            }
+            'g' | 'G' => {
+                let line = match count {
+                    Some(n) => n.max(1).min(rows.len()),
+                    None if key == 'g' => 1,
+                    None => rows.len(),
+                };
+                let y = line - 1;
+                Some(Motion { to: (y, first_nonblank(&rows[y])), linewise: true, inclusive: false })
+            }
+            _ => None,
+        }
+    }
+
+    /// Deletes, changes or copies from the cursor to where `motion` goes.
+    fn vim_operate(&mut self, op: char, motion: Motion) {
+        let register = self.vim.as_mut().and_then(|vim| vim.register.take());
+        let here = (self.cy, self.cx);
+        let (start, end) = if motion.to < here { (motion.to, here) } else { (here, motion.to) };
+        if motion.linewise {
+            self.vim_lines(op, start.0, end.0, register);
+            return;
+        }
+        let end = if motion.inclusive {
+            (end.0, next_boundary(&self.rows[end.0], end.1))
+        } else {
+            end
+        };
+        self.vim_region(op, start, end, register);
+    }
+
+    /// Does `op` to the text from `start` to `end`.
+    fn vim_region(&mut self, op: char, start: (usize, usize), end: (usize, usize), register: Option<char>) {
+        let text = self.region_text(start, end);
+        if op == 'y' {
+            self.store_register(register, text, false);
+            self.cy = start.0;
+            self.cx = start.1;
+            return;
+        }
+        if self.read_only {
+            self.set_status_msg(tr!(ReadOnly));
+            return;
+        }
+        if start != end {
+            self.delete_region(start, end);
+            self.store_register(register, text, true);
+        }
+        self.cy = start.0;
+        self.cx = start.1;
+        if op == 'c' {
+            self.vim_mode(Mode::Insert);
+        }
+    }
+
+    /// Does `op` to lines `first` to `last`.
+    fn vim_lines(&mut self, op: char, first: usize, last: usize, register: Option<char>) {
+        let text = self.region_text((first, 0), (last + 1, 0));
+        let text = if text.ends_with('\n') { text } else { text + "\n" };
+        if op == 'y' {
+            self.store_register(register, text, false);
+            self.cy = first;
+            return;
+        }
+        if self.read_only {
+            self.set_status_msg(tr!(ReadOnly));
+            return;
+        }
+        self.store_register(register, text, true);
+        let end = (last, self.rows[last].len());
+        if op == 'c' {
+            // The lines go but one stays to type the new text on, indented
+            // as the first was.
+            let indent = self.rows[first][..first_nonblank(&self.rows[first])].to_string();
+            self.replace_region((first, 0), end, &indent);
+            self.vim_mode(Mode::Insert);
+        } else if last + 1 < self.rows.len() {
+            self.delete_region((first, 0), (last + 1, 0));
+        } else if first > 0 {
+            let before = (first - 1, self.rows[first - 1].len());
+            self.delete_region(before, end);
+            self.cy = first - 1;
+        } else {
+            self.delete_region((0, 0), end);
+        }
+        if op == 'd' {
+            self.cx = self.rows.get(self.cy).map_or(0, |row| first_nonblank(row));
+        }
+    }
+
+    /// Runs an operator on the visual selection, or swaps its ends with `o`.
+    fn vim_visual(&mut self, key: char, mode: Mode, register: Option<char>) -> Result<()> {
+        let visual = self.vim.as_ref().unwrap().visual;
+        let here = (self.cy, self.cx);
+        let (start, end) = if visual < here { (visual, here) } else { (here, visual) };
+        let op = match key {
+            'd' | 'x' => 'd',
+            'c' | 's' => 'c',
+            'y' => 'y',
+            'o' => {
+                self.vim.as_mut().unwrap().visual = here;
+                self.cy = visual.0;
+                self.cx = visual.1;
+                return Ok(());
+            }
+            'v' | 'V' => {
+                let other = if key == 'v' { Mode::Visual } else { Mode::VisualLine };
+                if mode == other {
+                    self.vim_mode(Mode::Normal);
+                } else {
+                    self.vim.as_mut().unwrap().mode = other;
+                }
+                return Ok(());
+            }
+            ':' => {
+                return self.run_action(Action::Command, None);
+            }
+            _ => return Ok(()),
+        };
+        self.vim_mode(Mode::Normal);
+        if mode == Mode::VisualLine {
+            self.vim_lines(op, start.0, end.0, register);
+        } else {
+            let end = (end.0, next_boundary(&self.rows[end.0], end.1));
+            self.vim_region(op, start, end, register);
+        }
+        if op != 'c' {
+            self.vim_clamp();
+        }
+        Ok(())
+    }
+
+    /// Puts the register's text after the cursor, or before it; whole
+    /// lines go below the line, or above.
+    fn vim_put(&mut self, after: bool, register: Option<char>, times: usize) {
+        let text = match register {
+            Some(r) => self.registers.get(&r.to_ascii_lowercase()).cloned(),
+            None => self.clipboard.last().cloned(),
+        };
+        let text = match text {
+            Some(text) => text.repeat(times),
+            None => {
+                self.set_status_msg(tr!(RegisterEmpty));
+                return;
+            }
+        };
+        if self.read_only {
+            self.set_status_msg(tr!(ReadOnly));
+            return;
+        }
+        if !text.ends_with('\n') {
+            if after && !self.rows.is_empty() && self.cx < self.rows[self.cy].len() {
+                self.cx = next_boundary(&self.rows[self.cy], self.cx);
+            }
+            self.insert_text(&text);
+            // On the last character put in.
+            if self.cx > 0 {
+                self.cx = prev_boundary(&self.rows[self.cy], self.cx);
+            }
+            return;
+        }
+        let y = if after { self.cy + 1 } else { self.cy };
+        if y < self.rows.len() || self.rows.is_empty() {
+            self.replace_region((y, 0), (y, 0), &text);
+        } else {
+            // Below the last line: the break goes before the text.
+            let end = (y - 1, self.rows[y - 1].len());
+            self.replace_region(end, end, &("\n".to_string() + text.trim_end_matches('\n')));
+        }
+        self.cy = y;
+        self.cx = self.rows.get(y).map_or(0, |row| first_nonblank(row));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(lines: &[&str]) -> Rows {
+        lines.iter().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn w_stops_at_words_punctuation_and_empty_lines() {
+        let r = rows(&["foo.bar  baz", "", "  qux"]);
+        let stops = [(0, 0), (0, 3), (0, 4), (0, 9), (1, 0), (2, 2), (2, 5)];
+        for pair in stops.windows(2) {
+            assert_eq!(word_forward(&r, pair[0]), pair[1], "w from {:?}", pair[0]);
+        }
+    }
+
+    #[test]
+    fn b_goes_back_to_word_starts() {
+        let r = rows(&["foo.bar  baz", "", "  qux"]);
+        let stops = [(2, 2), (1, 0), (0, 9), (0, 4), (0, 3), (0, 0), (0, 0)];
+        for pair in stops.windows(2) {
+            assert_eq!(word_backward(&r, pair[0]), pair[1], "b from {:?}", pair[0]);
+        }
+    }
+
+    #[test]
+    fn e_goes_to_word_ends() {
+        let r = rows(&["foo.bar  baz", "", "  qux"]);
+        let stops = [(0, 0), (0, 2), (0, 3), (0, 6), (0, 11), (2, 4), (2, 5)];
+        for pair in stops.windows(2) {
+            assert_eq!(word_end(&r, pair[0]), pair[1], "e from {:?}", pair[0]);
+        }
+    }
+
+    #[test]
+    fn motions_step_over_whole_characters() {
+        let r = rows(&["éa ü"]);
+        assert_eq!(next(&r, (0, 0)), Some((0, 2)));
+        assert_eq!(prev(&r, (0, 4)), Some((0, 3)));
+        assert_eq!(word_forward(&r, (0, 0)), (0, 4));
+        assert_eq!(word_backward(&r, (0, 6)), (0, 4));
+        assert_eq!(word_end(&r, (0, 0)), (0, 2));
+    }
+
+    #[test]
+    fn first_nonblank_skips_indentation() {
+        assert_eq!(first_nonblank(" \tx = 1"), 2);
+        assert_eq!(first_nonblank("x"), 0);
+        assert_eq!(first_nonblank("   "), 3);
+        assert_eq!(first_nonblank(""), 0);
+    }
+}
@@ -0,0 +1,85 @@
+//! Noticing when someone else changes the open file. The directory is
+//! watched rather than the file, since saving by renaming over it -- as
+//! we and many other programs do -- would leave a watch on the file
+//! looking at the old one.
+//!
+//! Events come in on the watcher's own thread; each relevant one writes a
+//! byte to a pipe, so the event loop wakes up as it would for a key.
+
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::path::{Path, PathBuf};
+
+use libc;
+use notify::{self, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+pub struct FileWatch {
+    /// The path as the buffer names it.
+    pub path: PathBuf,
+    _watcher: RecommendedWatcher,
+    /// Read end of the pipe written to on each change.
+    wake: File,
+}
+
+/// A pipe whose ends don't block and aren't passed on to commands run.
+pub(crate) fn pipe() -> io::Result<(File, File)> {
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let (read, write) = unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) };
+    for &fd in &fds {
+        unsafe {
+            libc::fcntl(fd, libc::F_SETFL, libc::fcntl(fd, libc::F_GETFL) | libc::O_NONBLOCK);
+            libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC);
+        }
+    }
+    Ok((read, write))
+}
+
+impl FileWatch {
+    pub fn new(path: &Path) -> notify::Result<FileWatch> {
+        // Through a symlink it's the file pointed at that gets replaced.
+        let target = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        let name = target.file_name().map(|n| n.to_os_string());
+        let dir = match target.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        let (wake, mut signal) = pipe()?;
+        let mut watcher = notify::recommended_watcher(move |res: notify::Result<Event>| {
+            let event = match res {
+                Ok(event) => event,
+                Err(_) => return,
+            };
+            if let EventKind::Access(_) = event.kind {
+                return;
+            }
+            if event.paths.iter().any(|p| p.file_name() == name.as_deref()) {
+                // A full pipe already has a wakeup waiting.
+                let _ = signal.write(&[1]);
+            }
+        })?;
+        watcher.watch(&dir, RecursiveMode::NonRecursive)?;
+        Ok(FileWatch { path: path.to_path_buf(), _watcher: watcher, wake })
+    }
+
+    /// For `poll` to wait on along with the keyboard.
+    pub fn fd(&self) -> RawFd {
+        self.wake.as_raw_fd()
+    }
+
+    /// Whether the file was touched since the last call.
+    pub fn changed(&mut self) -> bool {
+        let mut buf = [0; 64];
+        let mut changed = false;
+        while let Ok(n) = self.wake.read(&mut buf) {
+            if n == 0 {
+                break;
+            }
+            changed = true;
+        }
+        changed
+    }
+}